@@ -45,6 +45,7 @@ import Splitter        from './objects/splitter.js';
 import Compressor      from './objects/compressor.js';
 import DattoroReverb   from './objects/dattoro-reverb.js';
 import Delay           from './objects/delay.js';
+import DynamicEQ       from './objects/dynamic-eq.js';
 import EQ              from './objects/eq.js';
 import Filter          from './objects/filter.js';
 import FlangerObject   from './objects/flanger.js';
@@ -76,6 +77,7 @@ Soundstage.register(
     Compressor,
     DattoroReverb,
     Delay,
+    DynamicEQ,
     EQ,
     Filter,
     FlangerObject,
@@ -13,10 +13,12 @@ function toDataType(e) {
 
 export default class DynamicsProcessor extends AudioWorkletNode {
     #reduction = 0;
+    #meter = {};
+    #settingsRequests = [];
 
     constructor(context, options = {}) {
         const defaultOptions = {
-            numberOfInputs: 2,  // Input 1 is for sidechain
+            numberOfInputs: 2,  // Input 1 is for sidechain, the key when ducking
             numberOfOutputs: 1,
             channelCount: 2,
             channelCountMode: 'explicit',
@@ -39,13 +41,34 @@ export default class DynamicsProcessor extends AudioWorkletNode {
             attack: 'attack',
             release: 'release',
             makeup: 'makeup',
+            makeupMode: 'makeupMode',
+            autoRelease: 'autoRelease',
             outputGain: 'outputGain',
             lookahead: 'lookahead',
             mix: 'mix',
+            wetGain: 'wetGain',
+            dryGain: 'dryGain',
+            wetHighpass: 'wetHighpass',
+            wetLowpass: 'wetLowpass',
+            range: 'range',
+            hold: 'hold',
+            hysteresis: 'hysteresis',
+            rampTime: 'rampTime',
             // Enum parameters handled separately
             mode: 'mode',
             character: 'character',
+            customCurve: 'customCurve',
+            topology: 'topology',
+            drive: 'drive',
+            colour: 'colour',
+            oversampling: 'oversampling',
             detectionMode: 'detectionMode',
+            detectionSource: 'detectionSource',
+            weighting: 'weighting',
+            rmsWindow: 'rmsWindow',
+            // Stereo linking parameters
+            linkMode: 'linkMode',
+            linkAmount: 'linkAmount',
             // Sidechain parameters
             sidechainExternal: 'sidechainExternal',
             sidechainFilter: 'sidechainFilter',
@@ -68,6 +91,15 @@ export default class DynamicsProcessor extends AudioWorkletNode {
                 // Convert gain reduction (0-1) to dB for the UI (negative values)
                 const gain = e.data.value;
                 this.#reduction = gain <= 0 ? -120 : 20 * Math.log10(gain);
+            },
+            'meter': (e) => {
+                // Levels and reductions in gain domain
+                const { type, ...meter } = e.data;
+                this.#meter = meter;
+            },
+            'settings': (e) => {
+                const resolve = this.#settingsRequests.shift();
+                if (resolve) resolve(e.data.settings);
             }
         });
     }
@@ -76,10 +108,64 @@ export default class DynamicsProcessor extends AudioWorkletNode {
         return this.#reduction;
     }
 
+    get meter() {
+        return this.#meter;
+    }
+
     setParameter(name, value) {
         this.port.postMessage({ type: 'param', name, value });
     }
 
+    // Schedule a parameter to reach value at a context time, in the same
+    // units as setParameter. Curve is 'step', 'linear' or 'exponential',
+    // ramping from the previous scheduled value as with AudioParams.
+    // Automatable parameters are threshold, ratio, knee, makeup,
+    // outputGain, mix, wetGain and dryGain.
+    scheduleParameter(name, value, time, curve = 'step') {
+        this.port.postMessage({ type: 'schedule', name, value, time, curve });
+    }
+
+    // Replace the character's static curve with a curve drawn through
+    // points, an array of interleaved input and output levels in dB.
+    // Interpolation is 'linear' or 'spline'. Threshold, ratio and knee do
+    // not apply to a custom curve. Pass null to restore the character's
+    // curve.
+    setCustomCurve(points, interpolation = 'spline') {
+        this.setParameter('customCurve', points && { points, interpolation });
+    }
+
+    cancelScheduledParameters() {
+        this.port.postMessage({ type: 'cancel' });
+    }
+
+    // Settings are an object of every processor setting with units in the
+    // names, as JSON-serialised by the processor, for saving with projects.
+    // Missing settings take their defaults when applied. Settings arrive
+    // from the worklet, so getSettings() returns a promise.
+    getSettings() {
+        return new Promise((resolve) => {
+            this.#settingsRequests.push(resolve);
+            this.port.postMessage({ type: 'get-settings' });
+        });
+    }
+
+    applySettings(settings) {
+        this.port.postMessage({ type: 'settings', settings });
+    }
+
+    applyPreset(name) {
+        this.port.postMessage({ type: 'settings', preset: name });
+    }
+
+    static presets = [
+        'vocal-leveller',
+        'drum-bus-glue',
+        'mastering-limiter',
+        'podcast-gate',
+        'parallel-drums',
+        'voice-over-duck'
+    ];
+
     static async preload(context) {
         if (!modulePromise) {
             modulePromise = (async () => {
@@ -102,20 +188,64 @@ export default class DynamicsProcessor extends AudioWorkletNode {
         outputGain: { min: -20, max: 20, default: 0, unit: 'dB' },
         lookahead: { min: 0, max: 50, default: 0, unit: 'ms' },
         mix: { min: 0, max: 1, default: 1 },
+        range: { min: 0, max: 60, default: 12, unit: 'dB' },
+        hold: { min: 0, max: 2, default: 0, unit: 's' },
+        hysteresis: { min: 0, max: 20, default: 0, unit: 'dB' },
+        rampTime: { min: 0, max: 0.5, default: 0.02, unit: 's' },
+        autoRelease: { type: 'boolean', default: false },
+        rmsWindow: { min: 1, max: 300, default: 50, law: 'log', unit: 'ms' },
+        
+        // Parallel compression parameters
+        wetGain: { min: -60, max: 12, default: 0, unit: 'dB' },
+        dryGain: { min: -60, max: 12, default: 0, unit: 'dB' },
+        wetHighpass: { min: 0, max: 2000, default: 0, unit: 'Hz' },
+        wetLowpass: { min: 0, max: 20000, default: 0, unit: 'Hz' },
+        
+        // Saturation parameters
+        drive: { min: 0, max: 0.25, default: 0 },
+        colour: { min: 0, max: 1, default: 0.5 },
+        oversampling: { type: 'boolean', default: false },
         
         // Enum parameters
         mode: { 
-            options: ['compress', 'expand', 'gate'],
+            options: ['compress', 'expand', 'gate', 'duck'],
             default: 'compress'
         },
+        makeupMode: {
+            options: ['manual', 'static', 'measured'],
+            default: 'manual'
+        },
         character: { 
             options: ['clean', 'smooth', 'punchy', 'vintage'],
             default: 'clean'
         },
+        topology: {
+            options: ['feedforward', 'feedback', 'hybrid'],
+            default: 'feedforward'
+        },
         detectionMode: { 
-            options: ['peak', 'rms', 'logrms'],
+            options: ['peak', 'rms', 'logrms', 'adaptive'],
             default: 'rms'
         },
+        // Stereo pairs may both detect from mid or side
+        detectionSource: {
+            options: ['channel', 'mid', 'side'],
+            default: 'channel'
+        },
+        weighting: {
+            options: ['flat', 'a', 'c', 'k'],
+            default: 'flat'
+        },
+        
+        // Stereo linking parameters, channels share one gain when fully
+        // linked in max or average mode, keeping the stereo image still.
+        // Midside links mid and side towards the louder by the amount, so
+        // reduces them separately only below 1
+        linkMode: {
+            options: ['independent', 'max', 'average', 'midside'],
+            default: 'max'
+        },
+        linkAmount: { min: 0, max: 1, default: 1 },
         
         // Sidechain parameters
         sidechainExternal: { type: 'boolean', default: false },
@@ -124,6 +254,7 @@ export default class DynamicsProcessor extends AudioWorkletNode {
         sidechainQ: { min: 0.1, max: 10, default: 0.7 },
         
         // Read-only property
-        reduction: { readonly: true }
+        reduction: { readonly: true },
+        meter: { readonly: true }
     };
 }
\ No newline at end of file
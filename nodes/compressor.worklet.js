@@ -10,6 +10,27 @@ function dbToGain(db) {
     return Math.pow(10, db / 20);
 }
 
+const empty = new Float32Array(0);
+
+// Automatable parameters, with the index of the processor's Param enum and
+// a conversion from parameter values to the Param's units. Parameters
+// arrive in the units of compressor.js, which match the Params.
+const identity = (value) => value;
+const automationParams = {
+    threshold:  [0, identity],
+    ratio:      [1, identity],
+    knee:       [2, identity],
+    makeup:     [3, identity],
+    outputGain: [4, identity],
+    mix:        [5, identity],
+    wetGain:    [6, identity],
+    dryGain:    [7, identity]
+};
+
+const curveMap = { 'step': 0, 'linear': 1, 'exponential': 2 };
+
+const interpolationMap = { 'linear': 0, 'spline': 1 };
+
 class DynamicsProcessorProcessor extends AudioWorkletProcessor {
     constructor(options) {
         super();
@@ -17,36 +38,79 @@ class DynamicsProcessorProcessor extends AudioWorkletProcessor {
         // Process options
         this.options = options.processorOptions || {};
         this.wasmModule = null;
-        this.processor = null;
+        this.wasm = null;
+        this.processor = null;           // Linked processor for all channels
+        this.pendingSettings = null;     // Settings or preset sent before the processor exists
         
-        // Default values - all in gain domain (0-1) unless specified
-        this.threshold = 0.125;          // gain value (~-18dB)
+        // Default values, in the units of compressor.js
+        this.threshold = -18;            // dB
         this.ratio = 4;                  // compression ratio (n:1)
-        this.knee = 0.5;                 // gain value (~6dB)
+        this.knee = 6;                   // dB, 0 for a hard knee
         this.attack = 0.003;             // seconds
         this.release = 0.25;             // seconds
-        this.makeup = 1.0;               // gain multiplier
-        this.outputGain = 1.0;           // gain multiplier
+        this.makeup = 0;                 // dB
+        this.makeupMode = 0;             // 0=manual, 1=static, 2=measured
+        this.autoRelease = false;
+        this.outputGain = 0;             // dB
         this.lookahead = 0;              // ms
-        this.mode = 0;                   // 0=compress, 1=expand, 2=gate
+        this.mode = 0;                   // 0=compress, 1=expand, 2=gate, 3=duck
+        this.range = 12;                 // Ducking depth in dB
+        this.hold = 0;                   // Ducking hold in seconds
+        this.hysteresis = 0;             // Gate hysteresis in dB
         this.character = 0;              // 0=clean, 1=smooth, 2=punchy, 3=vintage
+        this.customCurve = empty;        // Interleaved input and output dB, empty for the character's curve
+        this.curveInterpolation = 0;     // 0=linear, 1=spline
+        this.drive = null;               // Saturation drive, null for character default
+        this.colour = null;              // Saturation colour, null for character default
+        this.oversampling = false;
+        this.topology = null;            // 0=feedforward, 1=feedback, 2=hybrid, null for character default
         this.mix = 1.0;                  // 0=dry, 1=wet
-        this.detectionMode = 1;          // 0=peak, 1=RMS, 2=logRMS
+        this.wetGain = 0;                // dB
+        this.dryGain = 0;                // dB
+        this.wetHighpass = 0;            // Hz, 0 for off
+        this.wetLowpass = 0;             // Hz, 0 for off
+        this.detectionMode = 1;          // 0=peak, 1=RMS, 2=logRMS, 3=adaptive
+        this.detectionSource = 0;        // 0=channel, 1=mid, 2=side
+        this.weighting = 0;              // 0=flat, 1=A, 2=C, 3=K
+        this.rmsWindow = 50;             // ms
         this.sidechainExternal = false;
         this.sidechainFilter = false;
         this.sidechainFreq = 1000;       // Hz
         this.sidechainQ = 0.7;
+        this.rampTime = 0.02;            // Parameter smoothing in seconds
+        this.linkMode = 1;               // 0=independent, 1=max, 2=average, 3=midside
+        this.linkAmount = 1;             // 0=unlinked, 1=fully linked, also between mid and side
         
         // Gain reduction metering
         this.currentReduction = 1.0;     // Gain reduction as multiplier (1.0 = no reduction)
         this.meterUpdateCounter = 0;
         
+        // Planar views of the processor's shared buffers in wasm memory,
+        // one channel after another. Passing arrays to process_block
+        // instead would copy them through __wbindgen_malloc every block.
+        this.blockLength = 0;
+        this.inputBuffer = empty;
+        this.sidechainBuffer = empty;
+        this.outputBuffer = empty;
+        this.reductionTrace = empty;
+        
         // Setup message handling
         this.port.onmessage = (e) => {
             if (e.data instanceof ArrayBuffer) {
                 this.initWasm(e.data);
             } else if (e.data && e.data.type === 'param') {
                 this.updateParameter(e.data.name, e.data.value);
+            } else if (e.data && e.data.type === 'schedule') {
+                this.scheduleParameter(e.data.name, e.data.value, e.data.time, e.data.curve);
+            } else if (e.data && e.data.type === 'cancel') {
+                if (this.processor) this.processor.cancel_scheduled_params();
+            } else if (e.data && e.data.type === 'settings') {
+                this.applySettings(e.data.settings, e.data.preset);
+            } else if (e.data && e.data.type === 'get-settings') {
+                this.port.postMessage({
+                    type: 'settings',
+                    settings: this.processor ? JSON.parse(this.processor.get_settings()) : null
+                });
             }
         };
     }
@@ -67,11 +131,17 @@ class DynamicsProcessorProcessor extends AudioWorkletProcessor {
             
             this.wasmModule = wasmModule;
             
-            // Create the processor - the class is still named DynamicsProcessor in the Rust code
-            this.processor = wasm.DynamicsProcessor.new(sampleRate);
+            this.wasm = wasm;
+            
+            // Create a processor for a stereo input, channels are added
+            // when more arrive
+            this.processor = wasm.MultichannelDynamics.new(sampleRate, 2);
+            this.updateAllParameters(this.processor);
             
-            // Set default parameters
-            this.updateAllParameters();
+            if (this.pendingSettings) {
+                this.applySettings(this.pendingSettings.settings, this.pendingSettings.preset);
+                this.pendingSettings = null;
+            }
             
             // Notify that module is loaded
             this.port.postMessage({ type: 'wasm-module-loaded' });
@@ -84,122 +154,301 @@ class DynamicsProcessorProcessor extends AudioWorkletProcessor {
         }
     }
     
-    updateAllParameters() {
-        if (!this.processor) return;
-        
-        this.processor.set_threshold(this.threshold);
-        this.processor.set_ratio(this.ratio);
-        this.processor.set_knee_width(this.knee);
-        this.processor.set_attack_time(this.attack);
-        this.processor.set_release_time(this.release);
-        this.processor.set_makeup_gain(this.makeup);
-        this.processor.set_output_gain(this.outputGain);
-        this.processor.set_lookahead_ms(this.lookahead);
-        this.processor.set_mode(this.mode);
-        this.processor.set_character(this.character);
-        this.processor.set_mix(this.mix);
-        this.processor.set_detection_mode(this.detectionMode);
-        this.processor.set_sidechain_external(this.sidechainExternal);
-        this.processor.set_sidechain_filter_enabled(this.sidechainFilter);
-        this.processor.set_sidechain_filter_freq(this.sidechainFreq);
-        this.processor.set_sidechain_filter_q(this.sidechainQ);
+    updateAllParameters(processor) {
+        processor.set_ramp_time(this.rampTime);
+        processor.set_threshold_db(this.threshold);
+        processor.set_ratio(this.ratio);
+        processor.set_knee_db(this.knee);
+        processor.set_attack_time(this.attack);
+        processor.set_release_time(this.release);
+        processor.set_makeup_gain_db(this.makeup);
+        processor.set_makeup_mode(this.makeupMode);
+        processor.set_auto_release(this.autoRelease);
+        processor.set_output_gain_db(this.outputGain);
+        processor.set_lookahead_ms(this.lookahead);
+        processor.set_mode(this.mode);
+        processor.set_range_db(this.range);
+        processor.set_hold_time(this.hold);
+        processor.set_hysteresis_db(this.hysteresis);
+        processor.set_character(this.character);
+        processor.set_custom_curve(this.customCurve, this.curveInterpolation);
+        if (this.drive !== null) processor.set_drive(this.drive);
+        if (this.colour !== null) processor.set_colour(this.colour);
+        processor.set_oversampling(this.oversampling);
+        if (this.topology !== null) processor.set_topology(this.topology);
+        processor.set_mix(this.mix);
+        processor.set_wet_gain_db(this.wetGain);
+        processor.set_dry_gain_db(this.dryGain);
+        processor.set_wet_highpass(this.wetHighpass);
+        processor.set_wet_lowpass(this.wetLowpass);
+        processor.set_detection_mode(this.detectionMode);
+        processor.set_detection_source(this.detectionSource);
+        processor.set_weighting(this.weighting);
+        processor.set_rms_window_ms(this.rmsWindow);
+        processor.set_sidechain_external(this.sidechainExternal);
+        processor.set_sidechain_filter_enabled(this.sidechainFilter);
+        processor.set_sidechain_filter_freq(this.sidechainFreq);
+        processor.set_sidechain_filter_q(this.sidechainQ);
+        processor.set_link_mode(this.linkMode);
+        processor.set_link_amount(this.linkAmount);
     }
     
     updateParameter(name, value) {
-        if (!this.processor) return;
+        if (this.processor) {
+            this.setProcessorParameter(this.processor, name, value);
+        }
+    }
+    
+    scheduleParameter(name, value, time, curve = 'step') {
+        if (!(name in automationParams)) return;
+        
+        const [param, toParamUnits] = automationParams[name];
         
+        // Offset from the next frame processed, events already past start
+        // now. Channels added later start at the scheduled values.
+        const offset = Math.max(0, Math.round(time * sampleRate) - currentFrame);
+        this[name] = value;
+        
+        if (this.processor) {
+            this.processor.schedule_param(param, toParamUnits(value), offset, curveMap[curve] ?? 0);
+        }
+    }
+    
+    applySettings(settings, preset) {
+        // Keep the latest settings until the processor is created
+        if (!this.processor) {
+            this.pendingSettings = { settings, preset };
+            return;
+        }
+        
+        try {
+            if (preset) {
+                if (!this.processor.apply_preset(preset)) throw new Error('Unknown preset ' + preset);
+            } else {
+                this.processor.apply_settings(JSON.stringify(settings));
+            }
+        } catch (err) {
+            this.port.postMessage({ type: 'error', message: err.toString() });
+        }
+    }
+    
+    setProcessorParameter(processor, name, value) {
         switch (name) {
             case 'threshold':
-                this.threshold = value; // Already in gain domain (0-1)
-                this.processor.set_threshold(value);
+                this.threshold = value; // dB
+                processor.set_threshold_db(value);
                 break;
                 
             case 'ratio':
                 this.ratio = value;
-                this.processor.set_ratio(value);
+                processor.set_ratio(value);
                 break;
                 
             case 'knee':
-                this.knee = value; // Gain value, not dB
-                this.processor.set_knee_width(value);
+                this.knee = value; // dB
+                processor.set_knee_db(value);
                 break;
                 
             case 'attack':
                 this.attack = value;
-                this.processor.set_attack_time(value);
+                processor.set_attack_time(value);
                 break;
                 
             case 'release':
                 this.release = value;
-                this.processor.set_release_time(value);
+                processor.set_release_time(value);
                 break;
                 
             case 'makeup':
-                this.makeup = value; // Gain multiplier
-                this.processor.set_makeup_gain(value);
+                this.makeup = value; // dB
+                processor.set_makeup_gain_db(value);
+                break;
+                
+            case 'makeupMode':
+                // Convert makeup mode string to enum index
+                const makeupMap = { 'manual': 0, 'static': 1, 'measured': 2 };
+                this.makeupMode = makeupMap[value] || 0;
+                processor.set_makeup_mode(this.makeupMode);
+                break;
+                
+            case 'autoRelease':
+                this.autoRelease = !!value;
+                processor.set_auto_release(this.autoRelease);
                 break;
                 
             case 'outputGain':
-                this.outputGain = value; // Gain multiplier
-                this.processor.set_output_gain(value);
+                this.outputGain = value; // dB
+                processor.set_output_gain_db(value);
                 break;
                 
             case 'lookahead':
                 this.lookahead = value;
-                this.processor.set_lookahead_ms(value);
+                processor.set_lookahead_ms(value);
                 break;
                 
             case 'mode':
                 // Convert mode string to enum index
-                const modeMap = { 'compress': 0, 'expand': 1, 'gate': 2 };
+                const modeMap = { 'compress': 0, 'expand': 1, 'gate': 2, 'duck': 3 };
                 this.mode = modeMap[value] || 0;
-                this.processor.set_mode(this.mode);
+                processor.set_mode(this.mode);
+                // Ducking enables external sidechain
+                if (this.mode === 3) this.sidechainExternal = true;
+                break;
+                
+            case 'range':
+                this.range = value; // dB
+                processor.set_range_db(value);
+                break;
+                
+            case 'hold':
+                this.hold = value; // seconds
+                processor.set_hold_time(value);
+                break;
+                
+            case 'hysteresis':
+                this.hysteresis = value; // dB
+                processor.set_hysteresis_db(value);
                 break;
                 
             case 'character':
                 // Convert character string to enum index
                 const charMap = { 'clean': 0, 'smooth': 1, 'punchy': 2, 'vintage': 3 };
                 this.character = charMap[value] || 0;
-                this.processor.set_character(this.character);
+                // Drive, colour and topology set explicitly are kept
+                processor.set_character(this.character);
+                break;
+                
+            case 'customCurve':
+                // { points, interpolation } with points interleaved input
+                // and output dB, or null for the character's curve
+                this.customCurve = value && value.points ? Float32Array.from(value.points) : empty;
+                this.curveInterpolation = value ? interpolationMap[value.interpolation] || 0 : 0;
+                processor.set_custom_curve(this.customCurve, this.curveInterpolation);
+                break;
+                
+            case 'topology':
+                // Convert topology string to enum index
+                const topologyMap = { 'feedforward': 0, 'feedback': 1, 'hybrid': 2 };
+                this.topology = topologyMap[value] || 0;
+                processor.set_topology(this.topology);
+                break;
+                
+            case 'drive':
+                this.drive = value;
+                processor.set_drive(value);
+                break;
+                
+            case 'colour':
+                this.colour = value;
+                processor.set_colour(value);
+                break;
+                
+            case 'oversampling':
+                this.oversampling = !!value;
+                processor.set_oversampling(this.oversampling);
                 break;
                 
             case 'mix':
                 this.mix = value;
-                this.processor.set_mix(value);
+                processor.set_mix(value);
+                break;
+                
+            case 'wetGain':
+                this.wetGain = value; // dB
+                processor.set_wet_gain_db(value);
+                break;
+                
+            case 'dryGain':
+                this.dryGain = value; // dB
+                processor.set_dry_gain_db(value);
+                break;
+                
+            case 'wetHighpass':
+                this.wetHighpass = value;
+                processor.set_wet_highpass(value);
+                break;
+                
+            case 'wetLowpass':
+                this.wetLowpass = value;
+                processor.set_wet_lowpass(value);
                 break;
                 
             case 'detectionMode':
                 // Convert detection mode string to enum index
-                const detectionMap = { 'peak': 0, 'rms': 1, 'logrms': 2 };
-                this.detectionMode = detectionMap[value] || 1;
-                this.processor.set_detection_mode(this.detectionMode);
+                const detectionMap = { 'peak': 0, 'rms': 1, 'logrms': 2, 'adaptive': 3 };
+                this.detectionMode = detectionMap[value] ?? 1;
+                processor.set_detection_mode(this.detectionMode);
+                break;
+                
+            case 'detectionSource':
+                // Convert detection source string to enum index
+                const sourceMap = { 'channel': 0, 'mid': 1, 'side': 2 };
+                this.detectionSource = sourceMap[value] || 0;
+                processor.set_detection_source(this.detectionSource);
+                break;
+                
+            case 'weighting':
+                // Convert weighting string to enum index
+                const weightingMap = { 'flat': 0, 'a': 1, 'c': 2, 'k': 3 };
+                this.weighting = weightingMap[value] || 0;
+                processor.set_weighting(this.weighting);
+                break;
+                
+            case 'rmsWindow':
+                this.rmsWindow = value;
+                processor.set_rms_window_ms(value);
                 break;
                 
             case 'sidechainExternal':
                 this.sidechainExternal = !!value;
-                this.processor.set_sidechain_external(this.sidechainExternal);
+                processor.set_sidechain_external(this.sidechainExternal);
                 break;
                 
             case 'sidechainFilter':
                 this.sidechainFilter = !!value;
-                this.processor.set_sidechain_filter_enabled(this.sidechainFilter);
+                processor.set_sidechain_filter_enabled(this.sidechainFilter);
                 break;
                 
             case 'sidechainFreq':
                 this.sidechainFreq = value;
-                this.processor.set_sidechain_filter_freq(value);
+                processor.set_sidechain_filter_freq(value);
                 break;
                 
             case 'sidechainQ':
                 this.sidechainQ = value;
-                this.processor.set_sidechain_filter_q(value);
+                processor.set_sidechain_filter_q(value);
+                break;
+                
+            case 'rampTime':
+                this.rampTime = value;
+                processor.set_ramp_time(value);
+                break;
+                
+            case 'linkMode':
+                // Convert link mode string to enum index
+                const linkMap = { 'independent': 0, 'max': 1, 'average': 2, 'midside': 3 };
+                this.linkMode = linkMap[value] ?? 1;
+                processor.set_link_mode(this.linkMode);
+                break;
+                
+            case 'linkAmount':
+                this.linkAmount = value;
+                processor.set_link_amount(value);
                 break;
         }
     }
     
+    mapBuffers(size) {
+        const memory = this.wasm.memory.buffer;
+        this.inputBuffer = new Float32Array(memory, this.processor.input_ptr(), size);
+        this.sidechainBuffer = new Float32Array(memory, this.processor.sidechain_ptr(), size);
+        this.outputBuffer = new Float32Array(memory, this.processor.output_ptr(), size);
+        this.reductionTrace = new Float32Array(memory, this.processor.gain_reduction_ptr(), size);
+    }
+    
     process(inputs, outputs, parameters) {
         // If WASM module isn't loaded yet, pass audio through
-        if (!this.processor) {
+        if (!this.wasm) {
             // Pass through
             if (inputs[0] && outputs[0]) {
                 for (let channel = 0; channel < inputs[0].length; channel++) {
@@ -214,53 +463,54 @@ class DynamicsProcessorProcessor extends AudioWorkletProcessor {
         const input = inputs[0];
         const output = outputs[0];
         
-        // Optional sidechain input
+        // Optional sidechain input, the key for ducking. A mono key such
+        // as a voice-over keys every channel.
         const sidechain = inputs[1];
         
         if (!input || !output) return true;
         
-        // Number of channels to process (mono or stereo)
+        // Number of channels to process, linked by the processor
         const channels = Math.min(input.length, output.length);
+        if (!channels) return true;
         
-        // Process each channel
-        let totalReduction = 0;
+        const length = input[0].length;
+        const keys = sidechain ? Math.min(sidechain.length, channels) : 0;
+        
+        if (this.processor.channel_count() !== channels || this.blockLength !== length) {
+            this.processor.set_channel_count(channels);
+            this.processor.set_block_size(length);
+            this.blockLength = length;
+            this.mapBuffers(channels * length);
+        } else if (this.inputBuffer.length === 0) {
+            // Views are detached when wasm memory grows
+            this.mapBuffers(channels * length);
+        }
         
         for (let channel = 0; channel < channels; channel++) {
-            const inputChannel = input[channel];
-            const outputChannel = output[channel];
-            
-            // Optional sidechain input channel
-            const sidechainChannel = sidechain && sidechain[channel] ? sidechain[channel] : null;
-            
-            if (!inputChannel || !outputChannel) continue;
-            
-            // Process each sample
-            for (let i = 0; i < inputChannel.length; i++) {
-                // Get sidechain input if available
-                const sidechainInput = sidechainChannel ? sidechainChannel[i] : null;
-                
-                // Process through WASM compressor - now returns only the processed sample
-                const processedSample = this.processor.process_sample(
-                    inputChannel[i],
-                    sidechainInput
-                );
-                
-                // Get the gain reduction as a linear gain multiplier (0.0 to 1.0)
-                const gainReduction = this.processor.get_gain_reduction();
-                
-                // Write to output
-                outputChannel[i] = processedSample;
-                
-                // Track reduction for metering (as a gain multiplier)
-                totalReduction += gainReduction;
-            }
+            this.inputBuffer.set(input[channel], channel * length);
         }
         
-        // Calculate average reduction for this block
-        if (input[0] && input[0].length > 0) {
-            this.currentReduction = totalReduction / (channels * input[0].length);
+        for (let channel = 0; channel < keys; channel++) {
+            this.sidechainBuffer.set(sidechain[channel], channel * length);
         }
         
+        // Process the block through WASM compressor, tracing the gain
+        // reduction of each channel as a linear gain multiplier (0.0 to 1.0)
+        this.processor.process_buffers(keys);
+        
+        for (let channel = 0; channel < channels; channel++) {
+            output[channel].set(this.outputBuffer.subarray(channel * length, (channel + 1) * length));
+        }
+        
+        // Calculate average reduction for this block (as a gain multiplier)
+        let totalReduction = 0;
+        
+        for (let i = 0; i < this.reductionTrace.length; i++) {
+            totalReduction += this.reductionTrace[i];
+        }
+        
+        this.currentReduction = totalReduction / this.reductionTrace.length;
+        
         // Send gain reduction value to main thread occasionally (every ~3ms)
         // Value is in gain-domain (a multiplier, not dB)
         this.meterUpdateCounter++;
@@ -269,6 +519,23 @@ class DynamicsProcessorProcessor extends AudioWorkletProcessor {
                 type: 'reduction',
                 value: this.currentReduction // Linear gain multiplier (0.0 to 1.0)
             });
+            
+            // Levels over the last block, all in gain domain. The meter is
+            // a copy in wasm memory, freed once read.
+            const meter = this.processor.get_meter(0);
+            this.port.postMessage({
+                type: 'meter',
+                inputPeak: meter.input_peak,
+                inputRms: meter.input_rms,
+                outputPeak: meter.output_peak,
+                outputRms: meter.output_rms,
+                minReduction: meter.min_gain_reduction,
+                meanReduction: meter.mean_gain_reduction,
+                detectorLevel: meter.detector_level
+            });
+            
+            meter.free();
+            
             this.meterUpdateCounter = 0;
         }
         
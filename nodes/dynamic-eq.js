@@ -0,0 +1,106 @@
+import overload from '../../fn/modules/overload.js';
+import { rslashfilename } from '../modules/regexp.js';
+
+const wasmURL    = import.meta.url.replace(rslashfilename, '/dynamics/pkg/dynamics_bg.wasm');
+const workletURL = import.meta.url.replace(rslashfilename, '/dynamic-eq.worklet.js');
+
+let wasmBuffer;
+let modulePromise;
+
+function toDataType(e) {
+    return e.data?.type;
+}
+
+export default class DynamicEq extends AudioWorkletNode {
+    #bandCount;
+    #gains = [];
+
+    constructor(context, options = {}) {
+        const defaultOptions = {
+            numberOfInputs: 1,
+            numberOfOutputs: 1,
+            channelCount: 2,
+            channelCountMode: 'explicit',
+            channelInterpretation: 'speakers',
+            processorOptions: options
+        };
+
+        super(context, 'dynamic-eq', defaultOptions);
+        this.#bandCount = options.bandCount || 4;
+
+        if (wasmBuffer) {
+            this.port.postMessage(wasmBuffer);
+        }
+
+        // Set initial band parameters from options.bands, an array of
+        // objects of band parameters
+        if (options.bands) {
+            options.bands.forEach((params, band) => {
+                for (const name in params) {
+                    this.setBandParameter(band, name, params[name]);
+                }
+            });
+        }
+
+        this.port.onmessage = overload(toDataType, {
+            'wasm-module-loaded': (e) => console.log('WASM module loaded successfully in dynamic EQ worklet'),
+            'error': (e) => console.error('DynamicEq error from worklet:', e.data.message),
+            // Current band gains in dB
+            'gains': (e) => this.#gains = e.data.value
+        });
+    }
+
+    get bandCount() {
+        return this.#bandCount;
+    }
+
+    set bandCount(value) {
+        this.#bandCount = value;
+        this.port.postMessage({ type: 'band-count', value });
+    }
+
+    // Current gain of each band in dB, its static gain moved by the level
+    // in the band
+    get gains() {
+        return this.#gains;
+    }
+
+    // Set a band parameter, one of shape ('bell', 'lowshelf', 'highshelf'),
+    // mode ('above', 'below'), frequency in Hz, q, gain in dB, threshold in
+    // dB, ratio, range in dB (negative to cut, positive to boost), attack
+    // and release in seconds, or bypass
+    setBandParameter(band, name, value) {
+        this.port.postMessage({ type: 'param', band, name, value });
+    }
+
+    static async preload(context) {
+        if (!modulePromise) {
+            modulePromise = (async () => {
+                const wasmResponse = await fetch(wasmURL);
+                wasmBuffer = await wasmResponse.arrayBuffer();
+                return context.audioWorklet.addModule(workletURL);
+            })();
+        }
+        return modulePromise;
+    }
+
+    static config = {
+        bandCount: { min: 1, max: 8, default: 4 },
+
+        // Band parameters
+        shape: { options: ['bell', 'lowshelf', 'highshelf'], default: 'bell' },
+        mode: { options: ['above', 'below'], default: 'above' },
+        frequency: { min: 20, max: 20000, law: 'log', unit: 'Hz' },
+        q: { min: 0.1, max: 20, default: 2, law: 'log' },
+        gain: { min: -24, max: 24, default: 0, unit: 'dB' },
+        threshold: { min: -60, max: 0, default: -30, unit: 'dB' },
+        ratio: { min: 1, max: 20, default: 4 },
+        range: { min: -24, max: 24, default: -12, unit: 'dB' },
+        attack: { min: 0.0001, max: 0.5, default: 0.005, law: 'log-36db', unit: 's' },
+        release: { min: 0.01, max: 2, default: 0.1, law: 'log-36db', unit: 's' },
+        bypass: { type: 'boolean', default: false },
+
+        // Read-only property
+        gains: { readonly: true }
+    };
+}
@@ -0,0 +1,170 @@
+// Dynamic EQ - AudioWorklet
+// Bell and shelf bands whose gains follow the level in their own band
+
+const shapeMap = { 'bell': 0, 'lowshelf': 1, 'highshelf': 2 };
+const modeMap  = { 'above': 0, 'below': 1 };
+
+// Band parameters and the processor setter each calls, converting
+// option strings to enum indexes
+const bandSetters = {
+    shape:     (eq, band, value) => eq.set_band_shape(band, shapeMap[value] || 0),
+    mode:      (eq, band, value) => eq.set_band_mode(band, modeMap[value] || 0),
+    frequency: (eq, band, value) => eq.set_band_frequency(band, value),
+    q:         (eq, band, value) => eq.set_band_q(band, value),
+    gain:      (eq, band, value) => eq.set_band_gain_db(band, value),
+    threshold: (eq, band, value) => eq.set_band_threshold_db(band, value),
+    ratio:     (eq, band, value) => eq.set_band_ratio(band, value),
+    range:     (eq, band, value) => eq.set_band_range_db(band, value),
+    attack:    (eq, band, value) => eq.set_band_attack_time(band, value),
+    release:   (eq, band, value) => eq.set_band_release_time(band, value),
+    bypass:    (eq, band, value) => eq.set_band_bypass(band, !!value)
+};
+
+class DynamicEqProcessor extends AudioWorkletProcessor {
+    constructor(options) {
+        super();
+
+        this.options = options.processorOptions || {};
+        this.wasm = null;
+        this.processors = [];            // One per channel, each with its own detectors and filters
+        this.bandCount = this.options.bandCount || 4;
+        this.bands = [];                 // Band parameters set so far, for processors added later
+        this.meterUpdateCounter = 0;
+
+        // Views of each processor's shared buffers in wasm memory. Passing
+        // arrays to process_block instead would copy them through
+        // __wbindgen_malloc every block.
+        this.blockLength = 0;
+        this.inputBuffers = [];
+        this.outputBuffers = [];
+
+        this.port.onmessage = (e) => {
+            if (e.data instanceof ArrayBuffer) {
+                this.initWasm(e.data);
+            } else if (e.data && e.data.type === 'param') {
+                this.updateParameter(e.data.band, e.data.name, e.data.value);
+            } else if (e.data && e.data.type === 'band-count') {
+                this.bandCount = e.data.value;
+                this.processors.forEach((processor) => processor.set_band_count(this.bandCount));
+            }
+        };
+    }
+
+    async initWasm(wasmBuffer) {
+        try {
+            const imports = {
+                env: {
+                    now: Date.now,
+                    log: console.log
+                }
+            };
+
+            const wasmModule = await WebAssembly.instantiate(wasmBuffer, imports);
+            this.wasm = wasmModule.instance.exports;
+
+            // Create processors for a stereo input, more are added when
+            // more channels arrive
+            this.addProcessor();
+            this.addProcessor();
+
+            this.port.postMessage({ type: 'wasm-module-loaded' });
+        } catch (err) {
+            console.error('Error initializing WASM module:', err);
+            this.port.postMessage({
+                type: 'error',
+                message: 'Failed to initialize WASM module: ' + err.toString()
+            });
+        }
+    }
+
+    addProcessor() {
+        const processor = this.wasm.DynamicEq.new(sampleRate, this.bandCount);
+
+        this.bands.forEach((params, band) => {
+            for (const name in params) {
+                bandSetters[name](processor, band, params[name]);
+            }
+        });
+
+        if (this.blockLength) processor.set_block_size(this.blockLength);
+        this.processors.push(processor);
+    }
+
+    mapBuffers() {
+        const memory = this.wasm.memory.buffer;
+        const length = this.blockLength;
+        this.inputBuffers = this.processors.map((processor) => new Float32Array(memory, processor.input_ptr(), length));
+        this.outputBuffers = this.processors.map((processor) => new Float32Array(memory, processor.output_ptr(), length));
+    }
+
+    updateParameter(band, name, value) {
+        if (!(name in bandSetters)) return;
+
+        this.bands[band] = this.bands[band] || {};
+        this.bands[band][name] = value;
+
+        for (const processor of this.processors) {
+            bandSetters[name](processor, band, value);
+        }
+    }
+
+    process(inputs, outputs) {
+        const input = inputs[0];
+        const output = outputs[0];
+
+        if (!input || !output) return true;
+
+        // If WASM module isn't loaded yet, pass audio through
+        if (!this.wasm) {
+            for (let channel = 0; channel < input.length; channel++) {
+                if (output[channel] && input[channel]) {
+                    output[channel].set(input[channel]);
+                }
+            }
+            return true;
+        }
+
+        const channels = Math.min(input.length, output.length);
+
+        if (!channels) return true;
+
+        const length = input[0].length;
+        const added = this.processors.length < channels;
+
+        while (this.processors.length < channels) {
+            this.addProcessor();
+        }
+
+        if (this.blockLength !== length) {
+            this.blockLength = length;
+            this.processors.forEach((processor) => processor.set_block_size(length));
+            this.mapBuffers();
+        } else if (added || this.inputBuffers[0].length === 0) {
+            // Views are detached when wasm memory grows
+            this.mapBuffers();
+        }
+
+        for (let channel = 0; channel < channels; channel++) {
+            if (!input[channel] || !output[channel]) continue;
+            this.inputBuffers[channel].set(input[channel]);
+            this.processors[channel].process_buffers();
+            output[channel].set(this.outputBuffers[channel]);
+        }
+
+        // Send band gains in dB to the main thread occasionally
+        this.meterUpdateCounter++;
+        if (this.meterUpdateCounter >= 128) {
+            const gains = [];
+            for (let band = 0; band < this.bandCount; band++) {
+                gains.push(this.processors[0].get_band_gain_db(band));
+            }
+
+            this.port.postMessage({ type: 'gains', value: gains });
+            this.meterUpdateCounter = 0;
+        }
+
+        return true;
+    }
+}
+
+registerProcessor('dynamic-eq', DynamicEqProcessor);
@@ -0,0 +1,129 @@
+//! Per-sample cost of processing. Each benchmark processes a 128 sample
+//! render quantum, and reports throughput in samples.
+
+use std::f32::consts::PI;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use dynamics::{DynamicsProcessor, MultichannelDynamics, CompressionCharacter, DetectionMode, LinkMode};
+
+const SAMPLE_RATE: f32 = 48000.0;
+const BLOCK: usize = 128;
+
+fn signal(length: usize) -> Vec<f32> {
+    // A sine swelling across the threshold, so the gain computer works
+    (0..length)
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE;
+            (0.5 + 0.5 * (2.0 * PI * 2.0 * t).sin()) * (2.0 * PI * 440.0 * t).sin()
+        })
+        .collect()
+}
+
+fn characters(c: &mut Criterion) {
+    let input = signal(BLOCK);
+    let mut output = vec![0.0; BLOCK];
+    let mut group = c.benchmark_group("character");
+    group.throughput(Throughput::Elements(BLOCK as u64));
+
+    for (name, character) in [
+        ("clean", CompressionCharacter::Clean),
+        ("smooth", CompressionCharacter::Smooth),
+        ("punchy", CompressionCharacter::Punchy),
+        ("vintage", CompressionCharacter::Vintage)
+    ] {
+        let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+        processor.set_character(character);
+
+        group.bench_function(BenchmarkId::new("process_block", name), |b| {
+            b.iter(|| processor.process_block(&input, None, &mut output, None))
+        });
+    }
+
+    group.finish();
+}
+
+fn detection(c: &mut Criterion) {
+    let input = signal(BLOCK);
+    let mut output = vec![0.0; BLOCK];
+    let mut group = c.benchmark_group("detection");
+    group.throughput(Throughput::Elements(BLOCK as u64));
+
+    for (name, mode) in [
+        ("peak", DetectionMode::Peak),
+        ("rms", DetectionMode::RMS),
+        ("logrms", DetectionMode::LogRMS),
+        ("adaptive", DetectionMode::Adaptive)
+    ] {
+        let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+        processor.set_detection_mode(mode);
+
+        group.bench_function(BenchmarkId::new("process_block", name), |b| {
+            b.iter(|| processor.process_block(&input, None, &mut output, None))
+        });
+    }
+
+    group.finish();
+}
+
+fn features(c: &mut Criterion) {
+    let input = signal(BLOCK);
+    let mut output = vec![0.0; BLOCK];
+    let mut group = c.benchmark_group("features");
+    group.throughput(Throughput::Elements(BLOCK as u64));
+
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    group.bench_function("process_sample", |b| {
+        b.iter(|| {
+            for (out, &sample) in output.iter_mut().zip(&input) {
+                *out = processor.process_sample(sample, None);
+            }
+        })
+    });
+
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_lookahead_ms(5.0);
+    group.bench_function("lookahead", |b| {
+        b.iter(|| processor.process_block(&input, None, &mut output, None))
+    });
+
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_drive(0.05);
+    processor.set_oversampling(true);
+    group.bench_function("oversampled_saturation", |b| {
+        b.iter(|| processor.process_block(&input, None, &mut output, None))
+    });
+
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_mix(0.5);
+    processor.set_wet_highpass(100.0);
+    processor.set_wet_lowpass(10000.0);
+    group.bench_function("parallel_filtered", |b| {
+        b.iter(|| processor.process_block(&input, None, &mut output, None))
+    });
+
+    group.finish();
+}
+
+fn multichannel(c: &mut Criterion) {
+    let input: Vec<f32> = signal(BLOCK).into_iter().flat_map(|x| [x, -x]).collect();
+    let mut buffer = input.clone();
+    let mut group = c.benchmark_group("multichannel");
+    group.throughput(Throughput::Elements(input.len() as u64));
+
+    for (name, mode) in [("independent", LinkMode::Independent), ("max", LinkMode::Max), ("midside", LinkMode::MidSide)] {
+        let mut processor = MultichannelDynamics::new(SAMPLE_RATE, 2);
+        processor.set_link_mode(mode);
+
+        group.bench_function(BenchmarkId::new("stereo", name), |b| {
+            b.iter(|| {
+                buffer.copy_from_slice(&input);
+                processor.process_interleaved(&mut buffer);
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, characters, detection, features, multichannel);
+criterion_main!(benches);
@@ -0,0 +1,168 @@
+//! Loudness-normalising automatic gain control.
+
+use wasm_bindgen::prelude::*;
+
+use crate::filters::Biquad;
+use crate::loudness::k_weighting;
+use crate::utils::{gain_to_db, db_to_gain};
+use crate::{EnvelopeFollower, DetectionMode, RmsWindow};
+
+/// Offset of BS.1770 loudness from the level of the K-weighted signal
+const LUFS_OFFSET: f32 = -0.691;
+
+/// Window of the loudness measurement in ms, momentary loudness
+const LOUDNESS_WINDOW_MS: f32 = 400.0;
+
+/// Deepest cut the AGC applies in dB
+const MAX_CUT_DB: f32 = 24.0;
+
+/// Automatic gain control. Loudness is measured as in BS.1770, from the
+/// K-weighted power of all channels over a 400ms window, and the gain
+/// moves towards the gain that brings it to the target with a slow time
+/// constant. Below the gate the gain freezes, so that pauses and silence
+/// are not pulled up to the target. All channels share one gain.
+#[wasm_bindgen]
+pub struct AutoGain {
+    filters: Vec<[Biquad; 2]>, // K-weighting per channel
+    envelope: EnvelopeFollower,
+    sample_rate: f32,
+    target_lufs: f32,
+    max_gain_db: f32,        // Largest boost in dB
+    gate_lufs: f32,          // Loudness below which gain freezes
+    gain_coef: f32,
+    gain_db: f32,            // Current gain in dB
+    loudness: f32,           // Current loudness in LUFS
+    frame: Vec<f32>,         // Scratch for one frame of samples
+}
+
+#[wasm_bindgen]
+impl AutoGain {
+    /// Create a new AutoGain for a number of channels
+    pub fn new(sample_rate: f32, channel_count: usize) -> Self {
+        let channel_count = channel_count.max(1);
+
+        // The envelope receives the root of the summed power, so its RMS
+        // is the root of the mean power over the window
+        let mut envelope = EnvelopeFollower::new(sample_rate);
+        envelope.set_detection_mode(DetectionMode::RMS);
+        envelope.set_rms_window(RmsWindow::Rectangular);
+        envelope.set_rms_window_ms(LOUDNESS_WINDOW_MS);
+        envelope.set_attack_time(0.0);
+        envelope.set_release_time(0.0);
+
+        let mut agc = Self {
+            filters: (0..channel_count).map(|_| k_weighting(sample_rate)).collect(),
+            envelope,
+            sample_rate,
+            target_lufs: -16.0,
+            max_gain_db: 12.0,
+            gate_lufs: -50.0,
+            gain_coef: 0.0,
+            gain_db: 0.0,
+            loudness: f32::NEG_INFINITY,
+            frame: vec![0.0; channel_count],
+        };
+
+        agc.set_time_constant(3.0);
+        agc
+    }
+
+    /// Get the number of channels
+    pub fn channel_count(&self) -> usize {
+        self.filters.len()
+    }
+
+    // ======== Parameter settings ========
+
+    /// Set target loudness in LUFS
+    pub fn set_target_lufs(&mut self, target_lufs: f32) {
+        self.target_lufs = target_lufs.clamp(-60.0, 0.0);
+    }
+
+    /// Set the largest gain the AGC may apply in dB
+    pub fn set_max_gain_db(&mut self, max_gain_db: f32) {
+        self.max_gain_db = max_gain_db.max(0.0);
+    }
+
+    /// Set loudness in LUFS below which the gain freezes
+    pub fn set_gate_lufs(&mut self, gate_lufs: f32) {
+        self.gate_lufs = gate_lufs;
+    }
+
+    /// Set the time constant of gain changes in seconds
+    pub fn set_time_constant(&mut self, time: f32) {
+        self.gain_coef = (-1.0 / (time.max(0.001) * self.sample_rate)).exp();
+    }
+
+    // ======== Processing ========
+
+    /// Process a block of interleaved frames in place
+    pub fn process_interleaved(&mut self, buffer: &mut [f32]) {
+        let channel_count = self.filters.len();
+
+        for frame in buffer.chunks_exact_mut(channel_count) {
+            self.frame.copy_from_slice(frame);
+            self.process_frame();
+            frame.copy_from_slice(&self.frame);
+        }
+    }
+
+    /// Process a block of planar channels, laid out one channel after
+    /// another, from `input` into `output`
+    pub fn process_planar(&mut self, input: &[f32], output: &mut [f32]) {
+        let channel_count = self.filters.len();
+        let length = input.len().min(output.len()) / channel_count;
+
+        for i in 0..length {
+            for c in 0..channel_count {
+                self.frame[c] = input[c * length + i];
+            }
+
+            self.process_frame();
+
+            for c in 0..channel_count {
+                output[c * length + i] = self.frame[c];
+            }
+        }
+    }
+
+    /// Get the current gain in dB
+    pub fn get_gain_db(&self) -> f32 {
+        self.gain_db
+    }
+
+    /// Get the measured input loudness in LUFS
+    pub fn get_loudness(&self) -> f32 {
+        self.loudness
+    }
+
+    /// Reset the AGC to unity gain
+    pub fn reset(&mut self) {
+        self.filters.iter_mut().flatten().for_each(Biquad::reset);
+        self.envelope.reset();
+        self.gain_db = 0.0;
+        self.loudness = f32::NEG_INFINITY;
+    }
+}
+
+impl AutoGain {
+    /// Measure the frame held in `self.frame` and apply gain in place
+    fn process_frame(&mut self) {
+        let power: f32 = self.filters.iter_mut()
+            .zip(&self.frame)
+            .map(|(filters, &sample)| filters.iter_mut().fold(sample, |x, filter| filter.process(x)))
+            .map(|weighted| weighted * weighted)
+            .sum();
+
+        self.loudness = LUFS_OFFSET + gain_to_db(self.envelope.process(power.sqrt()));
+
+        // Freeze below the gate, otherwise move towards the target
+        if self.loudness > self.gate_lufs {
+            let target_db = (self.target_lufs - self.loudness).clamp(-MAX_CUT_DB, self.max_gain_db);
+            self.gain_db = self.gain_coef * (self.gain_db - target_db) + target_db;
+        }
+
+        let gain = db_to_gain(self.gain_db);
+        self.frame.iter_mut().for_each(|sample| *sample *= gain);
+    }
+}
@@ -1,34 +1,128 @@
 //! Compression/expansion algorithms with different character options.
 
 use wasm_bindgen::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::utils::{gain_to_db, db_to_gain};
 
 /// Compression algorithm character types
 #[wasm_bindgen]
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum CompressionCharacter {
     Clean,    // Transparent/clean digital compressor
-    Smooth,   // Smooth optical-style compression
-    Punchy,   // Fast VCA-style compression
-    Vintage   // Aggressive colorful compression with harmonics
+    Smooth,   // Smooth optical-style compression, with cell lag and memory
+    Punchy,   // Fast VCA-style compression, with log domain RMS detection
+    Vintage   // Aggressive colorful FET-style compression with harmonics
 }
 
-/// Dynamic processing mode (compress, expand, gate)
+/// Dynamic processing mode (compress, expand, gate, duck)
 #[wasm_bindgen]
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ProcessorMode {
     Compress, // Standard downward compression
     Expand,   // Downward expansion
-    Gate      // Hard noise gate
+    Gate,     // Hard noise gate
+    Duck      // Reduce by a fixed range while the key is above threshold
+}
+
+/// Interpolation between the points of a custom curve
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CurveInterpolation {
+    Linear,  // Straight lines between points
+    Spline   // Monotone cubic through the points, without overshoot
 }
 
+/// Range and resolution in dB of custom curve tables
+const CURVE_MIN_DB: f32 = -120.0;
+const CURVE_MAX_DB: f32 = 24.0;
+const CURVE_STEPS_PER_DB: f32 = 8.0;
+
+/// Knee width in dB of a knee width of 1.0
+pub const MAX_KNEE_DB: f32 = 40.0;
+
 /// Dynamics processor calculation traits
 pub trait DynamicsCalculator {
+    /// Calculate gain change in dB for an input level in dB, with threshold
+    /// and knee width in dB
+    fn calculate_gain_db(&self, input_db: f32, threshold_db: f32, ratio: f32, knee_db: f32, mode: &ProcessorMode) -> f32;
+    
     /// Calculate gain reduction as a linear gain multiplier based on input level in gain domain
-    fn calculate_gain_linear(&self, input_level: f32, threshold: f32, ratio: f32, knee_width: f32, mode: &ProcessorMode) -> f32;
+    fn calculate_gain_linear(&self, input_level: f32, threshold: f32, ratio: f32, knee_width: f32, mode: &ProcessorMode) -> f32 {
+        db_to_gain(self.calculate_gain_db(gain_to_db(input_level), gain_to_db(threshold), ratio, knee_width_to_db(knee_width), mode))
+    }
     
     /// Apply processor character to the output
     fn apply_character(&self, input: f32, gain: f32) -> f32;
+    
+    /// Drive and colour of the harmonic saturation stage that follows the
+    /// gain, see `Saturator`
+    fn saturation(&self) -> (f32, f32) {
+        (0.0, 0.5)
+    }
+}
+
+/// Convert a knee width (0.0 to 1.0) to dB, widening in proportion from
+/// a hard knee at 0.0 to `MAX_KNEE_DB` at 1.0
+pub fn knee_width_to_db(knee_width: f32) -> f32 {
+    knee_width.clamp(0.0, 1.0) * MAX_KNEE_DB
+}
+
+/// Static curve of the gain computer in dB, with the soft knee from
+/// Giannoulis, Massberg & Reiss, "Digital Dynamic Range Compressor Design"
+/// (JAES 2012). Returns output level in dB for an input level in dB.
+pub fn static_curve_db(input_db: f32, threshold_db: f32, ratio: f32, knee_db: f32, mode: &ProcessorMode) -> f32 {
+    let overshoot = input_db - threshold_db;
+    
+    match mode {
+        ProcessorMode::Compress => {
+            let slope = 1.0 / ratio - 1.0;
+            
+            if 2.0 * overshoot < -knee_db {
+                // Below the knee, no compression
+                input_db
+            } else if knee_db > 0.0 && 2.0 * overshoot.abs() <= knee_db {
+                // In the knee, quadratic interpolation between the slopes
+                let x = overshoot + knee_db * 0.5;
+                input_db + slope * x * x / (2.0 * knee_db)
+            } else {
+                // Above the knee, standard compression
+                threshold_db + overshoot / ratio
+            }
+        },
+        
+        ProcessorMode::Expand | ProcessorMode::Gate => {
+            // Use more aggressive ratio for gating
+            let ratio = match mode {
+                ProcessorMode::Gate => ratio * 2.0,
+                _ => ratio
+            };
+            let slope = 1.0 - ratio;
+            
+            if 2.0 * overshoot > knee_db {
+                // Above the knee, no expansion
+                input_db
+            } else if knee_db > 0.0 && 2.0 * overshoot.abs() <= knee_db {
+                // In the knee, quadratic interpolation between the slopes
+                let x = overshoot - knee_db * 0.5;
+                input_db + slope * x * x / (2.0 * knee_db)
+            } else {
+                // Below the knee, apply expansion
+                threshold_db + overshoot * ratio
+            }
+        },
+        
+        // Ducking depends on the key rather than the input, so the static
+        // curve of the input is flat
+        ProcessorMode::Duck => input_db
+    }
 }
 
 /// Clean (transparent) compressor algorithm
+#[derive(Clone, Copy)]
 pub struct CleanCompressor;
 
 impl CleanCompressor {
@@ -38,52 +132,8 @@ impl CleanCompressor {
 }
 
 impl DynamicsCalculator for CleanCompressor {
-    fn calculate_gain_linear(&self, input_level: f32, threshold: f32, ratio: f32, knee_width: f32, mode: &ProcessorMode) -> f32 {
-        match mode {
-            ProcessorMode::Compress => {
-                if input_level <= threshold {
-                    // Below threshold, no compression
-                    1.0
-                } else if knee_width > 0.0 && input_level < (threshold + knee_width) {
-                    // In the knee region, soft knee compression
-                    let knee_position = (input_level - threshold) / knee_width; // 0 to 1 in knee
-                    let compression_amount = knee_position * knee_position; // Quadratic soft knee
-                    
-                    // Interpolate between 1.0 and the compression curve
-                    let above_thresh = input_level / threshold;
-                    let compressed = threshold / input_level * above_thresh.powf(1.0 / ratio);
-                    1.0 + (compressed - 1.0) * compression_amount
-                } else {
-                    // Above threshold and knee, standard compression
-                    let above_thresh = input_level / threshold;
-                    threshold / input_level * above_thresh.powf(1.0 / ratio)
-                }
-            },
-            
-            ProcessorMode::Expand => {
-                if input_level >= threshold {
-                    // Above threshold, no expansion
-                    1.0
-                } else {
-                    // Below threshold, apply expansion
-                    let below_thresh = input_level / threshold;
-                    threshold / input_level * below_thresh.powf(ratio)
-                }
-            },
-            
-            ProcessorMode::Gate => {
-                if input_level >= threshold {
-                    // Above threshold, no gating
-                    1.0
-                } else {
-                    // Below threshold, apply aggressive gating
-                    // Use more aggressive ratio for gating
-                    let gate_ratio = ratio * 2.0;
-                    let below_thresh = input_level / threshold;
-                    threshold / input_level * below_thresh.powf(gate_ratio)
-                }
-            }
-        }
+    fn calculate_gain_db(&self, input_db: f32, threshold_db: f32, ratio: f32, knee_db: f32, mode: &ProcessorMode) -> f32 {
+        static_curve_db(input_db, threshold_db, ratio, knee_db, mode) - input_db
     }
     
     fn apply_character(&self, input: f32, gain: f32) -> f32 {
@@ -93,6 +143,7 @@ impl DynamicsCalculator for CleanCompressor {
 }
 
 /// Smooth (optical-style) compressor algorithm
+#[derive(Clone, Copy)]
 pub struct SmoothCompressor;
 
 impl SmoothCompressor {
@@ -102,24 +153,27 @@ impl SmoothCompressor {
 }
 
 impl DynamicsCalculator for SmoothCompressor {
-    fn calculate_gain_linear(&self, input_level: f32, threshold: f32, ratio: f32, knee_width: f32, mode: &ProcessorMode) -> f32 {
+    fn calculate_gain_db(&self, input_db: f32, threshold_db: f32, ratio: f32, knee_db: f32, mode: &ProcessorMode) -> f32 {
         // Calculate the basic gain reduction
-        let clean_gain = CleanCompressor.calculate_gain_linear(input_level, threshold, ratio, knee_width, mode);
+        let clean_gain_db = CleanCompressor.calculate_gain_db(input_db, threshold_db, ratio, knee_db, mode);
         
         // For optical-style, we soften the gain reduction curve more for high ratios
-        let softening_factor = ((ratio - 1.0) / 20.0).min(0.5).max(0.0);
-        1.0 + (clean_gain - 1.0) * (1.0 - softening_factor)
+        let softening_factor = ((ratio - 1.0) / 20.0).clamp(0.0, 0.5);
+        clean_gain_db * (1.0 - softening_factor)
     }
     
     fn apply_character(&self, input: f32, gain: f32) -> f32 {
-        // Apply subtle second harmonic distortion that increases with gain reduction
-        let gain_factor = 1.0 - gain;
-        let distortion = input * input * input.signum() * 0.02;
-        input * gain + distortion * gain_factor
+        input * gain
+    }
+    
+    fn saturation(&self) -> (f32, f32) {
+        // Subtle, mostly second harmonic, like a tube makeup stage
+        (0.01, 0.8)
     }
 }
 
 /// Punchy (VCA-style) compressor algorithm
+#[derive(Clone, Copy)]
 pub struct PunchyCompressor;
 
 impl PunchyCompressor {
@@ -129,17 +183,17 @@ impl PunchyCompressor {
 }
 
 impl DynamicsCalculator for PunchyCompressor {
-    fn calculate_gain_linear(&self, input_level: f32, threshold: f32, ratio: f32, knee_width: f32, mode: &ProcessorMode) -> f32 {
+    fn calculate_gain_db(&self, input_db: f32, threshold_db: f32, ratio: f32, knee_db: f32, mode: &ProcessorMode) -> f32 {
         // Calculate the basic gain reduction
-        let clean_gain = CleanCompressor.calculate_gain_linear(input_level, threshold, ratio, knee_width, mode);
+        let clean_gain_db = CleanCompressor.calculate_gain_db(input_db, threshold_db, ratio, knee_db, mode);
         
         match mode {
             ProcessorMode::Compress => {
                 // For VCA-style, we enhance transients with slightly more aggressive curve
                 // This makes the effect more pronounced
-                1.0 + (clean_gain - 1.0) * 1.1
+                clean_gain_db * 1.1
             },
-            _ => clean_gain
+            _ => clean_gain_db
         }
     }
     
@@ -150,6 +204,7 @@ impl DynamicsCalculator for PunchyCompressor {
 }
 
 /// Vintage-style compressor algorithm
+#[derive(Clone, Copy)]
 pub struct VintageCompressor;
 
 impl VintageCompressor {
@@ -159,36 +214,228 @@ impl VintageCompressor {
 }
 
 impl DynamicsCalculator for VintageCompressor {
-    fn calculate_gain_linear(&self, input_level: f32, threshold: f32, ratio: f32, knee_width: f32, mode: &ProcessorMode) -> f32 {
-        // Calculate the basic gain reduction with a wider knee
-        let clean_gain = CleanCompressor.calculate_gain_linear(input_level, threshold, ratio, knee_width * 1.5, mode);
+    fn calculate_gain_db(&self, input_db: f32, threshold_db: f32, ratio: f32, knee_db: f32, mode: &ProcessorMode) -> f32 {
+        // Calculate the basic gain reduction with a wider knee, the FET's
+        // knee softening at low ratios
+        let knee_db = knee_db + 12.0 / ratio;
+        let clean_gain_db = CleanCompressor.calculate_gain_db(input_db, threshold_db, ratio, knee_db, mode);
         
         // For vintage-style, we use a softer curve
-        1.0 + (clean_gain - 1.0) * 0.9
+        clean_gain_db * 0.9
     }
     
     fn apply_character(&self, input: f32, gain: f32) -> f32 {
-        // Add harmonic distortion that increases with compression amount
-        let gain_factor = 1.0 - gain;
-        let gain_factor_squared = gain_factor * gain_factor;
+        input * gain
+    }
+    
+    fn saturation(&self) -> (f32, f32) {
+        // Colourful, 2nd and 3rd harmonics at 5:2 at full scale
+        (0.035, 0.714)
+    }
+}
+
+/// User-defined static curve through points of input dB and output dB.
+/// Gains are tabulated when the curve is created, and looked up with
+/// linear interpolation between table steps. Beyond the first and last
+/// points the curve continues at unity slope. Threshold, ratio, knee and
+/// mode do not apply.
+#[derive(Clone)]
+pub struct CustomCurve {
+    points: Vec<f32>,        // Interleaved input and output dB, sorted by input
+    interpolation: CurveInterpolation,
+    table: Vec<f32>,         // Gain in dB at each step from CURVE_MIN_DB
+}
+
+impl CustomCurve {
+    /// Create a curve from interleaved pairs of input dB and output dB.
+    /// Pairs are sorted by input, and later pairs at the same input and
+    /// non-finite pairs are dropped.
+    pub fn new(points: &[f32], interpolation: CurveInterpolation) -> Self {
+        let mut pairs: Vec<(f32, f32)> = points.chunks_exact(2)
+            .map(|pair| (pair[0], pair[1]))
+            .filter(|(input_db, output_db)| input_db.is_finite() && output_db.is_finite())
+            .collect();
+        pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+        pairs.dedup_by(|a, b| a.0 == b.0);
         
-        // Second harmonic (octave)
-        let second_harmonic = input * input * input.signum() * 0.05;
+        let tangents = match interpolation {
+            CurveInterpolation::Linear => Vec::new(),
+            CurveInterpolation::Spline => monotone_tangents(&pairs)
+        };
         
-        // Third harmonic
-        let third_harmonic = input * input * input * 0.02;
+        let size = ((CURVE_MAX_DB - CURVE_MIN_DB) * CURVE_STEPS_PER_DB) as usize + 1;
+        let table = (0..size)
+            .map(|i| {
+                let input_db = CURVE_MIN_DB + i as f32 / CURVE_STEPS_PER_DB;
+                interpolate(&pairs, &tangents, input_db) - input_db
+            })
+            .collect();
         
-        // Apply with increasing intensity based on gain reduction
-        input * gain + (second_harmonic + third_harmonic) * gain_factor_squared
+        Self {
+            points: pairs.iter().flat_map(|&(input_db, output_db)| [input_db, output_db]).collect(),
+            interpolation,
+            table,
+        }
+    }
+    
+    /// Get the curve's points as interleaved input and output dB
+    pub fn points(&self) -> &[f32] {
+        &self.points
+    }
+    
+    /// Get the curve's interpolation
+    pub fn interpolation(&self) -> CurveInterpolation {
+        self.interpolation
+    }
+}
+
+impl DynamicsCalculator for CustomCurve {
+    fn calculate_gain_db(&self, input_db: f32, _threshold_db: f32, _ratio: f32, _knee_db: f32, _mode: &ProcessorMode) -> f32 {
+        let last = self.table.len() - 1;
+        let position = ((input_db - CURVE_MIN_DB) * CURVE_STEPS_PER_DB).clamp(0.0, last as f32);
+        let index = (position as usize).min(last - 1);
+        let fraction = position - index as f32;
+        self.table[index] + (self.table[index + 1] - self.table[index]) * fraction
+    }
+    
+    fn apply_character(&self, input: f32, gain: f32) -> f32 {
+        input * gain
+    }
+}
+
+/// Output dB of a curve through sorted points at an input dB, with
+/// Hermite tangents for a spline or none for straight lines
+fn interpolate(pairs: &[(f32, f32)], tangents: &[f32], input_db: f32) -> f32 {
+    let (Some(&first), Some(&last)) = (pairs.first(), pairs.last()) else {
+        return input_db;
+    };
+    
+    if input_db <= first.0 {
+        return first.1 + input_db - first.0;
+    }
+    
+    if input_db >= last.0 {
+        return last.1 + input_db - last.0;
+    }
+    
+    let k = pairs.partition_point(|&(x, _)| x <= input_db) - 1;
+    let (x0, y0) = pairs[k];
+    let (x1, y1) = pairs[k + 1];
+    let h = x1 - x0;
+    let t = (input_db - x0) / h;
+    
+    if tangents.is_empty() {
+        return y0 + (y1 - y0) * t;
+    }
+    
+    // Cubic Hermite basis
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+        + (t3 - 2.0 * t2 + t) * h * tangents[k]
+        + (-2.0 * t3 + 3.0 * t2) * y1
+        + (t3 - t2) * h * tangents[k + 1]
+}
+
+/// Tangents of a monotone cubic through sorted points, after Fritsch &
+/// Carlson, "Monotone Piecewise Cubic Interpolation" (SIAM 1980), so that
+/// the curve does not overshoot between points
+fn monotone_tangents(pairs: &[(f32, f32)]) -> Vec<f32> {
+    let n = pairs.len();
+    
+    if n < 2 {
+        return vec![1.0; n];
+    }
+    
+    let secants: Vec<f32> = pairs.windows(2)
+        .map(|w| (w[1].1 - w[0].1) / (w[1].0 - w[0].0))
+        .collect();
+    
+    let mut tangents = vec![0.0; n];
+    tangents[0] = secants[0];
+    tangents[n - 1] = secants[n - 2];
+    
+    for k in 1..n - 1 {
+        tangents[k] = if secants[k - 1] * secants[k] <= 0.0 {
+            0.0
+        } else {
+            (secants[k - 1] + secants[k]) * 0.5
+        };
+    }
+    
+    // Limit tangents to the region where the segment stays monotone
+    for k in 0..n - 1 {
+        if secants[k] == 0.0 {
+            tangents[k] = 0.0;
+            tangents[k + 1] = 0.0;
+            continue;
+        }
+        
+        let a = tangents[k] / secants[k];
+        let b = tangents[k + 1] / secants[k];
+        let magnitude = a * a + b * b;
+        
+        if magnitude > 9.0 {
+            let tau = 3.0 / magnitude.sqrt();
+            tangents[k] = tau * a * secants[k];
+            tangents[k + 1] = tau * b * secants[k];
+        }
+    }
+    
+    tangents
+}
+
+/// Calculator of a character, or a custom curve. Dispatching through the
+/// enum rather than a boxed trait object keeps calculators off the heap
+/// while processing, so the processor can hold one and processing never
+/// allocates.
+#[derive(Clone)]
+pub enum Calculator {
+    Clean(CleanCompressor),
+    Smooth(SmoothCompressor),
+    Punchy(PunchyCompressor),
+    Vintage(VintageCompressor),
+    Custom(CustomCurve)
+}
+
+impl DynamicsCalculator for Calculator {
+    fn calculate_gain_db(&self, input_db: f32, threshold_db: f32, ratio: f32, knee_db: f32, mode: &ProcessorMode) -> f32 {
+        match self {
+            Calculator::Clean(calculator) => calculator.calculate_gain_db(input_db, threshold_db, ratio, knee_db, mode),
+            Calculator::Smooth(calculator) => calculator.calculate_gain_db(input_db, threshold_db, ratio, knee_db, mode),
+            Calculator::Punchy(calculator) => calculator.calculate_gain_db(input_db, threshold_db, ratio, knee_db, mode),
+            Calculator::Vintage(calculator) => calculator.calculate_gain_db(input_db, threshold_db, ratio, knee_db, mode),
+            Calculator::Custom(calculator) => calculator.calculate_gain_db(input_db, threshold_db, ratio, knee_db, mode)
+        }
+    }
+    
+    fn apply_character(&self, input: f32, gain: f32) -> f32 {
+        match self {
+            Calculator::Clean(calculator) => calculator.apply_character(input, gain),
+            Calculator::Smooth(calculator) => calculator.apply_character(input, gain),
+            Calculator::Punchy(calculator) => calculator.apply_character(input, gain),
+            Calculator::Vintage(calculator) => calculator.apply_character(input, gain),
+            Calculator::Custom(calculator) => calculator.apply_character(input, gain)
+        }
+    }
+    
+    fn saturation(&self) -> (f32, f32) {
+        match self {
+            Calculator::Clean(calculator) => calculator.saturation(),
+            Calculator::Smooth(calculator) => calculator.saturation(),
+            Calculator::Punchy(calculator) => calculator.saturation(),
+            Calculator::Vintage(calculator) => calculator.saturation(),
+            Calculator::Custom(calculator) => calculator.saturation()
+        }
     }
 }
 
 /// Get the appropriate dynamics calculator based on character
-pub fn get_calculator(character: &CompressionCharacter) -> Box<dyn DynamicsCalculator> {
+pub fn get_calculator(character: &CompressionCharacter) -> Calculator {
     match character {
-        CompressionCharacter::Clean => Box::new(CleanCompressor::new()),
-        CompressionCharacter::Smooth => Box::new(SmoothCompressor::new()),
-        CompressionCharacter::Punchy => Box::new(PunchyCompressor::new()),
-        CompressionCharacter::Vintage => Box::new(VintageCompressor::new())
+        CompressionCharacter::Clean => Calculator::Clean(CleanCompressor::new()),
+        CompressionCharacter::Smooth => Calculator::Smooth(SmoothCompressor::new()),
+        CompressionCharacter::Punchy => Calculator::Punchy(PunchyCompressor::new()),
+        CompressionCharacter::Vintage => Calculator::Vintage(VintageCompressor::new())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,241 @@
+//! Parameter smoothing and sample-accurate automation.
+
+use wasm_bindgen::prelude::*;
+
+/// Automatable parameters, with values in the units given
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Param {
+    Threshold,  // dB
+    Ratio,      // n:1
+    Knee,       // dB
+    Makeup,     // dB
+    OutputGain, // dB
+    Mix,        // 0.0 = dry, 1.0 = wet
+    WetGain,    // dB
+    DryGain     // dB
+}
+
+const PARAM_COUNT: usize = 8;
+
+impl Param {
+    /// Clamp a value to the parameter's range
+    pub fn clamp(self, value: f32) -> f32 {
+        match self {
+            Param::Threshold => value.clamp(-120.0, 0.0),
+            Param::Ratio => value.clamp(1.0, 1000.0),
+            Param::Knee => value.clamp(0.0, 60.0),
+            Param::Mix => value.clamp(0.0, 1.0),
+            Param::Makeup | Param::OutputGain | Param::WetGain | Param::DryGain => value.clamp(-120.0, 40.0)
+        }
+    }
+
+    /// Whether exponential ramps of the parameter are geometric. Values
+    /// in dB are already logarithmic, so they ramp linearly.
+    fn is_geometric(self) -> bool {
+        matches!(self, Param::Ratio | Param::Mix)
+    }
+
+    pub(crate) fn from_index(index: usize) -> Self {
+        [
+            Param::Threshold, Param::Ratio, Param::Knee, Param::Makeup,
+            Param::OutputGain, Param::Mix, Param::WetGain, Param::DryGain
+        ][index]
+    }
+}
+
+/// Curves of scheduled parameter changes, after the Web Audio API
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum RampCurve {
+    Step,        // Jump to the value at the event time
+    Linear,      // Ramp linearly from the previous event to the value
+    Exponential  // Ramp exponentially from the previous event to the value,
+                 // which for parameters in dB is linear in dB
+}
+
+/// A ramp in progress
+#[derive(Clone, Copy)]
+struct Ramp {
+    target: f32,
+    increment: f32,          // Added per sample, or multiplied when exponential
+    exponential: bool,
+    remaining: usize,        // Samples left, 0 when idle
+    scheduled: bool,         // Started by a scheduled event
+}
+
+impl Ramp {
+    const IDLE: Ramp = Ramp { target: 0.0, increment: 0.0, exponential: false, remaining: 0, scheduled: false };
+}
+
+/// A scheduled parameter change
+#[derive(Clone, Copy)]
+struct Event {
+    param: Param,
+    value: f32,
+    time: u64,               // Sample time the value is reached
+    curve: RampCurve,
+}
+
+/// Smooths parameter changes and plays scheduled events. Values are held
+/// here in automation units, and `tick` reports those that change each
+/// sample for the owner to apply.
+pub(crate) struct Automation {
+    values: [f32; PARAM_COUNT],  // Current values
+    targets: [f32; PARAM_COUNT], // Values after ramps and scheduled events
+    ramps: [Ramp; PARAM_COUNT],
+    events: Vec<Event>,      // Ordered by time
+    time: u64,               // Sample time of the next sample
+    ramp_samples: usize,     // Length of smoothing ramps
+    sample_rate: f32,
+}
+
+impl Automation {
+    pub fn new(sample_rate: f32, values: [f32; PARAM_COUNT]) -> Self {
+        Self {
+            values,
+            targets: values,
+            ramps: [Ramp::IDLE; PARAM_COUNT],
+            events: Vec::with_capacity(32),
+            time: 0,
+            ramp_samples: 0,
+            sample_rate,
+        }
+    }
+
+    /// Set the time over which set parameters are smoothed, in seconds
+    pub fn set_ramp_time(&mut self, time: f32) {
+        self.ramp_samples = (time.max(0.0) * self.sample_rate) as usize;
+    }
+
+    /// Get the value a parameter is moving towards
+    pub fn target(&self, param: Param) -> f32 {
+        self.targets[param as usize]
+    }
+
+    /// Move a parameter to a value over the smoothing time. Values set
+    /// before the first sample apply immediately. Returns true when the
+    /// value applies immediately and the owner should apply it.
+    pub fn smooth(&mut self, param: Param, value: f32) -> bool {
+        let index = param as usize;
+        self.targets[index] = value;
+
+        if self.ramp_samples == 0 || self.time == 0 {
+            self.values[index] = value;
+            self.ramps[index] = Ramp::IDLE;
+            return true;
+        }
+
+        self.ramps[index] = Self::ramp(param, self.values[index], value, self.ramp_samples, RampCurve::Linear, false);
+        false
+    }
+
+    /// Schedule a change of a parameter `offset` samples after the next
+    /// sample to be processed
+    pub fn schedule(&mut self, param: Param, value: f32, offset: usize, curve: RampCurve) {
+        let event = Event { param, value, time: self.time + offset as u64, curve };
+        let index = self.events.partition_point(|e| e.time <= event.time);
+        self.events.insert(index, event);
+
+        // The target is the value of the latest event for the parameter,
+        // which need not be the one just scheduled
+        if let Some(latest) = self.events.iter().rfind(|e| e.param == param) {
+            self.targets[param as usize] = latest.value;
+        }
+    }
+
+    /// Cancel all scheduled events and ramps, holding current values
+    pub fn cancel(&mut self) {
+        self.events.clear();
+        self.ramps = [Ramp::IDLE; PARAM_COUNT];
+        self.targets = self.values;
+    }
+
+    #[inline]
+    pub fn is_idle(&self) -> bool {
+        self.events.is_empty() && self.ramps.iter().all(|ramp| ramp.remaining == 0)
+    }
+
+    /// Advance by one sample when idle, with nothing to play
+    #[inline]
+    pub fn advance(&mut self) {
+        self.time += 1;
+    }
+
+    /// Advance by one sample and return the values that changed
+    pub fn tick(&mut self) -> [Option<f32>; PARAM_COUNT] {
+        let mut changes = [None; PARAM_COUNT];
+
+        // Start scheduled events that are due. An event waits while an
+        // earlier event for its parameter is pending or ramping.
+        let mut blocked = self.ramps.map(|ramp| ramp.remaining > 0 && ramp.scheduled);
+        let mut i = 0;
+
+        while i < self.events.len() {
+            let event = self.events[i];
+            let index = event.param as usize;
+
+            if blocked[index] {
+                i += 1;
+                continue;
+            }
+
+            blocked[index] = true;
+
+            if event.curve == RampCurve::Step && event.time > self.time {
+                i += 1;
+                continue;
+            }
+
+            // Ramps reach their value on the sample at the event time
+            let length = (event.time.saturating_sub(self.time) + 1) as usize;
+            self.ramps[index] = Self::ramp(event.param, self.values[index], event.value, length, event.curve, true);
+            self.events.remove(i);
+        }
+
+        for (index, ramp) in self.ramps.iter_mut().enumerate() {
+            if ramp.remaining == 0 {
+                continue;
+            }
+
+            ramp.remaining -= 1;
+
+            let value = &mut self.values[index];
+            *value = if ramp.remaining == 0 {
+                ramp.target
+            } else if ramp.exponential {
+                *value * ramp.increment
+            } else {
+                *value + ramp.increment
+            };
+
+            changes[index] = Some(Param::from_index(index).clamp(*value));
+        }
+
+        self.time += 1;
+        changes
+    }
+
+    /// Build a ramp from a value to a target reached in `length` samples,
+    /// or on the next sample for steps. Exponential ramps are geometric
+    /// for parameters that are not in dB, and need values of the same sign
+    /// for that, falling back to linear otherwise.
+    fn ramp(param: Param, from: f32, to: f32, length: usize, curve: RampCurve, scheduled: bool) -> Ramp {
+        let length = length.max(1);
+        let exponential = curve == RampCurve::Exponential && param.is_geometric() && from * to > 0.0;
+
+        let increment = match curve {
+            RampCurve::Step => 0.0,
+            _ if exponential => (to / from).powf(1.0 / length as f32),
+            _ => (to - from) / length as f32
+        };
+
+        Ramp {
+            target: to,
+            increment,
+            exponential,
+            remaining: if curve == RampCurve::Step { 1 } else { length },
+            scheduled,
+        }
+    }
+}
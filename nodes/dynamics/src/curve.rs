@@ -0,0 +1,55 @@
+//! Static transfer curves for drawing dynamics curves in the UI.
+
+use wasm_bindgen::prelude::*;
+
+use crate::algorithms::{get_calculator, CompressionCharacter, ProcessorMode, DynamicsCalculator};
+use crate::automation::Param;
+
+/// Evaluate the static transfer curve of a character and mode over input
+/// levels from `min_db` to `max_db`. Returns `points` interleaved pairs of
+/// input dB and output dB, including makeup.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn transfer_curve(
+    character: CompressionCharacter,
+    mode: ProcessorMode,
+    threshold_db: f32,
+    ratio: f32,
+    knee_db: f32,
+    makeup_db: f32,
+    min_db: f32,
+    max_db: f32,
+    points: usize
+) -> Vec<f32> {
+    evaluate(&get_calculator(&character), mode, threshold_db, ratio, knee_db, makeup_db, min_db, max_db, points)
+}
+
+/// Evaluate the static transfer curve of a calculator, as `transfer_curve`
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn evaluate(
+    calculator: &impl DynamicsCalculator,
+    mode: ProcessorMode,
+    threshold_db: f32,
+    ratio: f32,
+    knee_db: f32,
+    makeup_db: f32,
+    min_db: f32,
+    max_db: f32,
+    points: usize
+) -> Vec<f32> {
+    // Clamp to the ranges of the processor's parameters
+    let threshold_db = Param::Threshold.clamp(threshold_db);
+    let ratio = Param::Ratio.clamp(ratio);
+    let knee_db = Param::Knee.clamp(knee_db);
+    let step = if points > 1 { (max_db - min_db) / (points - 1) as f32 } else { 0.0 };
+    let mut curve = Vec::with_capacity(points * 2);
+
+    for i in 0..points {
+        let input_db = min_db + step * i as f32;
+        let gain_db = calculator.calculate_gain_db(input_db, threshold_db, ratio, knee_db, &mode);
+        curve.push(input_db);
+        curve.push(input_db + gain_db + makeup_db);
+    }
+
+    curve
+}
@@ -0,0 +1,182 @@
+//! De-esser with a filtered sibilance detector.
+
+use wasm_bindgen::prelude::*;
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::filters::{Biquad, LinkwitzRiley};
+use crate::{DynamicsProcessor, DetectionMode};
+
+/// Range of the detector frequency in Hz
+const MIN_FREQUENCY: f32 = 4000.0;
+const MAX_FREQUENCY: f32 = 10000.0;
+
+/// De-esser reduction modes
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum DeEsserMode {
+    SplitBand, // Reduce only the band above the detector frequency
+    Wideband   // Reduce the whole signal
+}
+
+/// De-esser detector filter shapes
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum DeEsserFilter {
+    BandPass, // Detect around the frequency, with width set by Q
+    HighPass  // Detect everything above the frequency
+}
+
+/// De-esser. Sibilance is detected from a band-pass or high-pass filtered
+/// copy of the input, which drives a `DynamicsProcessor` as its sidechain.
+/// In split-band mode the input is divided by a Linkwitz-Riley crossover at
+/// the detector frequency and gain reduction is applied to the high band
+/// alone, so the bands recombine flat when no reduction is applied.
+#[wasm_bindgen]
+pub struct DeEsser {
+    processor: DynamicsProcessor,
+    sample_rate: f32,
+    mode: DeEsserMode,
+    filter: DeEsserFilter,
+    frequency: f32,          // Detector and crossover frequency in Hz
+    q: f32,                  // Width of the band-pass detector
+    listen: bool,            // Output the detector signal
+    detector_filter: Biquad,
+    crossover: LinkwitzRiley,
+}
+
+#[wasm_bindgen]
+impl DeEsser {
+    /// Create a new DeEsser
+    pub fn new(sample_rate: f32) -> Self {
+        let mut processor = DynamicsProcessor::new(sample_rate);
+
+        // Sibilants are short, so detect peaks with fast ballistics
+        processor.set_sidechain_external(true);
+        processor.set_detection_mode(DetectionMode::Peak);
+        processor.set_threshold_db(-30.0);
+        processor.set_ratio(6.0);
+        processor.set_knee_db(3.0);
+        processor.set_attack_time(0.001);
+        processor.set_release_time(0.06);
+
+        let frequency = 6000.0;
+        let q = 1.5;
+
+        Self {
+            processor,
+            sample_rate,
+            mode: DeEsserMode::SplitBand,
+            filter: DeEsserFilter::BandPass,
+            frequency,
+            q,
+            listen: false,
+            detector_filter: Biquad::bandpass(sample_rate, frequency, q),
+            crossover: LinkwitzRiley::new(sample_rate, frequency),
+        }
+    }
+
+    // ======== Parameter settings ========
+
+    /// Set reduction mode (SplitBand, Wideband)
+    pub fn set_mode(&mut self, mode: DeEsserMode) {
+        self.mode = mode;
+    }
+
+    /// Set detector filter shape (BandPass, HighPass)
+    pub fn set_filter(&mut self, filter: DeEsserFilter) {
+        self.filter = filter;
+        self.update_filters();
+    }
+
+    /// Set detector and crossover frequency in Hz (4000 to 10000)
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.frequency = frequency.clamp(MIN_FREQUENCY, MAX_FREQUENCY);
+        self.update_filters();
+    }
+
+    /// Set the width of the band-pass detector as Q
+    pub fn set_q(&mut self, q: f32) {
+        self.q = q.max(0.1);
+        self.update_filters();
+    }
+
+    /// Set threshold in dB
+    pub fn set_threshold_db(&mut self, threshold_db: f32) {
+        self.processor.set_threshold_db(threshold_db);
+    }
+
+    /// Set ratio (1:n)
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.processor.set_ratio(ratio);
+    }
+
+    /// Set attack time in seconds
+    pub fn set_attack_time(&mut self, attack_time: f32) {
+        self.processor.set_attack_time(attack_time);
+    }
+
+    /// Set release time in seconds
+    pub fn set_release_time(&mut self, release_time: f32) {
+        self.processor.set_release_time(release_time);
+    }
+
+    /// Output the detector signal instead of the processed signal, to hear
+    /// what the de-esser is responding to
+    pub fn set_listen(&mut self, listen: bool) {
+        self.listen = listen;
+    }
+
+    // ======== Processing ========
+
+    /// Process a single sample and return the processed audio
+    pub fn process_sample(&mut self, input: f32) -> f32 {
+        let sibilance = self.detector_filter.process(input);
+        let level = self.processor.detect(input, Some(sibilance));
+        let gain = self.processor.gain_for_level(level);
+
+        // Keep the crossover running in both modes so that switching mode
+        // does not click
+        let (low, high) = self.crossover.process(input);
+
+        if self.listen {
+            return sibilance;
+        }
+
+        match self.mode {
+            DeEsserMode::SplitBand => low + self.processor.apply_gain(high, gain),
+            DeEsserMode::Wideband => self.processor.apply_gain(input, gain)
+        }
+    }
+
+    /// Process a block of samples from `input` into `output`
+    pub fn process_block(&mut self, input: &[f32], output: &mut [f32]) {
+        for (out, &sample) in output.iter_mut().zip(input) {
+            *out = self.process_sample(sample);
+        }
+    }
+
+    /// Get current gain reduction as a linear gain multiplier (0.0 to 1.0)
+    pub fn get_gain_reduction(&self) -> f32 {
+        self.processor.get_gain_reduction()
+    }
+
+    /// Reset the de-esser state
+    pub fn reset(&mut self) {
+        self.detector_filter.reset();
+        self.crossover.reset();
+        self.processor.reset();
+    }
+}
+
+impl DeEsser {
+    /// Update detector and crossover filters from the frequency settings,
+    /// keeping their state so that moving the frequency does not click
+    fn update_filters(&mut self) {
+        match self.filter {
+            DeEsserFilter::BandPass => self.detector_filter.set_bandpass(self.sample_rate, self.frequency, self.q),
+            DeEsserFilter::HighPass => self.detector_filter.set_highpass(self.sample_rate, self.frequency, FRAC_1_SQRT_2)
+        }
+
+        self.crossover.set_freq(self.sample_rate, self.frequency);
+    }
+}
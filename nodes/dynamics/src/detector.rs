@@ -0,0 +1,115 @@
+//! Detector models of the time behaviour of hardware gain control elements.
+
+use crate::algorithms::CompressionCharacter;
+
+/// Fastest response of an optical cell in seconds, the lag of the
+/// photoresistor behind its LED
+const OPTO_MIN_ATTACK_TIME: f32 = 0.01;
+
+/// Time constants of the optical cell's memory in seconds. Memory charges
+/// with sustained light and holds gain reduction after fast release.
+const OPTO_MEMORY_CHARGE_TIME: f32 = 1.0;
+const OPTO_MEMORY_RELEASE_TIME: f32 = 5.0;
+
+/// FET attack as a proportion of the attack setting, and its fastest attack
+const FET_ATTACK_SCALE: f32 = 0.2;
+const FET_MIN_ATTACK_TIME: f32 = 0.00002;
+
+/// Detector models, one per compression character
+#[derive(Clone, Copy, PartialEq)]
+pub enum DetectorModel {
+    Digital, // Envelope follower as configured
+    Opto,    // Optical cell with photoresistor lag and memory
+    Vca,     // Ballistics in the log domain
+    Fet      // Faster attack than the setting
+}
+
+impl DetectorModel {
+    pub fn from_character(character: &CompressionCharacter) -> Self {
+        match character {
+            CompressionCharacter::Clean => DetectorModel::Digital,
+            CompressionCharacter::Smooth => DetectorModel::Opto,
+            CompressionCharacter::Punchy => DetectorModel::Vca,
+            CompressionCharacter::Vintage => DetectorModel::Fet
+        }
+    }
+}
+
+/// Converts a time in seconds to a one-pole smoothing coefficient, with
+/// the time being the 10% to 90% rise time
+pub(crate) fn coefficient(time: f32, sample_rate: f32) -> f32 {
+    if time <= 0.0 {
+        0.0
+    } else {
+        (-2.2 / (time * sample_rate)).exp()
+    }
+}
+
+/// Detector that shapes the time behaviour of level detection to model a
+/// hardware gain control element. Models shape the envelope follower
+/// rather than replacing it, so detection mode, RMS window and auto release
+/// apply to every model: the optical cell and FET change the follower's
+/// attack, the VCA runs its ballistics in the log domain, and the optical
+/// cell's memory holds the follower's level up after sustained reduction.
+pub struct Detector {
+    model: DetectorModel,
+    // Opto state
+    memory_charge_coef: f32,
+    memory_release_coef: f32,
+    memory: f32,
+}
+
+impl Detector {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            model: DetectorModel::Digital,
+            memory_charge_coef: coefficient(OPTO_MEMORY_CHARGE_TIME, sample_rate),
+            memory_release_coef: coefficient(OPTO_MEMORY_RELEASE_TIME, sample_rate),
+            memory: 0.0,
+        }
+    }
+
+    pub fn set_model(&mut self, model: DetectorModel) {
+        if model != self.model {
+            self.model = model;
+            self.reset();
+        }
+    }
+
+    /// Get the envelope follower's attack time in seconds for an attack
+    /// setting. The optical cell responds no faster than the photoresistor
+    /// lags its LED, and the FET responds faster than the setting.
+    pub fn attack_time(&self, attack_time: f32) -> f32 {
+        match self.model {
+            DetectorModel::Opto => attack_time.max(OPTO_MIN_ATTACK_TIME),
+            DetectorModel::Fet => (attack_time * FET_ATTACK_SCALE).max(FET_MIN_ATTACK_TIME),
+            _ => attack_time
+        }
+    }
+
+    /// Get whether the envelope follower runs its ballistics in the log
+    /// domain
+    pub fn log_domain(&self) -> bool {
+        self.model == DetectorModel::Vca
+    }
+
+    /// Shape the envelope follower's level, in gain domain
+    #[inline]
+    pub fn process(&mut self, envelope_level: f32) -> f32 {
+        match self.model {
+            DetectorModel::Opto => {
+                // Memory charges from sustained light and decays slowly,
+                // so release slows down after long periods of reduction
+                let coef = if envelope_level > self.memory { self.memory_charge_coef } else { self.memory_release_coef };
+                self.memory = coef * (self.memory - envelope_level) + envelope_level;
+                envelope_level.max(self.memory)
+            },
+
+            _ => envelope_level
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.memory = 0.0;
+    }
+}
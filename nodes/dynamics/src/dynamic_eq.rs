@@ -0,0 +1,323 @@
+//! Dynamic EQ, with bands whose gain follows their own gain computers.
+
+use wasm_bindgen::prelude::*;
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::filters::Biquad;
+use crate::utils::gain_to_db;
+use crate::{DynamicsProcessor, ProcessorMode, DetectionMode};
+
+/// Maximum number of bands
+const MAX_BANDS: usize = 8;
+
+/// Default band frequencies in Hz
+const DEFAULT_FREQUENCIES: [f32; MAX_BANDS] = [100.0, 250.0, 500.0, 1000.0, 2000.0, 3500.0, 6000.0, 10000.0];
+
+/// Change of band gain in dB below which filter coefficients are not
+/// recalculated
+const GAIN_RESOLUTION_DB: f32 = 0.01;
+
+/// Samples between recalculations of band filter coefficients, which take
+/// a power, sine and cosine. At 48kHz this is 0.67ms, well inside the
+/// fastest attack of a band.
+const COEFFICIENT_INTERVAL: usize = 32;
+
+/// Dynamic EQ band filter shapes
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum DynamicEqShape {
+    Bell,      // Peaking filter, detecting a band-pass around the frequency
+    LowShelf,  // Shelf below the frequency, detecting a low-pass
+    HighShelf  // Shelf above the frequency, detecting a high-pass
+}
+
+/// Dynamic EQ band modes
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum DynamicEqMode {
+    Above, // Move the band gain as the band rises above threshold
+    Below  // Move the band gain as the band falls below threshold
+}
+
+/// A band's filters and gain computer
+struct Band {
+    processor: DynamicsProcessor,  // Detector ballistics and gain computer
+    shape: DynamicEqShape,
+    frequency: f32,                // Hz
+    q: f32,
+    gain_db: f32,                  // Static gain of the band
+    range_db: f32,                 // Most the gain moves, negative to cut, positive to boost
+    bypass: bool,
+    detector: Biquad,
+    filter: Biquad,
+    filter_gain_db: f32,           // Gain the filter coefficients were last set for
+    countdown: usize,              // Samples until the coefficients are next recalculated
+}
+
+impl Band {
+    fn new(sample_rate: f32, frequency: f32) -> Self {
+        let mut processor = DynamicsProcessor::new(sample_rate);
+
+        // Resonances ring briefly, so detect peaks with fast ballistics
+        processor.set_sidechain_external(true);
+        processor.set_detection_mode(DetectionMode::Peak);
+        processor.set_threshold_db(-30.0);
+        processor.set_ratio(4.0);
+        processor.set_knee_db(6.0);
+        processor.set_attack_time(0.005);
+        processor.set_release_time(0.1);
+
+        let q = 2.0;
+
+        Self {
+            processor,
+            shape: DynamicEqShape::Bell,
+            frequency,
+            q,
+            gain_db: 0.0,
+            range_db: -12.0,
+            bypass: false,
+            detector: Biquad::bandpass(sample_rate, frequency, q),
+            filter: Biquad::new(),
+            filter_gain_db: 0.0,
+            countdown: 0,
+        }
+    }
+
+    /// Process a sample `x` through the band, detecting from `input`. The
+    /// gain computer runs every sample, and the filter follows its gain
+    /// every `COEFFICIENT_INTERVAL` samples.
+    fn process(&mut self, sample_rate: f32, input: f32, x: f32) -> f32 {
+        let key = self.detector.process(input);
+        let level = self.processor.detect(input, Some(key));
+        let reduction_db = -gain_to_db(self.processor.gain_for_level(level));
+
+        if self.countdown == 0 {
+            self.countdown = COEFFICIENT_INTERVAL;
+            let gain_db = self.gain_db + self.range_db.signum() * reduction_db.min(self.range_db.abs());
+
+            if (gain_db - self.filter_gain_db).abs() > GAIN_RESOLUTION_DB {
+                self.filter_gain_db = gain_db;
+                self.update_filter(sample_rate);
+            }
+        }
+
+        self.countdown -= 1;
+
+        self.filter.process(x)
+    }
+
+    /// Update the filter coefficients for the current gain
+    fn update_filter(&mut self, sample_rate: f32) {
+        match self.shape {
+            DynamicEqShape::Bell => self.filter.set_peaking(sample_rate, self.frequency, self.q, self.filter_gain_db),
+            DynamicEqShape::LowShelf => self.filter.set_lowshelf(sample_rate, self.frequency, FRAC_1_SQRT_2, self.filter_gain_db),
+            DynamicEqShape::HighShelf => self.filter.set_highshelf(sample_rate, self.frequency, FRAC_1_SQRT_2, self.filter_gain_db)
+        }
+    }
+
+    /// Update detector and filter from the shape, frequency and Q
+    fn update_filters(&mut self, sample_rate: f32) {
+        match self.shape {
+            DynamicEqShape::Bell => self.detector.set_bandpass(sample_rate, self.frequency, self.q),
+            DynamicEqShape::LowShelf => self.detector.set_lowpass(sample_rate, self.frequency, FRAC_1_SQRT_2),
+            DynamicEqShape::HighShelf => self.detector.set_highpass(sample_rate, self.frequency, FRAC_1_SQRT_2)
+        }
+
+        self.update_filter(sample_rate);
+    }
+}
+
+/// Dynamic EQ of 1 to 8 bands in series. Each band is a bell or shelf
+/// filter whose gain is its static gain moved by up to its range, by the
+/// gain reduction of a `DynamicsProcessor` gain computer. The computer is
+/// keyed by a band-pass, low-pass or high-pass filtered copy of the input
+/// matching the band, so a band only responds to its own frequencies, as
+/// when taming a resonance that rings on some notes and not others.
+#[wasm_bindgen]
+pub struct DynamicEq {
+    sample_rate: f32,
+    band_count: usize,
+    bands: Vec<Band>,
+    input_buffer: Vec<f32>,  // Shared buffers, written and read from JS
+    output_buffer: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl DynamicEq {
+    /// Create a new DynamicEq with 1 to 8 bands
+    pub fn new(sample_rate: f32, band_count: usize) -> Self {
+        let mut bands: Vec<Band> = DEFAULT_FREQUENCIES.iter()
+            .map(|&frequency| Band::new(sample_rate, frequency))
+            .collect();
+
+        bands.iter_mut().for_each(|b| b.update_filters(sample_rate));
+
+        Self {
+            sample_rate,
+            band_count: band_count.clamp(1, MAX_BANDS),
+            bands,
+            input_buffer: Vec::new(),
+            output_buffer: Vec::new(),
+        }
+    }
+
+    // ======== Band layout ========
+
+    /// Set the number of bands (1 to 8). Bands keep their settings.
+    pub fn set_band_count(&mut self, band_count: usize) {
+        self.band_count = band_count.clamp(1, MAX_BANDS);
+    }
+
+    /// Get the number of bands
+    pub fn get_band_count(&self) -> usize {
+        self.band_count
+    }
+
+    // ======== Band parameter settings ========
+
+    /// Set band filter shape (Bell, LowShelf, HighShelf)
+    pub fn set_band_shape(&mut self, band: usize, shape: DynamicEqShape) {
+        let sample_rate = self.sample_rate;
+        if let Some(b) = self.bands.get_mut(band) { b.shape = shape; b.update_filters(sample_rate); }
+    }
+
+    /// Set band mode (Above, Below)
+    pub fn set_band_mode(&mut self, band: usize, mode: DynamicEqMode) {
+        let mode = match mode {
+            DynamicEqMode::Above => ProcessorMode::Compress,
+            DynamicEqMode::Below => ProcessorMode::Expand
+        };
+
+        if let Some(b) = self.bands.get_mut(band) { b.processor.set_mode(mode); }
+    }
+
+    /// Set band frequency in Hz
+    pub fn set_band_frequency(&mut self, band: usize, frequency: f32) {
+        let sample_rate = self.sample_rate;
+        if let Some(b) = self.bands.get_mut(band) { b.frequency = frequency; b.update_filters(sample_rate); }
+    }
+
+    /// Set the width of a bell band and its detector as Q
+    pub fn set_band_q(&mut self, band: usize, q: f32) {
+        let sample_rate = self.sample_rate;
+        if let Some(b) = self.bands.get_mut(band) { b.q = q.max(0.1); b.update_filters(sample_rate); }
+    }
+
+    /// Set band static gain in dB, the gain when the gain computer is idle
+    pub fn set_band_gain_db(&mut self, band: usize, gain_db: f32) {
+        if let Some(b) = self.bands.get_mut(band) { b.gain_db = gain_db.clamp(-40.0, 40.0); }
+    }
+
+    /// Set band threshold in dB
+    pub fn set_band_threshold_db(&mut self, band: usize, threshold_db: f32) {
+        if let Some(b) = self.bands.get_mut(band) { b.processor.set_threshold_db(threshold_db); }
+    }
+
+    /// Set band ratio (1:n)
+    pub fn set_band_ratio(&mut self, band: usize, ratio: f32) {
+        if let Some(b) = self.bands.get_mut(band) { b.processor.set_ratio(ratio); }
+    }
+
+    /// Set band range in dB, the most the gain computer moves the band
+    /// gain. Negative ranges cut and positive ranges boost.
+    pub fn set_band_range_db(&mut self, band: usize, range_db: f32) {
+        if let Some(b) = self.bands.get_mut(band) { b.range_db = range_db.clamp(-40.0, 40.0); }
+    }
+
+    /// Set band attack time in seconds
+    pub fn set_band_attack_time(&mut self, band: usize, attack_time: f32) {
+        if let Some(b) = self.bands.get_mut(band) { b.processor.set_attack_time(attack_time); }
+    }
+
+    /// Set band release time in seconds
+    pub fn set_band_release_time(&mut self, band: usize, release_time: f32) {
+        if let Some(b) = self.bands.get_mut(band) { b.processor.set_release_time(release_time); }
+    }
+
+    /// Bypass a band, passing the signal through it unfiltered
+    pub fn set_band_bypass(&mut self, band: usize, bypass: bool) {
+        if let Some(b) = self.bands.get_mut(band) { b.bypass = bypass; }
+    }
+
+    /// Get a band's current gain in dB, its static gain moved by the gain
+    /// computer. Bypassed bands report 0.
+    pub fn get_band_gain_db(&self, band: usize) -> f32 {
+        match self.bands.get(band) {
+            Some(b) if !b.bypass => b.filter_gain_db,
+            _ => 0.0
+        }
+    }
+
+    /// Get the magnitude response of the bands at their current gains in
+    /// dB, at each of `frequencies` in Hz, for drawing in the UI
+    pub fn get_response(&self, frequencies: &[f32]) -> Vec<f32> {
+        frequencies.iter()
+            .map(|&frequency| self.bands[..self.band_count].iter()
+                .filter(|b| !b.bypass)
+                .map(|b| gain_to_db(b.filter.magnitude(self.sample_rate, frequency)))
+                .sum())
+            .collect()
+    }
+
+    // ======== Processing ========
+
+    /// Process a single sample and return the processed audio. Every band
+    /// detects from the input, so cuts in one band do not change what
+    /// another responds to.
+    pub fn process_sample(&mut self, input: f32) -> f32 {
+        let sample_rate = self.sample_rate;
+
+        self.bands[..self.band_count].iter_mut()
+            .filter(|b| !b.bypass)
+            .fold(input, |x, band| band.process(sample_rate, input, x))
+    }
+
+    /// Process a block of samples from `input` into `output`
+    pub fn process_block(&mut self, input: &[f32], output: &mut [f32]) {
+        for (out, &sample) in output.iter_mut().zip(input) {
+            *out = self.process_sample(sample);
+        }
+    }
+
+    /// Size the shared block buffers for blocks of `length` samples.
+    /// Allocates, so call it when the block size changes. Pointers to the
+    /// buffers are valid until the next call.
+    pub fn set_block_size(&mut self, length: usize) {
+        self.input_buffer.resize(length, 0.0);
+        self.output_buffer.resize(length, 0.0);
+    }
+
+    /// Get a pointer to the shared input buffer in wasm memory
+    pub fn input_ptr(&mut self) -> *mut f32 {
+        self.input_buffer.as_mut_ptr()
+    }
+
+    /// Get a pointer to the shared output buffer in wasm memory
+    pub fn output_ptr(&self) -> *const f32 {
+        self.output_buffer.as_ptr()
+    }
+
+    /// Process the block in the shared input buffer into the shared
+    /// output buffer. Unlike `process_block` from JS, this does not
+    /// allocate.
+    pub fn process_buffers(&mut self) {
+        let input = std::mem::take(&mut self.input_buffer);
+        let mut output = std::mem::take(&mut self.output_buffer);
+
+        self.process_block(&input, &mut output);
+
+        self.input_buffer = input;
+        self.output_buffer = output;
+    }
+
+    /// Reset the processor state
+    pub fn reset(&mut self) {
+        for band in self.bands.iter_mut() {
+            band.detector.reset();
+            band.filter.reset();
+            band.processor.reset();
+            band.countdown = 0;
+        }
+    }
+}
@@ -1,32 +1,119 @@
 //! Envelope follower module for audio level detection.
 
 use wasm_bindgen::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::filters::{Biquad, a_weighting, c_weighting};
+use crate::loudness::k_weighting;
+use crate::utils::{gain_to_db, db_to_gain};
 
 /// Detection modes for envelope followers
 #[wasm_bindgen]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DetectionMode {
     Peak,
     RMS,
-    LogRMS   // For compatibility, but handled differently in gain domain
+    LogRMS,  // Averages power in the log domain, a geometric mean over the window
+    Adaptive // Blends peak and RMS by crest factor, peak for transient material
+}
+
+/// Signals detected from a stereo pair
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectionSource {
+    Channel, // The channel's own signal
+    Mid,     // The sum of the pair, ignoring stereo-wide content
+    Side     // The difference of the pair, the stereo-wide content
 }
 
+/// Frequency weightings applied before detection
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrequencyWeighting {
+    Flat,
+    A,       // IEC 61672 A weighting, insensitive to low frequencies
+    C,       // IEC 61672 C weighting, rolling off below 30Hz and above 8kHz
+    K        // ITU-R BS.1770 K weighting, as used for loudness
+}
+
+/// RMS averaging windows
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RmsWindow {
+    Rectangular, // Running sum over a fixed window
+    Exponential  // One-pole average with the window as time constant
+}
+
+/// Scales of the envelope follower's output, measuring the envelope before
+/// it is shaped and mapped to the output range
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvelopeScale {
+    Linear,  // The envelope as a gain
+    Decibels // The envelope in dB from the floor to 0dBFS, normalised 0 to 1
+}
+
+/// Time constant in seconds over which the slow release stage charges
+const SLOW_STAGE_ATTACK_TIME: f32 = 0.5;
+
+/// Floor on power in `LogRMS` mode, -120dB, keeping silence finite
+const LOG_POWER_FLOOR: f32 = 1e-12;
+
+/// Release time in seconds of the peak level measuring crest factor in
+/// `Adaptive` mode
+const CREST_PEAK_TIME: f32 = 0.3;
+
+/// Crest factors in dB between which `Adaptive` mode moves from RMS to
+/// peak detection, from that of a sine to that of percussive material
+const CREST_RMS_DB: f32 = 3.0;
+const CREST_PEAK_DB: f32 = 15.0;
+
 /// Envelope follower for audio level detection
 #[wasm_bindgen]
 pub struct EnvelopeFollower {
     attack_coef: f32,
     release_coef: f32,
     current_envelope: f32,
+    log_domain: bool,        // Run ballistics on the level in dB
+    // Dual-stage release: a slow stage charges from the envelope during
+    // sustained levels and holds it up with a long release
+    auto_release: bool,
+    slow_attack_coef: f32,
+    slow_release_coef: f32,
+    slow_envelope: f32,
     sample_rate: f32,
     detection_mode: DetectionMode,
+    detection_source: DetectionSource,
+    weighting: FrequencyWeighting,
+    weighting_filters: Vec<Biquad>,
+    crest_peak: f32,         // Peak level for crest factor in Adaptive mode
+    crest_coef: f32,
+    rms_window: RmsWindow,
     rms_buffer: Vec<f32>,
     rms_buffer_size: usize,
     rms_buffer_pos: usize,
+    rms_sum: f32,            // Running sum of rms_buffer
+    rms_mean: f32,           // Exponential average
+    rms_coef: f32,           // Exponential average coefficient
     // Sidechain filter parameters
     external_sidechain: bool,
     filter_enabled: bool,
     filter_freq: f32,
     filter_q: f32,
+    // Mapping of the envelope to the block output
+    output_scale: EnvelopeScale,
+    output_floor_db: f32,    // Level at the foot of the Decibels scale
+    output_min: f32,         // Output at the foot of the scale
+    output_max: f32,         // Output at the top of the scale
+    output_curve: f32,       // Exponent shaping the scaled envelope
+    // Shared block buffers, written and read from JS
+    input_buffer: Vec<f32>,
+    output_buffer: Vec<f32>,
 }
 
 #[wasm_bindgen]
@@ -34,21 +121,42 @@ impl EnvelopeFollower {
     /// Create a new envelope follower instance
     pub fn new(sample_rate: f32) -> Self {
         // Default to 50ms for RMS calculation
-        let rms_buffer_size = (0.05 * sample_rate) as usize;
+        let rms_buffer_size = ((0.05 * sample_rate) as usize).max(1);
         
         Self {
             attack_coef: 0.0,
             release_coef: 0.0,
             current_envelope: 0.0,
+            log_domain: false,
+            auto_release: false,
+            slow_attack_coef: (-2.2 / (SLOW_STAGE_ATTACK_TIME * sample_rate)).exp(),
+            slow_release_coef: (-2.2 / (1.5 * sample_rate)).exp(),
+            slow_envelope: 0.0,
             sample_rate,
             detection_mode: DetectionMode::Peak,
+            detection_source: DetectionSource::Channel,
+            weighting: FrequencyWeighting::Flat,
+            weighting_filters: Vec::new(),
+            crest_peak: 0.0,
+            crest_coef: (-2.2 / (CREST_PEAK_TIME * sample_rate)).exp(),
+            rms_window: RmsWindow::Rectangular,
             rms_buffer: vec![0.0; rms_buffer_size],
             rms_buffer_size,
             rms_buffer_pos: 0,
+            rms_sum: 0.0,
+            rms_mean: 0.0,
+            rms_coef: (-1.0 / rms_buffer_size as f32).exp(),
             external_sidechain: false,
             filter_enabled: false,
             filter_freq: 1000.0,
             filter_q: 0.7,
+            output_scale: EnvelopeScale::Linear,
+            output_floor_db: -60.0,
+            output_min: 0.0,
+            output_max: 1.0,
+            output_curve: 1.0,
+            input_buffer: Vec::new(),
+            output_buffer: Vec::new(),
         }
     }
     
@@ -72,9 +180,61 @@ impl EnvelopeFollower {
         };
     }
     
+    /// Run attack and release on the level in dB, so that the envelope
+    /// releases at a steady rate in dB as a VCA detector does
+    pub fn set_log_domain(&mut self, enabled: bool) {
+        self.log_domain = enabled;
+    }
+    
+    /// Enable program-dependent dual-stage release. Transients release at
+    /// the release time while sustained levels release at the slow release
+    /// time, like an optical cell.
+    pub fn set_auto_release(&mut self, enabled: bool) {
+        self.auto_release = enabled;
+    }
+    
+    /// Get whether dual-stage auto release is enabled
+    pub fn get_auto_release(&self) -> bool {
+        self.auto_release
+    }
+    
+    /// Set slow stage release time in seconds for auto release
+    pub fn set_slow_release_time(&mut self, release_time: f32) {
+        self.slow_release_coef = if release_time <= 0.0 {
+            0.0
+        } else {
+            (-2.2 / (release_time * self.sample_rate)).exp()
+        };
+    }
+    
     /// Set detection mode
     pub fn set_detection_mode(&mut self, mode: DetectionMode) {
         self.detection_mode = mode;
+        // The buffer holds power or log power depending on mode, so refill it
+        self.clear_rms_buffer();
+    }
+    
+    /// Set RMS window length in ms
+    pub fn set_rms_window_ms(&mut self, window_ms: f32) {
+        let size = ((window_ms * 0.001 * self.sample_rate) as usize).max(1);
+        
+        if size != self.rms_buffer_size {
+            self.rms_buffer_size = size;
+            self.rms_buffer.resize(size, 0.0);
+            self.rms_coef = (-1.0 / size as f32).exp();
+            self.clear_rms_buffer();
+        }
+    }
+    
+    /// Set RMS averaging window type
+    pub fn set_rms_window(&mut self, window: RmsWindow) {
+        self.rms_window = window;
+        self.clear_rms_buffer();
+    }
+    
+    /// Get RMS averaging window type
+    pub fn get_rms_window(&self) -> RmsWindow {
+        self.rms_window
     }
     
     /// Get the current detection mode
@@ -82,6 +242,42 @@ impl EnvelopeFollower {
         self.detection_mode
     }
     
+    /// Set the signal detected from a stereo pair, see `select_source`
+    pub fn set_detection_source(&mut self, source: DetectionSource) {
+        self.detection_source = source;
+    }
+    
+    /// Get the current detection source
+    pub fn get_detection_source(&self) -> DetectionSource {
+        self.detection_source
+    }
+    
+    /// Set the frequency weighting applied before detection
+    pub fn set_weighting(&mut self, weighting: FrequencyWeighting) {
+        self.weighting = weighting;
+        self.weighting_filters = match weighting {
+            FrequencyWeighting::Flat => Vec::new(),
+            FrequencyWeighting::A => a_weighting(self.sample_rate),
+            FrequencyWeighting::C => c_weighting(self.sample_rate),
+            FrequencyWeighting::K => k_weighting(self.sample_rate).to_vec()
+        };
+    }
+    
+    /// Get the current frequency weighting
+    pub fn get_weighting(&self) -> FrequencyWeighting {
+        self.weighting
+    }
+    
+    /// Select the signal to detect for a channel of a stereo pair,
+    /// according to the detection source
+    pub fn select_source(&self, channel: f32, left: f32, right: f32) -> f32 {
+        match self.detection_source {
+            DetectionSource::Channel => channel,
+            DetectionSource::Mid => (left + right) * 0.5,
+            DetectionSource::Side => (left - right) * 0.5
+        }
+    }
+    
     /// Set external sidechain
     pub fn set_external_sidechain(&mut self, enabled: bool) {
         self.external_sidechain = enabled;
@@ -102,58 +298,217 @@ impl EnvelopeFollower {
         self.filter_q = q;
     }
     
+    /// Set the scale of the block output (Linear, Decibels)
+    pub fn set_output_scale(&mut self, scale: EnvelopeScale) {
+        self.output_scale = scale;
+    }
+    
+    /// Set the level in dB at the foot of the Decibels scale, where the
+    /// output is at its minimum
+    pub fn set_output_floor_db(&mut self, floor_db: f32) {
+        self.output_floor_db = floor_db.clamp(-120.0, -1.0);
+    }
+    
+    /// Set the range of the block output. The scaled envelope maps 0 to
+    /// `min` and 1 to `max`, so a range of 200 to 2000 drives a cutoff in
+    /// Hz, and a maximum below the minimum inverts the envelope.
+    pub fn set_output_range(&mut self, min: f32, max: f32) {
+        self.output_min = min;
+        self.output_max = max;
+    }
+    
+    /// Set the curve of the block output as an exponent (0.1 to 10) on the
+    /// scaled envelope. Above 1 the output rises late, below 1 early.
+    pub fn set_output_curve(&mut self, curve: f32) {
+        self.output_curve = curve.clamp(0.1, 10.0);
+    }
+    
+    /// Follow a block of samples from `input`, writing the envelope to
+    /// `output` scaled, shaped and mapped to the output range, as a
+    /// modulation signal
+    pub fn process_block(&mut self, input: &[f32], output: &mut [f32]) {
+        for (out, &sample) in output.iter_mut().zip(input) {
+            let envelope = self.process(sample);
+            *out = self.map_output(envelope);
+        }
+    }
+    
+    /// Size the shared block buffers for blocks of `length` samples.
+    /// Allocates, so call it when the block size changes. Pointers to the
+    /// buffers are valid until the next call.
+    pub fn set_block_size(&mut self, length: usize) {
+        self.input_buffer.resize(length, 0.0);
+        self.output_buffer.resize(length, 0.0);
+    }
+    
+    /// Get a pointer to the shared input buffer in wasm memory
+    pub fn input_ptr(&mut self) -> *mut f32 {
+        self.input_buffer.as_mut_ptr()
+    }
+    
+    /// Get a pointer to the shared output buffer in wasm memory
+    pub fn output_ptr(&self) -> *const f32 {
+        self.output_buffer.as_ptr()
+    }
+    
+    /// Follow the block in the shared input buffer, writing the mapped
+    /// envelope to the shared output buffer as `process_block` does.
+    /// Unlike `process_block` from JS, this does not allocate.
+    pub fn process_buffers(&mut self) {
+        let input = std::mem::take(&mut self.input_buffer);
+        let mut output = std::mem::take(&mut self.output_buffer);
+        
+        self.process_block(&input, &mut output);
+        
+        self.input_buffer = input;
+        self.output_buffer = output;
+    }
+    
+    /// Get the current envelope mapped as the block output
+    pub fn get_output(&self) -> f32 {
+        self.map_output(self.get_envelope())
+    }
+    
     /// Process a sample and update the envelope
     /// Returns the envelope level as a linear gain value (0.0 to 1.0+)
     pub fn process(&mut self, input: f32) -> f32 {
+        let weighted = self.weight(input);
+        self.follow(weighted)
+    }
+    
+    /// Get the current envelope value
+    pub fn get_envelope(&self) -> f32 {
+        if self.auto_release {
+            self.current_envelope.max(self.slow_envelope)
+        } else {
+            self.current_envelope
+        }
+    }
+    
+    /// Reset the envelope follower
+    pub fn reset(&mut self) {
+        self.current_envelope = 0.0;
+        self.slow_envelope = 0.0;
+        self.crest_peak = 0.0;
+        self.weighting_filters.iter_mut().for_each(Biquad::reset);
+        self.clear_rms_buffer();
+    }
+}
+
+impl EnvelopeFollower {
+    /// Apply the frequency weighting to a sample
+    #[inline]
+    pub fn weight(&mut self, input: f32) -> f32 {
+        self.weighting_filters.iter_mut().fold(input, |x, filter| filter.process(x))
+    }
+    
+    /// Update the envelope from a weighted sample and return the envelope
+    /// level as a linear gain value
+    pub fn follow(&mut self, input: f32) -> f32 {
         // Calculate detection value based on mode
         let detected = match self.detection_mode {
             DetectionMode::Peak => input.abs(),
             
             DetectionMode::RMS => {
-                // Update RMS buffer
-                self.rms_buffer[self.rms_buffer_pos] = input * input;
-                self.rms_buffer_pos = (self.rms_buffer_pos + 1) % self.rms_buffer_size;
-                
                 // Calculate RMS (already in gain domain)
-                let sum: f32 = self.rms_buffer.iter().sum();
-                (sum / self.rms_buffer_size as f32).sqrt()
+                self.average(input * input).max(0.0).sqrt()
             },
             
             DetectionMode::LogRMS => {
-                // For compatibility we treat this the same as RMS but convert back to gain
-                // Update RMS buffer
-                self.rms_buffer[self.rms_buffer_pos] = input * input;
-                self.rms_buffer_pos = (self.rms_buffer_pos + 1) % self.rms_buffer_size;
-                
-                // Calculate RMS (already in gain domain)
-                let sum: f32 = self.rms_buffer.iter().sum();
-                (sum / self.rms_buffer_size as f32).sqrt()
+                // Average log power and convert back to gain
+                let log_power = (input * input).max(LOG_POWER_FLOOR).ln();
+                (self.average(log_power) * 0.5).exp()
+            },
+            
+            DetectionMode::Adaptive => {
+                // Measure crest factor from a decaying peak and the RMS,
+                // and move from RMS towards peak as it rises
+                let peak = input.abs();
+                let rms = self.average(input * input).max(0.0).sqrt();
+                self.crest_peak = peak.max(self.crest_coef * self.crest_peak);
+                let crest_db = gain_to_db(self.crest_peak) - gain_to_db(rms);
+                let amount = ((crest_db - CREST_RMS_DB) / (CREST_PEAK_DB - CREST_RMS_DB)).clamp(0.0, 1.0);
+                rms + (peak - rms) * amount
             }
         };
         
         // Apply envelope detection with different attack/release times
-        if detected > self.current_envelope {
-            // Attack phase
-            self.current_envelope = self.attack_coef * (self.current_envelope - detected) + detected;
+        let coef = if detected > self.current_envelope { self.attack_coef } else { self.release_coef };
+        
+        self.current_envelope = if self.log_domain {
+            let (envelope_db, detected_db) = (gain_to_db(self.current_envelope), gain_to_db(detected));
+            db_to_gain(coef * (envelope_db - detected_db) + detected_db)
         } else {
-            // Release phase
-            self.current_envelope = self.release_coef * (self.current_envelope - detected) + detected;
+            coef * (self.current_envelope - detected) + detected
+        };
+        
+        if self.auto_release {
+            // Slow stage follows the envelope, charging slowly and releasing slowly
+            let coef = if self.current_envelope > self.slow_envelope {
+                self.slow_attack_coef
+            } else {
+                self.slow_release_coef
+            };
+            self.slow_envelope = coef * (self.slow_envelope - self.current_envelope) + self.current_envelope;
         }
         
-        self.current_envelope
+        self.get_envelope()
     }
     
-    /// Get the current envelope value
-    pub fn get_envelope(&self) -> f32 {
-        self.current_envelope
+    /// Scale, shape and map an envelope level to the output range. With the
+    /// defaults, a Linear scale from 0 to 1 and a curve of 1, the output is
+    /// the envelope.
+    fn map_output(&self, envelope: f32) -> f32 {
+        let position = match self.output_scale {
+            EnvelopeScale::Linear => envelope,
+            EnvelopeScale::Decibels => (1.0 - gain_to_db(envelope) / self.output_floor_db).clamp(0.0, 1.0)
+        };
+        
+        let shaped = if self.output_curve == 1.0 {
+            position
+        } else {
+            position.powf(self.output_curve)
+        };
+        
+        self.output_min + (self.output_max - self.output_min) * shaped
     }
     
-    /// Reset the envelope follower
-    pub fn reset(&mut self) {
-        self.current_envelope = 0.0;
-        for i in 0..self.rms_buffer_size {
-            self.rms_buffer[i] = 0.0;
+    /// Push a value into the RMS average and return the mean, in O(1)
+    #[inline]
+    fn average(&mut self, value: f32) -> f32 {
+        match self.rms_window {
+            RmsWindow::Rectangular => {
+                // Update running sum with the value replacing the oldest
+                let oldest = std::mem::replace(&mut self.rms_buffer[self.rms_buffer_pos], value);
+                self.rms_sum += value - oldest;
+                self.rms_buffer_pos += 1;
+                
+                // Re-sum once per window to discard accumulated rounding error
+                if self.rms_buffer_pos == self.rms_buffer_size {
+                    self.rms_buffer_pos = 0;
+                    self.rms_sum = self.rms_buffer.iter().sum();
+                }
+                
+                self.rms_sum / self.rms_buffer_size as f32
+            },
+            
+            RmsWindow::Exponential => {
+                self.rms_mean = self.rms_coef * (self.rms_mean - value) + value;
+                self.rms_mean
+            }
         }
+    }
+    
+    /// Fill the RMS buffer with silence for the current detection mode
+    fn clear_rms_buffer(&mut self) {
+        let silence = match self.detection_mode {
+            DetectionMode::LogRMS => LOG_POWER_FLOOR.ln(),
+            _ => 0.0
+        };
+        
+        self.rms_buffer.fill(silence);
         self.rms_buffer_pos = 0;
+        self.rms_sum = silence * self.rms_buffer_size as f32;
+        self.rms_mean = silence;
     }
 }
\ No newline at end of file
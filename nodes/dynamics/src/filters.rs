@@ -0,0 +1,278 @@
+//! Biquad filters, Linkwitz-Riley crossovers and weighting filters.
+
+use std::f32::consts::{PI, FRAC_1_SQRT_2};
+
+/// Second order IIR filter in transposed direct form II, with coefficients
+/// from the RBJ audio EQ cookbook
+#[derive(Clone)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    /// Create a pass-through filter
+    pub fn new() -> Self {
+        Self { b0: 1.0, b1: 0.0, b2: 0.0, a1: 0.0, a2: 0.0, z1: 0.0, z2: 0.0 }
+    }
+
+    /// Create a filter from unnormalised coefficients
+    pub fn from_raw(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        let mut filter = Self::new();
+        filter.set_raw(b0, b1, b2, a0, a1, a2);
+        filter
+    }
+
+    pub fn lowpass(sample_rate: f32, freq: f32, q: f32) -> Self {
+        let mut filter = Self::new();
+        filter.set_lowpass(sample_rate, freq, q);
+        filter
+    }
+
+    pub fn highpass(sample_rate: f32, freq: f32, q: f32) -> Self {
+        let mut filter = Self::new();
+        filter.set_highpass(sample_rate, freq, q);
+        filter
+    }
+
+    pub fn bandpass(sample_rate: f32, freq: f32, q: f32) -> Self {
+        let mut filter = Self::new();
+        filter.set_bandpass(sample_rate, freq, q);
+        filter
+    }
+
+    pub fn allpass(sample_rate: f32, freq: f32, q: f32) -> Self {
+        let mut filter = Self::new();
+        filter.set_allpass(sample_rate, freq, q);
+        filter
+    }
+
+    /// Set coefficients from unnormalised values
+    fn set_raw(&mut self, b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) {
+        self.b0 = b0 / a0;
+        self.b1 = b1 / a0;
+        self.b2 = b2 / a0;
+        self.a1 = a1 / a0;
+        self.a2 = a2 / a0;
+    }
+
+    pub fn set_lowpass(&mut self, sample_rate: f32, freq: f32, q: f32) {
+        let (cos, alpha) = Self::omega(sample_rate, freq, q);
+        self.set_raw((1.0 - cos) * 0.5, 1.0 - cos, (1.0 - cos) * 0.5, 1.0 + alpha, -2.0 * cos, 1.0 - alpha);
+    }
+
+    pub fn set_highpass(&mut self, sample_rate: f32, freq: f32, q: f32) {
+        let (cos, alpha) = Self::omega(sample_rate, freq, q);
+        self.set_raw((1.0 + cos) * 0.5, -(1.0 + cos), (1.0 + cos) * 0.5, 1.0 + alpha, -2.0 * cos, 1.0 - alpha);
+    }
+
+    /// Set band-pass coefficients, with 0dB gain at the centre frequency
+    pub fn set_bandpass(&mut self, sample_rate: f32, freq: f32, q: f32) {
+        let (cos, alpha) = Self::omega(sample_rate, freq, q);
+        self.set_raw(alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha);
+    }
+
+    pub fn set_allpass(&mut self, sample_rate: f32, freq: f32, q: f32) {
+        let (cos, alpha) = Self::omega(sample_rate, freq, q);
+        self.set_raw(1.0 - alpha, -2.0 * cos, 1.0 + alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha);
+    }
+
+    /// Set peaking (bell) coefficients, with `gain_db` at the centre
+    /// frequency and width set by Q
+    pub fn set_peaking(&mut self, sample_rate: f32, freq: f32, q: f32, gain_db: f32) {
+        let (cos, alpha) = Self::omega(sample_rate, freq, q);
+        let a = 10.0f32.powf(gain_db / 40.0);
+        self.set_raw(1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a, 1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a);
+    }
+
+    /// Set low shelf coefficients, with `gain_db` below the frequency and
+    /// the slope of the corner set by Q, where 0.707 is the steepest
+    /// without overshoot
+    pub fn set_lowshelf(&mut self, sample_rate: f32, freq: f32, q: f32, gain_db: f32) {
+        let (cos, alpha) = Self::omega(sample_rate, freq, q);
+        let a = 10.0f32.powf(gain_db / 40.0);
+        let k = 2.0 * a.sqrt() * alpha;
+        self.set_raw(
+            a * ((a + 1.0) - (a - 1.0) * cos + k),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+            a * ((a + 1.0) - (a - 1.0) * cos - k),
+            (a + 1.0) + (a - 1.0) * cos + k,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+            (a + 1.0) + (a - 1.0) * cos - k
+        );
+    }
+
+    /// Set high shelf coefficients, with `gain_db` above the frequency
+    pub fn set_highshelf(&mut self, sample_rate: f32, freq: f32, q: f32, gain_db: f32) {
+        let (cos, alpha) = Self::omega(sample_rate, freq, q);
+        let a = 10.0f32.powf(gain_db / 40.0);
+        let k = 2.0 * a.sqrt() * alpha;
+        self.set_raw(
+            a * ((a + 1.0) + (a - 1.0) * cos + k),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+            a * ((a + 1.0) + (a - 1.0) * cos - k),
+            (a + 1.0) - (a - 1.0) * cos + k,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos),
+            (a + 1.0) - (a - 1.0) * cos - k
+        );
+    }
+
+    /// Create a filter from a 2nd order analogue prototype with numerator
+    /// `b` and denominator `a` in ascending powers of s, by the bilinear
+    /// transform
+    fn from_analog(sample_rate: f64, b: [f64; 3], a: [f64; 3]) -> Self {
+        let c = 2.0 * sample_rate;
+        let c2 = c * c;
+
+        Self::from_raw(
+            (b[0] + b[1] * c + b[2] * c2) as f32,
+            (2.0 * (b[0] - b[2] * c2)) as f32,
+            (b[0] - b[1] * c + b[2] * c2) as f32,
+            (a[0] + a[1] * c + a[2] * c2) as f32,
+            (2.0 * (a[0] - a[2] * c2)) as f32,
+            (a[0] - a[1] * c + a[2] * c2) as f32
+        )
+    }
+
+    /// Magnitude of the frequency response at a frequency
+    pub(crate) fn magnitude(&self, sample_rate: f32, freq: f32) -> f32 {
+        let w = 2.0 * PI * freq / sample_rate;
+        let (cos1, sin1, cos2, sin2) = (w.cos(), w.sin(), (2.0 * w).cos(), (2.0 * w).sin());
+        let num = (self.b0 + self.b1 * cos1 + self.b2 * cos2, self.b1 * sin1 + self.b2 * sin2);
+        let den = (1.0 + self.a1 * cos1 + self.a2 * cos2, self.a1 * sin1 + self.a2 * sin2);
+        (num.0.hypot(num.1)) / (den.0.hypot(den.1))
+    }
+
+    /// Scale the output of the filter
+    fn scale(&mut self, gain: f32) {
+        self.b0 *= gain;
+        self.b1 *= gain;
+        self.b2 *= gain;
+    }
+
+    /// Return cos(w0) and alpha for a frequency clamped below Nyquist
+    fn omega(sample_rate: f32, freq: f32, q: f32) -> (f32, f32) {
+        let freq = freq.clamp(1.0, sample_rate * 0.49);
+        let w0 = 2.0 * PI * freq / sample_rate;
+        (w0.cos(), w0.sin() / (2.0 * q.max(0.01)))
+    }
+
+    /// Filter a sample
+    #[inline]
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = self.b0 * input + self.z1;
+        self.z1 = self.b1 * input - self.a1 * output + self.z2;
+        self.z2 = self.b2 * input - self.a2 * output;
+        output
+    }
+
+    /// Clear the filter state
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
+
+impl Default for Biquad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 4th order Linkwitz-Riley crossover. The low and high outputs sum to a
+/// 2nd order allpass at the crossover frequency, so bands recombine flat.
+#[derive(Clone)]
+pub struct LinkwitzRiley {
+    lowpass: [Biquad; 2],
+    highpass: [Biquad; 2],
+}
+
+impl LinkwitzRiley {
+    pub fn new(sample_rate: f32, freq: f32) -> Self {
+        let lowpass = Biquad::lowpass(sample_rate, freq, FRAC_1_SQRT_2);
+        let highpass = Biquad::highpass(sample_rate, freq, FRAC_1_SQRT_2);
+
+        Self {
+            lowpass: [lowpass.clone(), lowpass],
+            highpass: [highpass.clone(), highpass],
+        }
+    }
+
+    /// Return the allpass that matches the phase of this crossover's summed
+    /// output, for aligning bands that have not passed through it
+    pub fn allpass(sample_rate: f32, freq: f32) -> Biquad {
+        Biquad::allpass(sample_rate, freq, FRAC_1_SQRT_2)
+    }
+
+    /// Move the crossover frequency, keeping the filter state so that a
+    /// moving crossover does not click
+    pub fn set_freq(&mut self, sample_rate: f32, freq: f32) {
+        self.lowpass.iter_mut().for_each(|filter| filter.set_lowpass(sample_rate, freq, FRAC_1_SQRT_2));
+        self.highpass.iter_mut().for_each(|filter| filter.set_highpass(sample_rate, freq, FRAC_1_SQRT_2));
+    }
+
+    /// Move an allpass made by `allpass` to a new crossover frequency
+    pub fn set_allpass_freq(allpass: &mut Biquad, sample_rate: f32, freq: f32) {
+        allpass.set_allpass(sample_rate, freq, FRAC_1_SQRT_2);
+    }
+
+    /// Split a sample into (low, high)
+    #[inline]
+    pub fn process(&mut self, input: f32) -> (f32, f32) {
+        let low = self.lowpass[0].process(input);
+        let high = self.highpass[0].process(input);
+        (self.lowpass[1].process(low), self.highpass[1].process(high))
+    }
+
+    pub fn reset(&mut self) {
+        self.lowpass.iter_mut().chain(self.highpass.iter_mut()).for_each(Biquad::reset);
+    }
+}
+
+/// Pole frequencies of the A and C weighting curves of IEC 61672 in Hz
+const WEIGHTING_POLES: [f64; 4] = [20.598997, 107.65265, 737.86223, 12194.217];
+
+/// Analogue pole frequency in rad/s, prewarped so that the digital pole
+/// lands on `freq`
+fn prewarp(sample_rate: f64, freq: f64) -> f64 {
+    2.0 * sample_rate * (std::f64::consts::PI * freq / sample_rate).tan()
+}
+
+/// Normalise a cascade of filters to 0dB at 1kHz
+fn normalise(mut filters: Vec<Biquad>, sample_rate: f32) -> Vec<Biquad> {
+    let gain: f32 = filters.iter().map(|filter| filter.magnitude(sample_rate, 1000.0)).product();
+    filters[0].scale(1.0 / gain);
+    filters
+}
+
+/// C weighting filter, a 2nd order high-pass and a 2nd order low-pass
+pub fn c_weighting(sample_rate: f32) -> Vec<Biquad> {
+    let sr = sample_rate as f64;
+    let w1 = prewarp(sr, WEIGHTING_POLES[0]);
+    let w4 = prewarp(sr, WEIGHTING_POLES[3]);
+
+    normalise(vec![
+        Biquad::from_analog(sr, [0.0, 0.0, 1.0], [w1 * w1, 2.0 * w1, 1.0]),
+        Biquad::from_analog(sr, [w4 * w4, 0.0, 0.0], [w4 * w4, 2.0 * w4, 1.0]),
+    ], sample_rate)
+}
+
+/// A weighting filter, C weighting with a further 2nd order high-pass
+pub fn a_weighting(sample_rate: f32) -> Vec<Biquad> {
+    let sr = sample_rate as f64;
+    let w1 = prewarp(sr, WEIGHTING_POLES[0]);
+    let w2 = prewarp(sr, WEIGHTING_POLES[1]);
+    let w3 = prewarp(sr, WEIGHTING_POLES[2]);
+    let w4 = prewarp(sr, WEIGHTING_POLES[3]);
+
+    normalise(vec![
+        Biquad::from_analog(sr, [0.0, 0.0, 1.0], [w1 * w1, 2.0 * w1, 1.0]),
+        Biquad::from_analog(sr, [0.0, 0.0, 1.0], [w2 * w3, w2 + w3, 1.0]),
+        Biquad::from_analog(sr, [w4 * w4, 0.0, 0.0], [w4 * w4, 2.0 * w4, 1.0]),
+    ], sample_rate)
+}
@@ -1,12 +1,76 @@
 mod utils;
 mod envelope;
 mod algorithms;
+mod multichannel;
+mod filters;
+mod multiband;
+mod meter;
+mod curve;
+mod saturation;
+mod detector;
+mod deesser;
+mod transient;
+mod loudness;
+mod agc;
+mod automation;
+mod settings;
+mod dynamic_eq;
 
 use wasm_bindgen::prelude::*;
+use serde::{Serialize, Deserialize};
 use std::collections::VecDeque;
+use std::f32::consts::FRAC_1_SQRT_2;
 
-pub use envelope::{EnvelopeFollower, DetectionMode};
-pub use algorithms::{CompressionCharacter, ProcessorMode};
+pub use envelope::{EnvelopeFollower, EnvelopeScale, DetectionMode, DetectionSource, FrequencyWeighting, RmsWindow};
+pub use algorithms::{CompressionCharacter, ProcessorMode, DynamicsCalculator, CustomCurve, CurveInterpolation};
+pub use multichannel::{MultichannelDynamics, LinkMode};
+pub use multiband::MultibandDynamics;
+pub use meter::DynamicsMeter;
+pub use curve::transfer_curve;
+pub use saturation::Saturator;
+pub use deesser::{DeEsser, DeEsserMode, DeEsserFilter};
+pub use transient::TransientShaper;
+pub use loudness::LoudnessMeter;
+pub use agc::AutoGain;
+pub use automation::{Param, RampCurve};
+pub use settings::{DynamicsSettings, PRESET_NAMES};
+pub use dynamic_eq::{DynamicEq, DynamicEqShape, DynamicEqMode};
+
+use algorithms::{get_calculator, Calculator};
+use meter::{MeterAccumulator, GainHistory};
+use detector::{Detector, DetectorModel, coefficient};
+use filters::{Biquad, LinkwitzRiley};
+use automation::Automation;
+
+/// Time constant in seconds of the average gain reduction used by
+/// measured auto makeup
+const MEASURED_MAKEUP_TIME: f32 = 3.0;
+
+/// Largest gain in dB that measured auto makeup applies
+const MAX_MEASURED_MAKEUP_DB: f32 = 24.0;
+
+/// Default time in seconds over which parameter changes are smoothed
+const DEFAULT_RAMP_TIME: f32 = 0.02;
+
+/// Makeup gain modes
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MakeupMode {
+    Manual,   // Makeup gain only
+    Static,   // Compensate the gain reduction of the static curve at 0dB
+    Measured  // Compensate the measured average gain reduction, up to 24dB
+}
+
+/// Detection topologies
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Topology {
+    FeedForward, // Detect from the input or sidechain
+    FeedBack,    // Detect from the previous compressed output sample
+    Hybrid       // Detect from the geometric mean of input and previous output
+}
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global allocator.
 #[cfg(feature = "wee_alloc")]
@@ -17,22 +81,62 @@ static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 #[wasm_bindgen]
 pub struct DynamicsProcessor {
     envelope: EnvelopeFollower,
-    threshold: f32,          // 0.0 to 1.0 in gain domain
+    detector: Detector,      // Models the time behaviour of the character
+    detector_level: f32,     // Last detected level in gain domain
+    threshold_db: f32,       // Threshold in dB
     ratio: f32,              // Compression ratio (n:1)
-    knee_width: f32,         // 0.0 to 1.0 in gain domain
+    knee_db: f32,            // Knee width in dB
     makeup_gain: f32,        // Linear gain multiplier
+    makeup_mode: MakeupMode,
+    auto_makeup_gain: f32,   // Linear gain multiplier from auto makeup
+    average_gain_db: f32,    // Average gain change for measured makeup
+    average_coef: f32,
     output_gain: f32,        // Linear gain multiplier
     character: CompressionCharacter,
+    calculator: Calculator,  // Gain computer of the character
+    saturator: Saturator,
     mode: ProcessorMode,
+    topology: Topology,
+    topology_override: Option<Topology>, // Set by the caller, kept over the character's
+    drive_override: Option<f32>,
+    colour_override: Option<f32>,
+    range_db: f32,           // Depth of ducking in dB
+    hold_samples: usize,     // Time ducking holds after the key falls
+    hold_counter: usize,
+    hysteresis_db: f32,      // Gate closes this far below threshold
+    gate_open: bool,
+    duck_gain_db: f32,       // Ducking gain after hold and ramps
+    duck_attack_coef: f32,
+    duck_release_coef: f32,
+    feedback: f32,           // Previous compressed sample, before makeup
     lookahead_buffer: VecDeque<f32>,
     lookahead_samples: usize,
     current_gain: f32,       // Current gain reduction as multiplier
+    meter: DynamicsMeter,    // Levels over the last block
+    gain_history: GainHistory,
     sample_rate: f32,
     mix: f32,                // 0.0 = dry, 1.0 = wet
+    wet_gain: f32,           // Linear gain of the compressed path
+    dry_gain: f32,           // Linear gain of the dry path
+    wet_highpass_freq: f32,  // 0.0 when off
+    wet_lowpass_freq: f32,   // 0.0 when off
+    wet_filters: Vec<Biquad>,
+    dry_filters: Vec<Biquad>,   // Wet filters on the dry signal, for the band they reject
+    dry_allpasses: Vec<Biquad>, // Match the phase of the wet filters
     sidechain_external: bool,
     sidechain_filter_enabled: bool,
     sidechain_filter_freq: f32,
     sidechain_filter_q: f32,
+    sidechain_filter: Biquad,
+    automation: Automation,  // Smoothing and scheduled parameter changes
+    // Settings as set, where the processor keeps them in other units
+    attack_time: f32,
+    release_time: f32,
+    slow_release_time: f32,
+    hold_time: f32,
+    lookahead_ms: f32,
+    rms_window_ms: f32,
+    ramp_time: f32,
 }
 
 #[wasm_bindgen]
@@ -48,24 +152,67 @@ impl DynamicsProcessor {
         envelope.set_release_time(0.25); // 250ms release
         envelope.set_detection_mode(DetectionMode::RMS);
         
+        // Automation starts from the defaults below, in automation units
+        let mut automation = Automation::new(sample_rate, [-18.0, 4.0, 6.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+        automation.set_ramp_time(DEFAULT_RAMP_TIME);
+        
         Self {
             envelope,
-            threshold: 0.125,         // ~-18dB as gain value (10^(-18/20))
+            detector: Detector::new(sample_rate),
+            detector_level: 0.0,
+            threshold_db: -18.0,
             ratio: 4.0,
-            knee_width: 0.5,          // ~6dB as gain ratio
+            knee_db: 6.0,
             makeup_gain: 1.0,         // Unity gain
+            makeup_mode: MakeupMode::Manual,
+            auto_makeup_gain: 1.0,
+            average_gain_db: 0.0,
+            average_coef: (-1.0 / (MEASURED_MAKEUP_TIME * sample_rate)).exp(),
             output_gain: 1.0,         // Unity gain
             character: CompressionCharacter::Clean,
+            calculator: get_calculator(&CompressionCharacter::Clean),
+            saturator: Saturator::new(sample_rate),
             mode: ProcessorMode::Compress,
+            topology: Topology::FeedForward,
+            topology_override: None,
+            drive_override: None,
+            colour_override: None,
+            range_db: 12.0,
+            hold_samples: 0,
+            hold_counter: 0,
+            hysteresis_db: 0.0,
+            gate_open: false,
+            duck_gain_db: 0.0,
+            duck_attack_coef: coefficient(0.003, sample_rate),
+            duck_release_coef: coefficient(0.25, sample_rate),
+            feedback: 0.0,
             lookahead_buffer: VecDeque::new(),
             lookahead_samples: 0,
             current_gain: 1.0,        // No gain reduction
+            meter: DynamicsMeter::new(),
+            gain_history: GainHistory::new(),
             sample_rate,
             mix: 1.0,
+            wet_gain: 1.0,
+            dry_gain: 1.0,
+            wet_highpass_freq: 0.0,
+            wet_lowpass_freq: 0.0,
+            wet_filters: Vec::new(),
+            dry_filters: Vec::new(),
+            dry_allpasses: Vec::new(),
             sidechain_external: false,
             sidechain_filter_enabled: false,
             sidechain_filter_freq: 1000.0,
             sidechain_filter_q: 0.7,
+            sidechain_filter: Biquad::bandpass(sample_rate, 1000.0, 0.7),
+            automation,
+            attack_time: 0.003,
+            release_time: 0.25,
+            slow_release_time: 1.5,
+            hold_time: 0.0,
+            lookahead_ms: 0.0,
+            rms_window_ms: 50.0,
+            ramp_time: DEFAULT_RAMP_TIME,
         }
     }
     
@@ -73,41 +220,87 @@ impl DynamicsProcessor {
     
     /// Set threshold as gain value (0.0 to 1.0)
     pub fn set_threshold(&mut self, threshold: f32) {
-        self.threshold = threshold.max(0.0).min(1.0);
+        self.set_param(Param::Threshold, utils::gain_to_db(threshold.clamp(0.0, 1.0)));
+    }
+    
+    /// Set threshold in dB (-120 to 0)
+    pub fn set_threshold_db(&mut self, threshold_db: f32) {
+        self.set_param(Param::Threshold, threshold_db);
     }
     
     /// Set ratio (1:n)
     pub fn set_ratio(&mut self, ratio: f32) {
-        self.ratio = ratio.max(1.0);
+        self.set_param(Param::Ratio, ratio);
     }
     
-    /// Set knee width as gain ratio (0.0 to 1.0)
+    /// Set knee width (0.0 to 1.0), where 0.0 is a hard knee and 1.0 is
+    /// 40dB, widening in proportion between
     pub fn set_knee_width(&mut self, knee_width: f32) {
-        self.knee_width = knee_width.max(0.0).min(1.0);
+        self.set_param(Param::Knee, algorithms::knee_width_to_db(knee_width));
+    }
+    
+    /// Set knee width in dB (0 for a hard knee)
+    pub fn set_knee_db(&mut self, knee_db: f32) {
+        self.set_param(Param::Knee, knee_db);
     }
     
     /// Set attack time in seconds
     pub fn set_attack_time(&mut self, attack_time: f32) {
-        self.envelope.set_attack_time(attack_time);
+        self.attack_time = attack_time;
+        self.envelope.set_attack_time(self.detector.attack_time(attack_time));
+        self.duck_attack_coef = coefficient(attack_time, self.sample_rate);
     }
     
     /// Set release time in seconds
     pub fn set_release_time(&mut self, release_time: f32) {
+        self.release_time = release_time;
         self.envelope.set_release_time(release_time);
+        self.duck_release_coef = coefficient(release_time, self.sample_rate);
     }
     
     /// Set makeup gain as linear gain multiplier
     pub fn set_makeup_gain(&mut self, makeup_gain: f32) {
-        self.makeup_gain = makeup_gain.max(0.0);
+        self.set_param(Param::Makeup, utils::gain_to_db(makeup_gain.max(0.0)));
+    }
+    
+    /// Set makeup gain in dB
+    pub fn set_makeup_gain_db(&mut self, makeup_db: f32) {
+        self.set_param(Param::Makeup, makeup_db);
+    }
+    
+    /// Set makeup mode. In auto modes makeup gain acts as a trim on top of
+    /// the automatic makeup. Measured makeup applies in compress mode only.
+    pub fn set_makeup_mode(&mut self, mode: MakeupMode) {
+        self.makeup_mode = mode;
+        self.average_gain_db = 0.0;
+        self.update_auto_makeup();
+    }
+    
+    /// Enable program-dependent dual-stage release of the envelope
+    /// follower. The `Smooth` character's optical cell has its own memory.
+    pub fn set_auto_release(&mut self, enabled: bool) {
+        self.envelope.set_auto_release(enabled);
+    }
+    
+    /// Set slow stage release time in seconds for auto release
+    pub fn set_slow_release_time(&mut self, release_time: f32) {
+        self.slow_release_time = release_time;
+        self.envelope.set_slow_release_time(release_time);
     }
     
     /// Set output gain as linear gain multiplier
     pub fn set_output_gain(&mut self, output_gain: f32) {
-        self.output_gain = output_gain.max(0.0);
+        self.set_param(Param::OutputGain, utils::gain_to_db(output_gain.max(0.0)));
+    }
+    
+    /// Set output gain in dB
+    pub fn set_output_gain_db(&mut self, output_db: f32) {
+        self.set_param(Param::OutputGain, output_db);
     }
     
     /// Set lookahead time in ms
     pub fn set_lookahead_ms(&mut self, lookahead_ms: f32) {
+        self.lookahead_ms = lookahead_ms.max(0.0);
         let new_samples = (lookahead_ms * 0.001 * self.sample_rate) as usize;
         
         // Resize buffer if needed
@@ -118,19 +311,141 @@ impl DynamicsProcessor {
         }
     }
     
-    /// Set processor mode (Compress, Expand, Gate)
+    /// Set processor mode (Compress, Expand, Gate, Duck). Ducking is keyed
+    /// by the sidechain, so `Duck` enables external sidechain, which stays
+    /// enabled on leaving it.
     pub fn set_mode(&mut self, mode: ProcessorMode) {
         self.mode = mode;
+        if matches!(mode, ProcessorMode::Duck) {
+            self.sidechain_external = true;
+        }
+        self.update_auto_makeup();
     }
     
-    /// Set compression character
+    /// Set the depth of ducking in dB
+    pub fn set_range_db(&mut self, range_db: f32) {
+        self.range_db = range_db.clamp(0.0, 120.0);
+    }
+    
+    /// Set the time in seconds that ducking holds after the key falls
+    /// below threshold, before release
+    pub fn set_hold_time(&mut self, hold_time: f32) {
+        self.hold_time = hold_time.max(0.0);
+        self.hold_samples = (self.hold_time * self.sample_rate) as usize;
+    }
+    
+    /// Set gate hysteresis in dB. The gate opens at the threshold and
+    /// closes this far below it, so levels hovering around the threshold
+    /// do not chatter.
+    pub fn set_hysteresis_db(&mut self, hysteresis_db: f32) {
+        self.hysteresis_db = hysteresis_db.clamp(0.0, 40.0);
+    }
+    
+    /// Set detection topology. Feedback topologies scale the gain computer
+    /// so that the effective ratio matches the ratio setting. Kept over
+    /// the character's topology when the character changes.
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology_override = Some(topology);
+        self.topology = topology;
+    }
+    
+    /// Set compression character, selecting its detector model and curve,
+    /// along with its drive, colour and topology except those set by
+    /// `set_drive`, `set_colour` and `set_topology`. A custom curve stays
+    /// in place of the character's curve.
     pub fn set_character(&mut self, character: CompressionCharacter) {
+        let calculator = get_calculator(&character);
+        let (drive, colour) = calculator.saturation();
         self.character = character;
+        if !matches!(self.calculator, Calculator::Custom(_)) {
+            self.calculator = calculator;
+        }
+        self.detector.set_model(DetectorModel::from_character(&character));
+        self.envelope.set_log_domain(self.detector.log_domain());
+        self.envelope.set_attack_time(self.detector.attack_time(self.attack_time));
+        // Optical and FET units detect from their output
+        self.topology = self.topology_override.unwrap_or(match character {
+            CompressionCharacter::Smooth | CompressionCharacter::Vintage => Topology::FeedBack,
+            _ => Topology::FeedForward
+        });
+        self.saturator.set_drive(self.drive_override.unwrap_or(drive));
+        self.saturator.set_colour(self.colour_override.unwrap_or(colour));
+        self.update_auto_makeup();
+    }
+    
+    /// Forget drive, colour and topology set explicitly, returning them to
+    /// those of the character
+    pub fn use_character_defaults(&mut self) {
+        self.topology_override = None;
+        self.drive_override = None;
+        self.colour_override = None;
+        self.set_character(self.character);
+    }
+    
+    /// Replace the character's curve with a custom curve through `points`,
+    /// interleaved pairs of input dB and output dB, tabulated now so that
+    /// processing only looks it up. Threshold, ratio and knee do not apply
+    /// to a custom curve, and ducking keeps its own curve. A custom curve
+    /// detects feed-forward whatever the topology, so that it maps input
+    /// levels as drawn. An empty slice restores the character's curve.
+    pub fn set_custom_curve(&mut self, points: &[f32], interpolation: CurveInterpolation) {
+        self.calculator = if points.len() < 2 {
+            get_calculator(&self.character)
+        } else {
+            Calculator::Custom(CustomCurve::new(points, interpolation))
+        };
+        self.update_auto_makeup();
+    }
+    
+    /// Set saturation drive, the total harmonic level relative to the
+    /// fundamental for a full scale sine (0.0 to 0.25). Kept over the
+    /// character's drive when the character changes.
+    pub fn set_drive(&mut self, drive: f32) {
+        self.drive_override = Some(drive);
+        self.saturator.set_drive(drive);
+    }
+    
+    /// Set saturation colour (0.0 = 3rd harmonic only, 1.0 = 2nd only).
+    /// Kept over the character's colour when the character changes.
+    pub fn set_colour(&mut self, colour: f32) {
+        self.colour_override = Some(colour);
+        self.saturator.set_colour(colour);
+    }
+    
+    /// Enable 2x oversampling of the saturation stage
+    pub fn set_oversampling(&mut self, enabled: bool) {
+        self.saturator.set_oversampling(enabled);
     }
     
     /// Set dry/wet mix (0.0 = dry, 1.0 = wet)
     pub fn set_mix(&mut self, mix: f32) {
-        self.mix = mix.max(0.0).min(1.0);
+        self.set_param(Param::Mix, mix);
+    }
+    
+    /// Set the gain of the compressed path for parallel compression in dB
+    pub fn set_wet_gain_db(&mut self, wet_db: f32) {
+        self.set_param(Param::WetGain, wet_db);
+    }
+    
+    /// Set the gain of the dry path for parallel compression in dB
+    pub fn set_dry_gain_db(&mut self, dry_db: f32) {
+        self.set_param(Param::DryGain, dry_db);
+    }
+    
+    /// Set the frequency of a 4th order high-pass on the compressed path
+    /// in Hz, 0 to turn it off. The band below passes the wet path
+    /// uncompressed.
+    pub fn set_wet_highpass(&mut self, freq: f32) {
+        self.wet_highpass_freq = freq.max(0.0);
+        self.update_wet_filters();
+    }
+    
+    /// Set the frequency of a 4th order low-pass on the compressed path
+    /// in Hz, 0 to turn it off. The band above passes the wet path
+    /// uncompressed.
+    pub fn set_wet_lowpass(&mut self, freq: f32) {
+        self.wet_lowpass_freq = freq.max(0.0);
+        self.update_wet_filters();
     }
     
     /// Set detection mode
@@ -138,100 +453,586 @@ impl DynamicsProcessor {
         self.envelope.set_detection_mode(mode);
     }
     
+    /// Set the signal detected for a channel of a stereo pair, used by
+    /// `MultichannelDynamics`
+    pub fn set_detection_source(&mut self, source: DetectionSource) {
+        self.envelope.set_detection_source(source);
+    }
+    
+    /// Set the frequency weighting applied before detection
+    pub fn set_weighting(&mut self, weighting: FrequencyWeighting) {
+        self.envelope.set_weighting(weighting);
+    }
+    
+    /// Set RMS detection window length in ms
+    pub fn set_rms_window_ms(&mut self, window_ms: f32) {
+        self.rms_window_ms = window_ms;
+        self.envelope.set_rms_window_ms(window_ms);
+    }
+    
+    /// Set RMS averaging window type
+    pub fn set_rms_window(&mut self, window: RmsWindow) {
+        self.envelope.set_rms_window(window);
+    }
+    
     /// Enable/disable external sidechain
     pub fn set_sidechain_external(&mut self, enabled: bool) {
         self.sidechain_external = enabled;
     }
     
-    /// Enable/disable sidechain filter
+    /// Enable/disable the sidechain filter, a band-pass on the detection
+    /// input that keys detection from part of the spectrum
     pub fn set_sidechain_filter_enabled(&mut self, enabled: bool) {
         self.sidechain_filter_enabled = enabled;
+        self.sidechain_filter.reset();
     }
     
-    /// Set sidechain filter frequency
+    /// Set sidechain filter centre frequency in Hz
     pub fn set_sidechain_filter_freq(&mut self, freq: f32) {
         self.sidechain_filter_freq = freq;
+        self.sidechain_filter.set_bandpass(self.sample_rate, self.sidechain_filter_freq, self.sidechain_filter_q);
     }
     
     /// Set sidechain filter Q
     pub fn set_sidechain_filter_q(&mut self, q: f32) {
         self.sidechain_filter_q = q;
+        self.sidechain_filter.set_bandpass(self.sample_rate, self.sidechain_filter_freq, self.sidechain_filter_q);
+    }
+    
+    /// Set the time in seconds over which parameter changes are smoothed,
+    /// 0 to apply them immediately
+    pub fn set_ramp_time(&mut self, time: f32) {
+        self.ramp_time = time.max(0.0);
+        self.automation.set_ramp_time(time);
+    }
+    
+    /// Schedule a parameter to reach `value` at `sample_offset` samples
+    /// from the next sample processed, following `curve` from the value
+    /// at the previous event. Events for a parameter play in time order.
+    pub fn schedule_param(&mut self, param: Param, value: f32, sample_offset: usize, curve: RampCurve) {
+        self.automation.schedule(param, param.clamp(value), sample_offset, curve);
+    }
+    
+    /// Cancel scheduled parameter changes and ramps, holding parameters
+    /// at their current values
+    pub fn cancel_scheduled_params(&mut self) {
+        self.automation.cancel();
+    }
+    
+    /// Get all settings as JSON, for saving with a project
+    pub fn get_settings(&self) -> String {
+        self.settings().to_json()
+    }
+    
+    /// Apply settings from JSON as produced by `get_settings`. Settings
+    /// missing from the JSON take their defaults.
+    pub fn apply_settings(&mut self, json: &str) -> Result<(), JsError> {
+        let settings = DynamicsSettings::from_json(json).map_err(|error| JsError::new(&error.to_string()))?;
+        self.set_settings(&settings);
+        Ok(())
+    }
+    
+    /// Apply a built-in preset by name. Returns false for unknown names.
+    pub fn apply_preset(&mut self, name: &str) -> bool {
+        match DynamicsSettings::preset(name) {
+            Some(settings) => {
+                self.set_settings(&settings);
+                true
+            },
+            None => false
+        }
+    }
+    
+    /// Get the names of the built-in presets
+    pub fn preset_names() -> Vec<String> {
+        PRESET_NAMES.iter().map(|name| name.to_string()).collect()
     }
     
     // ======== Processing ========
     
     /// Process a single sample and return the processed audio
     pub fn process_sample(&mut self, input: f32, sidechain_input: Option<f32>) -> f32 {
-        // Determine which input to use for level detection
-        let detection_input = if self.sidechain_external && sidechain_input.is_some() {
-            sidechain_input.unwrap()
+        let envelope_gain = self.detect(input, sidechain_input);
+        let target_gain = self.gain_for_level(envelope_gain);
+        self.apply_gain(input, target_gain)
+    }
+    
+    /// Process a block of samples from `input` into `output`. An empty
+    /// `sidechain` or `gain_reduction` slice is treated as absent.
+    #[wasm_bindgen(js_name = process_block)]
+    pub fn process_block_js(&mut self, input: &[f32], sidechain: &[f32], output: &mut [f32], gain_reduction: &mut [f32]) {
+        let sidechain = (!sidechain.is_empty()).then_some(sidechain);
+        let gain_reduction = (!gain_reduction.is_empty()).then_some(gain_reduction);
+        self.process_block(input, sidechain, output, gain_reduction);
+    }
+    
+    /// Get the current gain reduction as a linear gain multiplier (0.0 to 1.0)
+    pub fn get_gain_reduction(&self) -> f32 {
+        // Return gain reduction as a linear gain multiplier
+        self.current_gain
+    }
+    
+    /// Evaluate the static transfer curve of the current settings over
+    /// input levels from `min_db` to `max_db`. Returns `points` interleaved
+    /// pairs of input dB and output dB, including makeup.
+    pub fn get_transfer_curve(&self, min_db: f32, max_db: f32, points: usize) -> Vec<f32> {
+        let target = |param| self.automation.target(param);
+        let makeup_db = target(Param::Makeup) + utils::gain_to_db(self.auto_makeup_gain);
+        curve::evaluate(&self.calculator, self.mode, target(Param::Threshold), target(Param::Ratio), target(Param::Knee), makeup_db, min_db, max_db, points)
+    }
+    
+    /// Get levels measured over the last processed block
+    pub fn get_meter(&self) -> DynamicsMeter {
+        self.meter
+    }
+    
+    /// Keep a history of `length` gain reduction values, each the deepest
+    /// reduction over `decimation` samples of block processing. A length
+    /// of 0 disables the history.
+    pub fn set_gain_history(&mut self, length: usize, decimation: usize) {
+        self.gain_history.configure(length, decimation);
+    }
+    
+    /// Get a pointer to the gain reduction history in wasm memory, valid
+    /// until the history is reconfigured
+    pub fn gain_history_ptr(&self) -> *const f32 {
+        self.gain_history.as_ptr()
+    }
+    
+    /// Get the length of the gain reduction history
+    pub fn gain_history_length(&self) -> usize {
+        self.gain_history.len()
+    }
+    
+    /// Get the index of the oldest entry in the gain reduction history,
+    /// which is the next to be written
+    pub fn gain_history_index(&self) -> usize {
+        self.gain_history.index()
+    }
+    
+    /// Process a sample and get both the output and gain reduction
+    /// Note: This is for JavaScript usage, calling both process_sample and get_gain_reduction_db
+    pub fn process(&mut self, input: f32, sidechain_input: Option<f32>) -> f32 {
+        self.process_sample(input, sidechain_input)
+    }
+    
+    /// Reset the processor state
+    pub fn reset(&mut self) {
+        self.envelope.reset();
+        self.detector.reset();
+        self.detector_level = 0.0;
+        self.saturator.reset();
+        self.wet_filters.iter_mut()
+            .chain(self.dry_filters.iter_mut())
+            .chain(self.dry_allpasses.iter_mut())
+            .for_each(Biquad::reset);
+        self.current_gain = 1.0;
+        self.feedback = 0.0;
+        self.hold_counter = 0;
+        self.duck_gain_db = 0.0;
+        self.gate_open = false;
+        self.sidechain_filter.reset();
+        self.meter = DynamicsMeter::new();
+        self.gain_history.reset();
+        self.average_gain_db = 0.0;
+        self.update_auto_makeup();
+        self.lookahead_buffer.clear();
+        self.lookahead_buffer.resize(self.lookahead_samples, 0.0);
+    }
+}
+
+impl DynamicsProcessor {
+    /// Process a block of samples from `input` into `output`, detecting
+    /// from `sidechain` when external sidechain is enabled. When given,
+    /// `gain_reduction` receives the per-sample gain reduction as a linear
+    /// gain multiplier. Does not allocate.
+    pub fn process_block(&mut self, input: &[f32], sidechain: Option<&[f32]>, output: &mut [f32], mut gain_reduction: Option<&mut [f32]>) {
+        let mut meter = MeterAccumulator::new();
+        
+        for (i, (out, &sample)) in output.iter_mut().zip(input).enumerate() {
+            let sidechain_input = sidechain.and_then(|sidechain| sidechain.get(i).copied());
+            let envelope_gain = self.detect(sample, sidechain_input);
+            let target_gain = self.gain_for_level(envelope_gain);
+            *out = self.apply_gain(sample, target_gain);
+            
+            if let Some(reduction) = gain_reduction.as_deref_mut().and_then(|trace| trace.get_mut(i)) {
+                *reduction = target_gain;
+            }
+            
+            self.record(&mut meter, sample, *out, target_gain);
+        }
+        
+        self.finish_meter(&meter);
+    }
+    
+    /// Get all settings. Smoothed and scheduled parameters report the
+    /// value they are moving towards.
+    pub fn settings(&self) -> DynamicsSettings {
+        let target = |param| self.automation.target(param);
+        
+        DynamicsSettings {
+            threshold_db: target(Param::Threshold),
+            ratio: target(Param::Ratio),
+            knee_db: target(Param::Knee),
+            attack_time: self.attack_time,
+            release_time: self.release_time,
+            auto_release: self.envelope.get_auto_release(),
+            slow_release_time: self.slow_release_time,
+            makeup_db: target(Param::Makeup),
+            makeup_mode: self.makeup_mode,
+            output_gain_db: target(Param::OutputGain),
+            lookahead_ms: self.lookahead_ms,
+            mode: self.mode,
+            range_db: self.range_db,
+            hold_time: self.hold_time,
+            hysteresis_db: self.hysteresis_db,
+            character: self.character,
+            topology: self.topology,
+            drive: self.saturator.get_drive(),
+            colour: self.saturator.get_colour(),
+            oversampling: self.saturator.get_oversampling(),
+            mix: target(Param::Mix),
+            wet_gain_db: target(Param::WetGain),
+            dry_gain_db: target(Param::DryGain),
+            wet_highpass: self.wet_highpass_freq,
+            wet_lowpass: self.wet_lowpass_freq,
+            detection_mode: self.envelope.get_detection_mode(),
+            detection_source: self.envelope.get_detection_source(),
+            weighting: self.envelope.get_weighting(),
+            rms_window_ms: self.rms_window_ms,
+            rms_window: self.envelope.get_rms_window(),
+            sidechain_external: self.sidechain_external,
+            sidechain_filter_enabled: self.sidechain_filter_enabled,
+            sidechain_filter_freq: self.sidechain_filter_freq,
+            sidechain_filter_q: self.sidechain_filter_q,
+            ramp_time: self.ramp_time,
+            custom_curve: match &self.calculator {
+                Calculator::Custom(curve) => curve.points().to_vec(),
+                _ => Vec::new()
+            },
+            curve_interpolation: match &self.calculator {
+                Calculator::Custom(curve) => curve.interpolation(),
+                _ => CurveInterpolation::Linear
+            },
+        }
+    }
+    
+    /// Apply all settings. Character goes first, as it resets drive,
+    /// colour and topology.
+    pub fn set_settings(&mut self, settings: &DynamicsSettings) {
+        // Only values that differ from the character's are kept over it
+        self.use_character_defaults();
+        self.set_character(settings.character);
+        self.set_custom_curve(&settings.custom_curve, settings.curve_interpolation);
+        if settings.topology != self.topology { self.set_topology(settings.topology); }
+        if settings.drive != self.saturator.get_drive() { self.set_drive(settings.drive); }
+        if settings.colour != self.saturator.get_colour() { self.set_colour(settings.colour); }
+        self.set_oversampling(settings.oversampling);
+        self.set_mode(settings.mode);
+        self.set_ramp_time(settings.ramp_time);
+        self.set_threshold_db(settings.threshold_db);
+        self.set_ratio(settings.ratio);
+        self.set_knee_db(settings.knee_db);
+        self.set_attack_time(settings.attack_time);
+        self.set_release_time(settings.release_time);
+        self.set_auto_release(settings.auto_release);
+        self.set_slow_release_time(settings.slow_release_time);
+        self.set_makeup_gain_db(settings.makeup_db);
+        self.set_makeup_mode(settings.makeup_mode);
+        self.set_output_gain_db(settings.output_gain_db);
+        self.set_lookahead_ms(settings.lookahead_ms);
+        self.set_range_db(settings.range_db);
+        self.set_hold_time(settings.hold_time);
+        self.set_hysteresis_db(settings.hysteresis_db);
+        self.set_mix(settings.mix);
+        self.set_wet_gain_db(settings.wet_gain_db);
+        self.set_dry_gain_db(settings.dry_gain_db);
+        self.set_wet_highpass(settings.wet_highpass);
+        self.set_wet_lowpass(settings.wet_lowpass);
+        self.set_detection_mode(settings.detection_mode);
+        self.set_detection_source(settings.detection_source);
+        self.set_weighting(settings.weighting);
+        self.set_rms_window_ms(settings.rms_window_ms);
+        self.set_rms_window(settings.rms_window);
+        self.set_sidechain_external(settings.sidechain_external);
+        self.set_sidechain_filter_enabled(settings.sidechain_filter_enabled);
+        self.set_sidechain_filter_freq(settings.sidechain_filter_freq);
+        self.set_sidechain_filter_q(settings.sidechain_filter_q);
+    }
+    
+    /// Record a processed sample in a block meter and the gain history
+    pub(crate) fn record(&mut self, meter: &mut MeterAccumulator, input: f32, output: f32, gain: f32) {
+        meter.add(input, output, gain);
+        
+        if self.gain_history.is_enabled() {
+            self.gain_history.push(gain);
+        }
+    }
+    
+    /// Keep the levels of a finished block for `get_meter`
+    pub(crate) fn finish_meter(&mut self, meter: &MeterAccumulator) {
+        self.meter = meter.finish(self.detector_level);
+    }
+    
+    /// Select the signal to detect for a channel of a stereo pair
+    pub(crate) fn select_source(&self, channel: f32, left: f32, right: f32) -> f32 {
+        self.envelope.select_source(channel, left, right)
+    }
+    
+    /// Run the detector on the input, or on the sidechain when external
+    /// sidechain is enabled, and return the envelope level in gain domain.
+    /// Starts each sample, so also advances parameter automation.
+    pub(crate) fn detect(&mut self, input: f32, sidechain_input: Option<f32>) -> f32 {
+        if !self.automation.is_idle() {
+            for (index, value) in self.automation.tick().into_iter().enumerate() {
+                if let Some(value) = value {
+                    self.apply_param(Param::from_index(index), value);
+                }
+            }
         } else {
-            input
+            self.automation.advance();
+        }
+        
+        // Determine which input to use for level detection
+        let feed_forward = match sidechain_input {
+            Some(sidechain) if self.sidechain_external => sidechain,
+            _ => input
+        };
+        
+        // Ducking is keyed, so always detects feed-forward, as do custom
+        // curves, which map input levels to output levels
+        let topology = match (&self.mode, &self.calculator) {
+            (ProcessorMode::Duck, _) | (_, Calculator::Custom(_)) => Topology::FeedForward,
+            _ => self.topology
         };
         
-        // TODO: Apply sidechain filter if enabled
-        let filtered_detection = detection_input;
+        let detection_input = match topology {
+            Topology::FeedForward => feed_forward,
+            Topology::FeedBack => self.feedback,
+            // The geometric mean is the mean of the two levels in dB
+            Topology::Hybrid => (feed_forward * self.feedback).abs().sqrt()
+        };
         
-        // Process envelope follower to get the level in gain domain
-        let envelope_gain = self.envelope.process(filtered_detection);
+        let filtered_detection = if self.sidechain_filter_enabled {
+            self.sidechain_filter.process(detection_input)
+        } else {
+            detection_input
+        };
         
-        // Get the appropriate algorithm
-        let calculator = algorithms::get_calculator(&self.character);
+        // Weight and follow the envelope to get the level in gain domain,
+        // and shape it with the character's detector model
+        let weighted = self.envelope.weight(filtered_detection);
+        let envelope_level = self.envelope.follow(weighted);
+        self.detector_level = self.detector.process(envelope_level);
+        self.detector_level
+    }
+    
+    /// Calculate the target gain for an envelope level, excluding makeup
+    pub(crate) fn gain_for_level(&mut self, envelope_gain: f32) -> f32 {
+        // Calculate gain reduction in the log domain
+        let level_db = utils::gain_to_db(envelope_gain);
+        let threshold_db = self.gate_threshold_db(level_db);
+        let gain_db = match self.mode {
+            ProcessorMode::Duck => self.duck_gain(level_db),
+            _ => self.calculator.calculate_gain_db(
+                level_db, 
+                threshold_db, 
+                self.ratio, 
+                self.knee_db, 
+                &self.mode
+            ) * self.topology_scale()
+        };
+        let target_gain = utils::db_to_gain(gain_db);
         
-        // Calculate gain reduction in gain domain
-        let target_gain = calculator.calculate_gain_linear(
-            envelope_gain, 
-            self.threshold, 
-            self.ratio, 
-            self.knee_width, 
-            &self.mode
-        );
+        // Track average gain reduction for measured makeup while
+        // compressing, holding it while the level is below threshold so
+        // that pauses do not pull the makeup down
+        if self.makeup_mode == MakeupMode::Measured && matches!(self.mode, ProcessorMode::Compress) && gain_db < 0.0 {
+            self.average_gain_db = self.average_coef * (self.average_gain_db - gain_db) + gain_db;
+            self.update_auto_makeup();
+        }
         
         // Update current gain for metering
         self.current_gain = target_gain;
-        
+        target_gain
+    }
+    
+    /// Apply a target gain to the input through lookahead, character, mix
+    /// and output gain stages
+    pub(crate) fn apply_gain(&mut self, input: f32, target_gain: f32) -> f32 {
         // Apply makeup gain
-        let target_gain_with_makeup = target_gain * self.makeup_gain;
+        let target_gain_with_makeup = target_gain * self.makeup_gain * self.auto_makeup_gain;
         
-        // Handle lookahead delay
+        // Handle lookahead delay, popping before pushing so the buffer
+        // never grows past its length
         let delayed_input = if self.lookahead_samples > 0 {
+            let delayed = self.lookahead_buffer.pop_front().unwrap_or(input);
             self.lookahead_buffer.push_back(input);
-            self.lookahead_buffer.pop_front().unwrap_or(input)
+            delayed
         } else {
             input
         };
         
-        // Apply character-specific processing
-        let processed = calculator.apply_character(delayed_input, target_gain_with_makeup);
+        // Keep the compressed sample for feedback detection
+        self.feedback = delayed_input * target_gain;
         
-        // Apply dry/wet mix if needed
-        let output = if self.mix < 1.0 {
-            processed * self.mix + delayed_input * (1.0 - self.mix)
-        } else {
-            processed
-        };
+        // Apply character-specific processing and saturation
+        let processed = self.calculator.apply_character(delayed_input, target_gain_with_makeup);
+        let processed = self.saturator.process_sample(processed);
+        
+        // Filter the wet path, and pass the dry path through filters with
+        // the same phase response as the saturator and wet filters, so the
+        // paths sum without comb filtering. Linkwitz-Riley filters sum to
+        // their allpass, so the band the wet filters reject is the aligned
+        // dry signal less its filtered part, and the wet path carries it
+        // uncompressed.
+        let dry = self.saturator.align(delayed_input);
+        let filtered_dry = self.dry_filters.iter_mut().fold(dry, |x, filter| filter.process(x));
+        let dry = self.dry_allpasses.iter_mut().fold(dry, |x, allpass| allpass.process(x));
+        let wet = self.wet_filters.iter_mut().fold(processed, |x, filter| filter.process(x)) + dry - filtered_dry;
+        
+        // Apply dry/wet mix
+        let output = wet * self.wet_gain * self.mix + dry * self.dry_gain * (1.0 - self.mix);
         
         // Apply output gain
         output * self.output_gain
     }
     
-    /// Get the current gain reduction as a linear gain multiplier (0.0 to 1.0)
-    pub fn get_gain_reduction(&self) -> f32 {
-        // Return gain reduction as a linear gain multiplier
-        self.current_gain
+    /// Ducking gain in dB for a key level in dB. The key ducks by the full
+    /// range above threshold, blending in over the knee, and the depth is
+    /// held for the hold time after the key falls before ramping back at
+    /// the release time.
+    fn duck_gain(&mut self, key_db: f32) -> f32 {
+        let overshoot = key_db - self.threshold_db;
+        let amount = if self.knee_db > 0.0 {
+            (overshoot / self.knee_db + 0.5).clamp(0.0, 1.0)
+        } else if overshoot >= 0.0 {
+            1.0
+        } else {
+            0.0
+        };
+        let target_db = -self.range_db * amount;
+        
+        if target_db <= self.duck_gain_db {
+            self.hold_counter = 0;
+            self.duck_gain_db = self.duck_attack_coef * (self.duck_gain_db - target_db) + target_db;
+        } else if self.hold_counter < self.hold_samples {
+            self.hold_counter += 1;
+        } else {
+            self.duck_gain_db = self.duck_release_coef * (self.duck_gain_db - target_db) + target_db;
+        }
+        
+        self.duck_gain_db
     }
     
-    /// Process a sample and get both the output and gain reduction
-    /// Note: This is for JavaScript usage, calling both process_sample and get_gain_reduction_db
-    pub fn process(&mut self, input: f32, sidechain_input: Option<f32>) -> f32 {
-        self.process_sample(input, sidechain_input)
+    /// Threshold of the gain computer for a level in dB. With hysteresis,
+    /// an open gate's threshold drops by the hysteresis until the level
+    /// falls below it and the gate closes.
+    fn gate_threshold_db(&mut self, level_db: f32) -> f32 {
+        if !matches!(self.mode, ProcessorMode::Gate) || self.hysteresis_db <= 0.0 {
+            return self.threshold_db;
+        }
+        
+        if level_db >= self.threshold_db {
+            self.gate_open = true;
+        } else if level_db < self.threshold_db - self.hysteresis_db {
+            self.gate_open = false;
+        }
+        
+        if self.gate_open {
+            self.threshold_db - self.hysteresis_db
+        } else {
+            self.threshold_db
+        }
     }
     
-    /// Reset the processor state
-    pub fn reset(&mut self) {
-        self.envelope.reset();
-        self.current_gain = 1.0;
-        self.lookahead_buffer.clear();
-        self.lookahead_buffer.resize(self.lookahead_samples, 0.0);
+    /// Rebuild wet filters and their matching dry allpasses. Each wet
+    /// filter is a Linkwitz-Riley pair of Butterworth sections, whose
+    /// phase response is that of a 2nd order allpass at its frequency.
+    fn update_wet_filters(&mut self) {
+        let sample_rate = self.sample_rate;
+        self.wet_filters.clear();
+        self.dry_allpasses.clear();
+        
+        if self.wet_highpass_freq > 0.0 {
+            let highpass = Biquad::highpass(sample_rate, self.wet_highpass_freq, FRAC_1_SQRT_2);
+            self.wet_filters.extend([highpass.clone(), highpass]);
+            self.dry_allpasses.push(LinkwitzRiley::allpass(sample_rate, self.wet_highpass_freq));
+        }
+        
+        if self.wet_lowpass_freq > 0.0 {
+            let lowpass = Biquad::lowpass(sample_rate, self.wet_lowpass_freq, FRAC_1_SQRT_2);
+            self.wet_filters.extend([lowpass.clone(), lowpass]);
+            self.dry_allpasses.push(LinkwitzRiley::allpass(sample_rate, self.wet_lowpass_freq));
+        }
+        
+        self.dry_filters = self.wet_filters.clone();
     }
-}
\ No newline at end of file
+    
+    /// Set a parameter through automation, smoothing the change
+    fn set_param(&mut self, param: Param, value: f32) {
+        let value = param.clamp(value);
+        if self.automation.smooth(param, value) {
+            self.apply_param(param, value);
+        }
+    }
+    
+    /// Write a parameter value in automation units to its field
+    fn apply_param(&mut self, param: Param, value: f32) {
+        match param {
+            Param::Threshold => self.threshold_db = value,
+            Param::Ratio => self.ratio = value,
+            Param::Knee => self.knee_db = value,
+            Param::Makeup => self.makeup_gain = utils::db_to_gain(value),
+            Param::OutputGain => self.output_gain = utils::db_to_gain(value),
+            Param::Mix => self.mix = value,
+            Param::WetGain => self.wet_gain = utils::db_to_gain(value),
+            Param::DryGain => self.dry_gain = utils::db_to_gain(value)
+        }
+        
+        if matches!(param, Param::Threshold | Param::Ratio | Param::Knee) {
+            self.update_auto_makeup();
+        }
+    }
+    
+    /// Recalculate static auto makeup from the gain computer at 0dB input
+    fn update_auto_makeup(&mut self) {
+        self.auto_makeup_gain = match self.makeup_mode {
+            MakeupMode::Manual => 1.0,
+            MakeupMode::Static => {
+                let gain_db = self.calculator.calculate_gain_db(0.0, self.threshold_db, self.ratio, self.knee_db, &self.mode);
+                utils::db_to_gain(-gain_db)
+            },
+            // Only compression has a reduction to make up
+            MakeupMode::Measured if matches!(self.mode, ProcessorMode::Compress) => {
+                utils::db_to_gain((-self.average_gain_db).clamp(0.0, MAX_MEASURED_MAKEUP_DB))
+            },
+            MakeupMode::Measured => 1.0
+        };
+    }
+    
+    /// Scale of the gain computer's output for the topology. Detecting
+    /// from the output, gain g = k·(y - T) settles at a slope of 1/(1 - k),
+    /// so compression at R:1 needs k = -(R - 1), which is R times the
+    /// feed-forward slope. Detecting from the mean of input and output in
+    /// dB, the slope is (1 + k/2)/(1 - k/2), and the scales follow likewise.
+    /// Custom curves detect feed-forward, so are not scaled.
+    fn topology_scale(&self) -> f32 {
+        if matches!(self.calculator, Calculator::Custom(_)) {
+            return 1.0;
+        }
+        
+        let ratio = match self.mode {
+            ProcessorMode::Gate => self.ratio * 2.0,
+            _ => self.ratio
+        };
+        
+        match (self.topology, self.mode) {
+            (Topology::FeedForward, _) | (_, ProcessorMode::Duck) => 1.0,
+            (Topology::FeedBack, ProcessorMode::Compress) => ratio,
+            (Topology::FeedBack, _) => 1.0 / ratio,
+            (Topology::Hybrid, ProcessorMode::Compress) => 2.0 * ratio / (ratio + 1.0),
+            (Topology::Hybrid, _) => 2.0 / (ratio + 1.0)
+        }
+    }
+}
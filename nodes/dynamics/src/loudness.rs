@@ -0,0 +1,383 @@
+//! Loudness metering to ITU-R BS.1770-4 and EBU R128.
+
+use wasm_bindgen::prelude::*;
+use std::f64::consts::PI;
+
+use crate::filters::Biquad;
+use crate::utils::gain_to_db;
+
+/// Length of a gating step in seconds. Gating blocks of 400ms overlap by
+/// 75%, so a new one completes every 100ms.
+const STEP_TIME: f64 = 0.1;
+
+/// Windows in steps, 400ms momentary and 3s short-term
+const MOMENTARY_STEPS: usize = 4;
+const SHORT_TERM_STEPS: usize = 30;
+
+/// Absolute gate, and the relative gates of integrated loudness and
+/// loudness range, in LUFS and LU
+const ABSOLUTE_GATE: f64 = -70.0;
+const INTEGRATED_GATE: f64 = -10.0;
+const RANGE_GATE: f64 = -20.0;
+
+/// Percentiles of the short-term loudness distribution that bound the
+/// loudness range, from EBU Tech 3342
+const RANGE_LOW_PERCENTILE: f64 = 0.10;
+const RANGE_HIGH_PERCENTILE: f64 = 0.95;
+
+/// Loudness histograms have 0.1 LU bins from the absolute gate to +30 LUFS
+const HISTOGRAM_STEP: f64 = 0.1;
+const HISTOGRAM_BINS: usize = 1000;
+
+/// Taps per phase of the true peak interpolator, 48 taps at 4x as in
+/// BS.1770-4 Annex 2
+const TRUE_PEAK_TAPS: usize = 12;
+
+/// Loudness in LUFS of a weighted mean square
+fn power_to_lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+/// The two stage K-weighting filter of BS.1770, a high shelf modelling the
+/// acoustic effect of the head followed by the RLB high-pass. Coefficients
+/// are derived for any sample rate from the analogue prototypes.
+pub(crate) fn k_weighting(sample_rate: f32) -> [Biquad; 2] {
+    let sample_rate = sample_rate as f64;
+
+    // Stage 1, high shelf
+    let f0 = 1681.974450955533;
+    let q = 0.7071752369554196;
+    let k = (PI * f0 / sample_rate).tan();
+    let vh = 10.0f64.powf(3.999843853973347 / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let shelf = Biquad::from_raw(
+        (vh + vb * k / q + k * k) as f32,
+        (2.0 * (k * k - vh)) as f32,
+        (vh - vb * k / q + k * k) as f32,
+        (1.0 + k / q + k * k) as f32,
+        (2.0 * (k * k - 1.0)) as f32,
+        (1.0 - k / q + k * k) as f32
+    );
+
+    // Stage 2, RLB high-pass
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+    let k = (PI * f0 / sample_rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let highpass = Biquad::from_raw(
+        a0 as f32,
+        (-2.0 * a0) as f32,
+        a0 as f32,
+        a0 as f32,
+        (2.0 * (k * k - 1.0)) as f32,
+        (1.0 - k / q + k * k) as f32
+    );
+
+    [shelf, highpass]
+}
+
+/// Histogram of gated block powers. Each bin keeps the sum of the powers
+/// that fall in it, so gated means are exact and only the gate thresholds
+/// are quantised to the bin width.
+struct LoudnessHistogram {
+    power: Vec<f64>,
+    count: Vec<u32>,
+}
+
+impl LoudnessHistogram {
+    fn new() -> Self {
+        Self {
+            power: vec![0.0; HISTOGRAM_BINS],
+            count: vec![0; HISTOGRAM_BINS],
+        }
+    }
+
+    fn bin(lufs: f64) -> usize {
+        (((lufs - ABSOLUTE_GATE) / HISTOGRAM_STEP) as usize).min(HISTOGRAM_BINS - 1)
+    }
+
+    /// Add a block, discarding it below the absolute gate
+    fn add(&mut self, power: f64) {
+        let lufs = power_to_lufs(power);
+
+        if lufs > ABSOLUTE_GATE {
+            let bin = Self::bin(lufs);
+            self.power[bin] += power;
+            self.count[bin] += 1;
+        }
+    }
+
+    /// Mean power and number of blocks in bins from `from` upwards
+    fn mean_from(&self, from: usize) -> Option<f64> {
+        let count: u32 = self.count[from..].iter().sum();
+        (count > 0).then(|| self.power[from..].iter().sum::<f64>() / count as f64)
+    }
+
+    /// Bin `from` after applying a relative gate to the absolute gated mean
+    fn relative_gate(&self, gate: f64) -> Option<usize> {
+        self.mean_from(0).map(|power| Self::bin((power_to_lufs(power) + gate).max(ABSOLUTE_GATE)))
+    }
+
+    /// Loudness of the bin at a percentile of the blocks in bins from
+    /// `from` upwards
+    fn percentile(&self, from: usize, percentile: f64) -> f64 {
+        let total: u32 = self.count[from..].iter().sum();
+        let target = (percentile * total.saturating_sub(1) as f64).round() as u32;
+        let mut seen = 0;
+
+        for (bin, &count) in self.count.iter().enumerate().skip(from) {
+            seen += count;
+            if seen > target {
+                return ABSOLUTE_GATE + (bin as f64 + 0.5) * HISTOGRAM_STEP;
+            }
+        }
+
+        ABSOLUTE_GATE + HISTOGRAM_BINS as f64 * HISTOGRAM_STEP
+    }
+
+    fn reset(&mut self) {
+        self.power.fill(0.0);
+        self.count.fill(0);
+    }
+}
+
+/// Per channel state of the meter
+struct Channel {
+    weight: f64,             // Channel weighting G
+    filters: [Biquad; 2],    // K-weighting
+    history: [f32; TRUE_PEAK_TAPS], // Recent samples for true peak, newest first
+}
+
+/// Loudness meter to ITU-R BS.1770-4 and EBU R128. Input is K-weighted and
+/// the weighted mean square of all channels is summed in 100ms steps, from
+/// which momentary (400ms) and short-term (3s) loudness are read. Gating
+/// blocks feed integrated loudness with an absolute gate of -70 LUFS and a
+/// relative gate of -10 LU, and short-term values feed loudness range with
+/// a relative gate of -20 LU. True peak is measured by oversampling toward
+/// 192kHz by at most 4x, as BS.1770 does at 48kHz, so 44.1kHz input is
+/// measured at 176.4kHz. Loudness is reported in LUFS, and as negative
+/// infinity before there is anything to measure.
+#[wasm_bindgen]
+pub struct LoudnessMeter {
+    channels: Vec<Channel>,
+    step_length: usize,      // Frames per step
+    step_position: usize,
+    step_power: f64,         // Weighted sum of squares of the current step
+    steps: [f64; SHORT_TERM_STEPS], // Mean powers of recent steps
+    step_index: usize,       // Index of the next step to be written
+    step_count: usize,       // Steps completed, up to SHORT_TERM_STEPS
+    integrated: LoudnessHistogram,
+    range: LoudnessHistogram,
+    interpolator: Vec<f32>,  // Polyphase interpolation filter, by phase then tap
+    true_peak: f32,
+}
+
+#[wasm_bindgen]
+impl LoudnessMeter {
+    /// Create a new LoudnessMeter. Meters with 5 or 6 channels take the
+    /// surround weights of BS.1770, in the order L R C Ls Rs or L R C LFE
+    /// Ls Rs. Other layouts weight all channels equally.
+    pub fn new(sample_rate: f32, channel_count: usize) -> Self {
+        let channel_count = channel_count.max(1);
+        let weights: &[f64] = match channel_count {
+            5 => &[1.0, 1.0, 1.0, 1.41, 1.41],
+            6 => &[1.0, 1.0, 1.0, 0.0, 1.41, 1.41],
+            _ => &[]
+        };
+
+        // Oversample 4x, up to 192kHz, for true peak
+        let oversampling = ((192000.0 / sample_rate).ceil() as usize).clamp(1, 4);
+
+        Self {
+            channels: (0..channel_count).map(|c| Channel {
+                weight: weights.get(c).copied().unwrap_or(1.0),
+                filters: k_weighting(sample_rate),
+                history: [0.0; TRUE_PEAK_TAPS],
+            }).collect(),
+            step_length: ((sample_rate as f64 * STEP_TIME).round() as usize).max(1),
+            step_position: 0,
+            step_power: 0.0,
+            steps: [0.0; SHORT_TERM_STEPS],
+            step_index: 0,
+            step_count: 0,
+            integrated: LoudnessHistogram::new(),
+            range: LoudnessHistogram::new(),
+            interpolator: interpolator(oversampling),
+            true_peak: 0.0,
+        }
+    }
+
+    /// Get the number of channels
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Set the weighting G of a channel, 0.0 to exclude it
+    pub fn set_channel_weight(&mut self, channel: usize, weight: f32) {
+        if let Some(c) = self.channels.get_mut(channel) { c.weight = weight.max(0.0) as f64; }
+    }
+
+    // ======== Processing ========
+
+    /// Measure a block of interleaved frames
+    pub fn process_interleaved(&mut self, input: &[f32]) {
+        let channel_count = self.channels.len();
+
+        for frame in input.chunks_exact(channel_count) {
+            for (c, &sample) in frame.iter().enumerate() {
+                self.process_channel(c, sample);
+            }
+
+            self.advance();
+        }
+    }
+
+    /// Measure a block of planar channels, laid out one channel after
+    /// another
+    pub fn process_planar(&mut self, input: &[f32]) {
+        let channel_count = self.channels.len();
+        let length = input.len() / channel_count;
+
+        for i in 0..length {
+            for c in 0..channel_count {
+                self.process_channel(c, input[c * length + i]);
+            }
+
+            self.advance();
+        }
+    }
+
+    // ======== Readings ========
+
+    /// Get momentary loudness over the last 400ms in LUFS
+    pub fn get_momentary(&self) -> f32 {
+        self.window_loudness(MOMENTARY_STEPS)
+    }
+
+    /// Get short-term loudness over the last 3s in LUFS
+    pub fn get_short_term(&self) -> f32 {
+        self.window_loudness(SHORT_TERM_STEPS)
+    }
+
+    /// Get gated integrated loudness since the last reset in LUFS
+    pub fn get_integrated(&self) -> f32 {
+        self.integrated.relative_gate(INTEGRATED_GATE)
+            .and_then(|from| self.integrated.mean_from(from))
+            .map_or(f32::NEG_INFINITY, |power| power_to_lufs(power) as f32)
+    }
+
+    /// Get loudness range in LU, the spread between the 10th and 95th
+    /// percentiles of gated short-term loudness
+    pub fn get_loudness_range(&self) -> f32 {
+        match self.range.relative_gate(RANGE_GATE) {
+            Some(from) => (self.range.percentile(from, RANGE_HIGH_PERCENTILE)
+                - self.range.percentile(from, RANGE_LOW_PERCENTILE)) as f32,
+            None => 0.0
+        }
+    }
+
+    /// Get the maximum true peak since the last reset in dBTP
+    pub fn get_true_peak(&self) -> f32 {
+        gain_to_db(self.true_peak)
+    }
+
+    /// Reset all measurements
+    pub fn reset(&mut self) {
+        for channel in self.channels.iter_mut() {
+            channel.filters.iter_mut().for_each(Biquad::reset);
+            channel.history = [0.0; TRUE_PEAK_TAPS];
+        }
+
+        self.step_position = 0;
+        self.step_power = 0.0;
+        self.steps = [0.0; SHORT_TERM_STEPS];
+        self.step_index = 0;
+        self.step_count = 0;
+        self.integrated.reset();
+        self.range.reset();
+        self.true_peak = 0.0;
+    }
+}
+
+impl LoudnessMeter {
+    /// Weight and accumulate a sample of a channel, and track its true peak
+    #[inline]
+    fn process_channel(&mut self, c: usize, sample: f32) {
+        let channel = &mut self.channels[c];
+        let weighted = channel.filters.iter_mut().fold(sample, |x, filter| filter.process(x));
+        self.step_power += channel.weight * (weighted as f64) * (weighted as f64);
+
+        // Interpolate between samples and keep the largest magnitude
+        channel.history.copy_within(0..TRUE_PEAK_TAPS - 1, 1);
+        channel.history[0] = sample;
+
+        for phase in self.interpolator.chunks_exact(TRUE_PEAK_TAPS) {
+            let value: f32 = phase.iter().zip(&channel.history).map(|(h, x)| h * x).sum();
+            self.true_peak = self.true_peak.max(value.abs());
+        }
+    }
+
+    /// Count a frame, completing a step when the step is full
+    #[inline]
+    fn advance(&mut self) {
+        self.step_position += 1;
+
+        if self.step_position < self.step_length {
+            return;
+        }
+
+        self.steps[self.step_index] = self.step_power / self.step_length as f64;
+        self.step_index = (self.step_index + 1) % SHORT_TERM_STEPS;
+        self.step_count = (self.step_count + 1).min(SHORT_TERM_STEPS);
+        self.step_position = 0;
+        self.step_power = 0.0;
+
+        // A gating block completes with every step once there are enough
+        // steps to fill one
+        if self.step_count >= MOMENTARY_STEPS {
+            self.integrated.add(self.window_power(MOMENTARY_STEPS));
+        }
+
+        if self.step_count >= SHORT_TERM_STEPS {
+            self.range.add(self.window_power(SHORT_TERM_STEPS));
+        }
+    }
+
+    /// Mean power of the last `steps` steps
+    fn window_power(&self, steps: usize) -> f64 {
+        (1..=steps)
+            .map(|i| self.steps[(self.step_index + SHORT_TERM_STEPS - i) % SHORT_TERM_STEPS])
+            .sum::<f64>() / steps as f64
+    }
+
+    fn window_loudness(&self, steps: usize) -> f32 {
+        if self.step_count < steps {
+            return f32::NEG_INFINITY;
+        }
+
+        power_to_lufs(self.window_power(steps)) as f32
+    }
+}
+
+/// Polyphase interpolation filter for `factor` times oversampling, a
+/// Blackman windowed sinc split into `factor` phases of `TRUE_PEAK_TAPS`.
+/// The sinc is centred on a tap of phase 0, so that phase passes the input
+/// samples through unchanged and true peak never reads below sample peak.
+fn interpolator(factor: usize) -> Vec<f32> {
+    let length = factor * TRUE_PEAK_TAPS;
+    let centre = (length / 2) as f64;
+    let mut coefficients = vec![0.0; length];
+
+    for phase in 0..factor {
+        for tap in 0..TRUE_PEAK_TAPS {
+            let n = tap * factor + phase;
+            let x = (n as f64 - centre) / factor as f64;
+            let sinc = if x == 0.0 { 1.0 } else { (PI * x).sin() / (PI * x) };
+            let w = 2.0 * PI * n as f64 / length as f64;
+            let window = 0.42 - 0.5 * w.cos() + 0.08 * (2.0 * w).cos();
+            coefficients[phase * TRUE_PEAK_TAPS + tap] = (sinc * window) as f32;
+        }
+    }
+
+    coefficients
+}
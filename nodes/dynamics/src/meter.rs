@@ -0,0 +1,160 @@
+//! Block metering and gain reduction history.
+
+use wasm_bindgen::prelude::*;
+
+/// Levels measured over the last processed block, all in gain domain
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct DynamicsMeter {
+    pub input_peak: f32,
+    pub input_rms: f32,
+    pub output_peak: f32,
+    pub output_rms: f32,
+    pub min_gain_reduction: f32,  // Deepest gain reduction as multiplier
+    pub mean_gain_reduction: f32, // Mean gain reduction as multiplier
+    pub detector_level: f32,      // Envelope level at the end of the block
+}
+
+impl DynamicsMeter {
+    pub fn new() -> Self {
+        Self {
+            input_peak: 0.0,
+            input_rms: 0.0,
+            output_peak: 0.0,
+            output_rms: 0.0,
+            min_gain_reduction: 1.0,
+            mean_gain_reduction: 1.0,
+            detector_level: 0.0,
+        }
+    }
+}
+
+impl Default for DynamicsMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Accumulates sample statistics over a block for a `DynamicsMeter`
+pub(crate) struct MeterAccumulator {
+    input_peak: f32,
+    input_sum: f32,
+    output_peak: f32,
+    output_sum: f32,
+    min_gain: f32,
+    gain_sum: f32,
+    count: usize,
+}
+
+impl MeterAccumulator {
+    pub fn new() -> Self {
+        Self {
+            input_peak: 0.0,
+            input_sum: 0.0,
+            output_peak: 0.0,
+            output_sum: 0.0,
+            min_gain: 1.0,
+            gain_sum: 0.0,
+            count: 0,
+        }
+    }
+
+    #[inline]
+    pub fn add(&mut self, input: f32, output: f32, gain: f32) {
+        self.input_peak = self.input_peak.max(input.abs());
+        self.input_sum += input * input;
+        self.output_peak = self.output_peak.max(output.abs());
+        self.output_sum += output * output;
+        self.min_gain = self.min_gain.min(gain);
+        self.gain_sum += gain;
+        self.count += 1;
+    }
+
+    pub fn finish(&self, detector_level: f32) -> DynamicsMeter {
+        if self.count == 0 {
+            return DynamicsMeter { detector_level, ..DynamicsMeter::new() };
+        }
+
+        let count = self.count as f32;
+
+        DynamicsMeter {
+            input_peak: self.input_peak,
+            input_rms: (self.input_sum / count).sqrt(),
+            output_peak: self.output_peak,
+            output_rms: (self.output_sum / count).sqrt(),
+            min_gain_reduction: self.min_gain,
+            mean_gain_reduction: self.gain_sum / count,
+            detector_level,
+        }
+    }
+}
+
+/// Ring buffer of gain reduction history. Every `decimation` samples the
+/// deepest gain reduction seen is written at `index`, so the buffer can be
+/// read straight out of wasm memory to draw a gain reduction curve.
+pub(crate) struct GainHistory {
+    buffer: Vec<f32>,
+    index: usize,
+    decimation: usize,
+    counter: usize,
+    min_gain: f32,
+}
+
+impl GainHistory {
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            index: 0,
+            decimation: 1,
+            counter: 0,
+            min_gain: 1.0,
+        }
+    }
+
+    /// Resize the history to `length` entries, each covering `decimation`
+    /// samples. A length of 0 disables the history.
+    pub fn configure(&mut self, length: usize, decimation: usize) {
+        self.buffer.clear();
+        self.buffer.resize(length, 1.0);
+        self.decimation = decimation.max(1);
+        self.reset();
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+
+    #[inline]
+    pub fn push(&mut self, gain: f32) {
+        self.min_gain = self.min_gain.min(gain);
+        self.counter += 1;
+
+        if self.counter == self.decimation {
+            self.buffer[self.index] = self.min_gain;
+            self.index = (self.index + 1) % self.buffer.len();
+            self.counter = 0;
+            self.min_gain = 1.0;
+        }
+    }
+
+    pub fn as_ptr(&self) -> *const f32 {
+        self.buffer.as_ptr()
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Index of the next entry to be written, which is the oldest entry
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.fill(1.0);
+        self.index = 0;
+        self.counter = 0;
+        self.min_gain = 1.0;
+    }
+}
@@ -0,0 +1,253 @@
+//! Multiband dynamics processing with Linkwitz-Riley band splitting.
+
+use wasm_bindgen::prelude::*;
+
+use crate::filters::{Biquad, LinkwitzRiley};
+use crate::saturation::dc_blocker_coef;
+use crate::{DynamicsProcessor, CompressionCharacter, ProcessorMode};
+
+/// Maximum number of bands
+const MAX_BANDS: usize = 5;
+
+/// Default crossover frequencies in Hz, indexed by band count - 2
+const DEFAULT_CROSSOVERS: [&[f32]; 4] = [
+    &[1000.0],
+    &[200.0, 2000.0],
+    &[120.0, 1000.0, 6000.0],
+    &[100.0, 500.0, 2000.0, 8000.0],
+];
+
+/// A band's dynamics processor along with its solo and bypass state
+struct Band {
+    processor: DynamicsProcessor,
+    solo: bool,
+    bypass: bool,
+}
+
+/// Multiband dynamics processor. The input is split into 2 to 5 bands by
+/// 4th order Linkwitz-Riley crossovers, and lower bands are passed through
+/// allpasses matching the crossovers above them so that the bands sum
+/// phase-coherently. Each band runs its own `DynamicsProcessor`. Bands
+/// whose character saturates leave DC blocking to one blocker on the sum,
+/// which shifts every band alike.
+#[wasm_bindgen]
+pub struct MultibandDynamics {
+    sample_rate: f32,
+    band_count: usize,
+    bands: Vec<Band>,
+    crossover_freqs: Vec<f32>,
+    crossovers: Vec<LinkwitzRiley>,
+    allpasses: Vec<Vec<Biquad>>, // Per band, one allpass per crossover above the band's own
+    dc_coef: f32,
+    dc_input: f32,
+    dc_output: f32,
+}
+
+#[wasm_bindgen]
+impl MultibandDynamics {
+    /// Create a new MultibandDynamics processor with 2 to 5 bands
+    pub fn new(sample_rate: f32, band_count: usize) -> Self {
+        let band_count = band_count.clamp(2, MAX_BANDS);
+
+        let mut multiband = Self {
+            sample_rate,
+            band_count,
+            bands: (0..MAX_BANDS).map(|_| {
+                let mut processor = DynamicsProcessor::new(sample_rate);
+                processor.saturator.set_dc_blocking(false);
+                Band { processor, solo: false, bypass: false }
+            }).collect(),
+            crossover_freqs: DEFAULT_CROSSOVERS[band_count - 2].to_vec(),
+            crossovers: Vec::new(),
+            allpasses: Vec::new(),
+            dc_coef: dc_blocker_coef(sample_rate),
+            dc_input: 0.0,
+            dc_output: 0.0,
+        };
+
+        multiband.update_filters();
+        multiband
+    }
+
+    // ======== Band layout ========
+
+    /// Set the number of bands (2 to 5), resetting crossovers to defaults
+    pub fn set_band_count(&mut self, band_count: usize) {
+        self.band_count = band_count.clamp(2, MAX_BANDS);
+        self.crossover_freqs = DEFAULT_CROSSOVERS[self.band_count - 2].to_vec();
+        self.update_filters();
+    }
+
+    /// Get the number of bands
+    pub fn get_band_count(&self) -> usize {
+        self.band_count
+    }
+
+    /// Set the frequency of crossover `index` in Hz. Crossovers are kept
+    /// in ascending order between their neighbours.
+    pub fn set_crossover_freq(&mut self, index: usize, freq: f32) {
+        if index >= self.crossover_freqs.len() {
+            return;
+        }
+
+        let min = if index > 0 { self.crossover_freqs[index - 1] } else { 20.0 };
+        let max = self.crossover_freqs.get(index + 1).copied().unwrap_or(self.sample_rate * 0.45);
+        self.crossover_freqs[index] = freq.clamp(min, max);
+        self.update_coefficients();
+    }
+
+    /// Get the frequency of crossover `index` in Hz
+    pub fn get_crossover_freq(&self, index: usize) -> f32 {
+        self.crossover_freqs.get(index).copied().unwrap_or(0.0)
+    }
+
+    // ======== Band parameter settings ========
+
+    /// Set band threshold as gain value (0.0 to 1.0)
+    pub fn set_band_threshold(&mut self, band: usize, threshold: f32) {
+        if let Some(b) = self.bands.get_mut(band) { b.processor.set_threshold(threshold); }
+    }
+
+    /// Set band ratio (1:n)
+    pub fn set_band_ratio(&mut self, band: usize, ratio: f32) {
+        if let Some(b) = self.bands.get_mut(band) { b.processor.set_ratio(ratio); }
+    }
+
+    /// Set band knee width (0.0 = hard knee, 1.0 = 40dB)
+    pub fn set_band_knee_width(&mut self, band: usize, knee_width: f32) {
+        if let Some(b) = self.bands.get_mut(band) { b.processor.set_knee_width(knee_width); }
+    }
+
+    /// Set band attack time in seconds
+    pub fn set_band_attack_time(&mut self, band: usize, attack_time: f32) {
+        if let Some(b) = self.bands.get_mut(band) { b.processor.set_attack_time(attack_time); }
+    }
+
+    /// Set band release time in seconds
+    pub fn set_band_release_time(&mut self, band: usize, release_time: f32) {
+        if let Some(b) = self.bands.get_mut(band) { b.processor.set_release_time(release_time); }
+    }
+
+    /// Set band makeup gain as linear gain multiplier
+    pub fn set_band_makeup_gain(&mut self, band: usize, makeup_gain: f32) {
+        if let Some(b) = self.bands.get_mut(band) { b.processor.set_makeup_gain(makeup_gain); }
+    }
+
+    /// Set band processor mode (Compress, Expand, Gate)
+    pub fn set_band_mode(&mut self, band: usize, mode: ProcessorMode) {
+        if let Some(b) = self.bands.get_mut(band) { b.processor.set_mode(mode); }
+    }
+
+    /// Set band compression character
+    pub fn set_band_character(&mut self, band: usize, character: CompressionCharacter) {
+        if let Some(b) = self.bands.get_mut(band) { b.processor.set_character(character); }
+    }
+
+    /// Solo a band. When any band is soloed only soloed bands are output.
+    pub fn set_band_solo(&mut self, band: usize, solo: bool) {
+        if let Some(b) = self.bands.get_mut(band) { b.solo = solo; }
+    }
+
+    /// Bypass a band's dynamics processing, passing the band through
+    pub fn set_band_bypass(&mut self, band: usize, bypass: bool) {
+        if let Some(b) = self.bands.get_mut(band) { b.bypass = bypass; }
+    }
+
+    /// Get a band's current gain reduction as a linear gain multiplier
+    /// (0.0 to 1.0). Bypassed bands report 1.0.
+    pub fn get_band_gain_reduction(&self, band: usize) -> f32 {
+        match self.bands.get(band) {
+            Some(b) if !b.bypass => b.processor.get_gain_reduction(),
+            _ => 1.0
+        }
+    }
+
+    // ======== Processing ========
+
+    /// Process a single sample and return the processed audio
+    pub fn process_sample(&mut self, input: f32) -> f32 {
+        let last = self.band_count - 1;
+        let any_solo = self.bands[..self.band_count].iter().any(|b| b.solo);
+        let mut rest = input;
+        let mut output = 0.0;
+
+        for (index, band) in self.bands[..self.band_count].iter_mut().enumerate() {
+            // Split off this band, aligning its phase with the bands above
+            let signal = if index < last {
+                let (low, high) = self.crossovers[index].process(rest);
+                rest = high;
+                self.allpasses[index].iter_mut().fold(low, |x, allpass| allpass.process(x))
+            } else {
+                rest
+            };
+
+            let processed = if band.bypass {
+                signal
+            } else {
+                band.processor.process_sample(signal, None)
+            };
+
+            if !any_solo || band.solo {
+                output += processed;
+            }
+        }
+
+        // Block DC from the even harmonics of saturating bands, keeping
+        // the blocker's state running while no band saturates
+        let saturating = self.bands[..self.band_count].iter()
+            .any(|b| !b.bypass && b.processor.saturator.get_drive() > 0.0);
+        self.dc_output = output - self.dc_input + self.dc_coef * self.dc_output;
+        self.dc_input = output;
+
+        if saturating { self.dc_output } else { output }
+    }
+
+    /// Process a block of samples from `input` into `output`
+    pub fn process_block(&mut self, input: &[f32], output: &mut [f32]) {
+        for (out, &sample) in output.iter_mut().zip(input) {
+            *out = self.process_sample(sample);
+        }
+    }
+
+    /// Reset the processor state
+    pub fn reset(&mut self) {
+        self.crossovers.iter_mut().for_each(LinkwitzRiley::reset);
+        self.allpasses.iter_mut().flatten().for_each(Biquad::reset);
+        self.bands.iter_mut().for_each(|b| b.processor.reset());
+        self.dc_input = 0.0;
+        self.dc_output = 0.0;
+    }
+}
+
+impl MultibandDynamics {
+    /// Rebuild crossover and allpass filters from `crossover_freqs`
+    fn update_filters(&mut self) {
+        let sample_rate = self.sample_rate;
+
+        self.crossovers = self.crossover_freqs.iter()
+            .map(|&freq| LinkwitzRiley::new(sample_rate, freq))
+            .collect();
+
+        self.allpasses = (0..self.crossover_freqs.len())
+            .map(|index| self.crossover_freqs[index + 1..].iter()
+                .map(|&freq| LinkwitzRiley::allpass(sample_rate, freq))
+                .collect())
+            .collect();
+    }
+
+    /// Update crossover and allpass coefficients from `crossover_freqs` in
+    /// place, keeping filter state so that moving a crossover is smooth
+    fn update_coefficients(&mut self) {
+        let sample_rate = self.sample_rate;
+
+        for (crossover, &freq) in self.crossovers.iter_mut().zip(&self.crossover_freqs) {
+            crossover.set_freq(sample_rate, freq);
+        }
+
+        for (index, allpasses) in self.allpasses.iter_mut().enumerate() {
+            for (allpass, &freq) in allpasses.iter_mut().zip(&self.crossover_freqs[index + 1..]) {
+                LinkwitzRiley::set_allpass_freq(allpass, sample_rate, freq);
+            }
+        }
+    }
+}
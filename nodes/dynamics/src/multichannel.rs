@@ -0,0 +1,543 @@
+//! Multichannel dynamics processing with linked detection.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{DynamicsProcessor, DynamicsMeter, CompressionCharacter, CurveInterpolation, ProcessorMode, DetectionMode, DetectionSource, FrequencyWeighting, RmsWindow, MakeupMode, Topology, Param, RampCurve};
+use crate::meter::MeterAccumulator;
+
+/// Channel linking modes for multichannel detection
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum LinkMode {
+    Independent, // Each channel detects and reduces on its own level
+    Max,         // Channels are driven by the loudest channel
+    Average,     // Channels are driven by the mean channel level
+    MidSide      // Stereo only, detect and reduce mid and side, linked by the link amount
+}
+
+/// Multichannel dynamics processor. Each channel has its own detector and
+/// gain stage, and detected levels are linked across channels before the
+/// gain is calculated. With a link amount of 1.0 in `Max` or `Average` mode
+/// every channel receives one shared gain, keeping the stereo image still.
+#[wasm_bindgen]
+pub struct MultichannelDynamics {
+    channels: Vec<DynamicsProcessor>,
+    link_mode: LinkMode,
+    link_amount: f32,        // 0.0 = unlinked, 1.0 = fully linked
+    frame: Vec<f32>,         // Scratch for one frame of samples
+    keys: Vec<f32>,          // Scratch for one frame of sidechain samples
+    levels: Vec<f32>,        // Scratch for detected levels per channel
+    gains: Vec<f32>,         // Scratch for target gains per channel
+    meters: Vec<MeterAccumulator>, // Block meter per channel
+    block_size: usize,       // Frames per block of the shared buffers
+    input_buffer: Vec<f32>,  // Shared planar buffers, written and read from JS
+    sidechain_buffer: Vec<f32>,
+    output_buffer: Vec<f32>,
+    reduction_buffer: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl MultichannelDynamics {
+    /// Create a new MultichannelDynamics processor
+    pub fn new(sample_rate: f32, channel_count: usize) -> Self {
+        let channel_count = channel_count.max(1);
+
+        Self {
+            channels: (0..channel_count).map(|_| DynamicsProcessor::new(sample_rate)).collect(),
+            link_mode: LinkMode::Max,
+            link_amount: 1.0,
+            frame: vec![0.0; channel_count],
+            keys: vec![0.0; channel_count],
+            levels: vec![0.0; channel_count],
+            gains: vec![1.0; channel_count],
+            meters: (0..channel_count).map(|_| MeterAccumulator::new()).collect(),
+            block_size: 0,
+            input_buffer: Vec::new(),
+            sidechain_buffer: Vec::new(),
+            output_buffer: Vec::new(),
+            reduction_buffer: Vec::new(),
+        }
+    }
+
+    /// Get the number of channels
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Set the number of channels. Added channels copy the settings of
+    /// the first. Allocates, so call it outside of processing where
+    /// possible.
+    pub fn set_channel_count(&mut self, channel_count: usize) {
+        let channel_count = channel_count.max(1);
+        let settings = self.channels[0].settings();
+        let sample_rate = self.channels[0].sample_rate;
+
+        self.channels.truncate(channel_count);
+        while self.channels.len() < channel_count {
+            let mut processor = DynamicsProcessor::new(sample_rate);
+            processor.set_settings(&settings);
+            self.channels.push(processor);
+        }
+
+        self.frame.resize(channel_count, 0.0);
+        self.keys.resize(channel_count, 0.0);
+        self.levels.resize(channel_count, 0.0);
+        self.gains.resize(channel_count, 1.0);
+        self.meters.resize_with(channel_count, MeterAccumulator::new);
+        self.resize_buffers();
+    }
+
+    /// Size the shared block buffers for blocks of `length` frames of
+    /// every channel. Allocates, so call it when the block size changes.
+    /// Pointers to the buffers are valid until the next call, or the
+    /// next change of channel count.
+    pub fn set_block_size(&mut self, length: usize) {
+        self.block_size = length;
+        self.resize_buffers();
+    }
+
+    /// Get a pointer to the shared planar input buffer in wasm memory
+    pub fn input_ptr(&mut self) -> *mut f32 {
+        self.input_buffer.as_mut_ptr()
+    }
+
+    /// Get a pointer to the shared planar sidechain buffer in wasm memory
+    pub fn sidechain_ptr(&mut self) -> *mut f32 {
+        self.sidechain_buffer.as_mut_ptr()
+    }
+
+    /// Get a pointer to the shared planar output buffer in wasm memory
+    pub fn output_ptr(&self) -> *const f32 {
+        self.output_buffer.as_ptr()
+    }
+
+    /// Get a pointer to the shared planar gain reduction trace in wasm
+    /// memory
+    pub fn gain_reduction_ptr(&self) -> *const f32 {
+        self.reduction_buffer.as_ptr()
+    }
+
+    // ======== Linking ========
+
+    /// Set link mode. `MidSide` falls back to `Independent` unless there
+    /// are exactly two channels.
+    pub fn set_link_mode(&mut self, mode: LinkMode) {
+        self.link_mode = mode;
+    }
+
+    /// Set link amount (0.0 = each channel uses its own level, 1.0 = fully linked)
+    pub fn set_link_amount(&mut self, amount: f32) {
+        self.link_amount = amount.clamp(0.0, 1.0);
+    }
+
+    // ======== Parameter settings ========
+
+    /// Set threshold as gain value (0.0 to 1.0)
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_threshold(threshold));
+    }
+
+    /// Set threshold in dB
+    pub fn set_threshold_db(&mut self, threshold_db: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_threshold_db(threshold_db));
+    }
+
+    /// Set ratio (1:n)
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_ratio(ratio));
+    }
+
+    /// Set knee width (0.0 = hard knee, 1.0 = 40dB)
+    pub fn set_knee_width(&mut self, knee_width: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_knee_width(knee_width));
+    }
+
+    /// Set knee width in dB (0 for a hard knee)
+    pub fn set_knee_db(&mut self, knee_db: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_knee_db(knee_db));
+    }
+
+    /// Set attack time in seconds
+    pub fn set_attack_time(&mut self, attack_time: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_attack_time(attack_time));
+    }
+
+    /// Set release time in seconds
+    pub fn set_release_time(&mut self, release_time: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_release_time(release_time));
+    }
+
+    /// Set makeup gain as linear gain multiplier
+    pub fn set_makeup_gain(&mut self, makeup_gain: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_makeup_gain(makeup_gain));
+    }
+
+    /// Set makeup gain in dB
+    pub fn set_makeup_gain_db(&mut self, makeup_db: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_makeup_gain_db(makeup_db));
+    }
+
+    /// Set makeup mode
+    pub fn set_makeup_mode(&mut self, mode: MakeupMode) {
+        self.channels.iter_mut().for_each(|p| p.set_makeup_mode(mode));
+    }
+
+    /// Enable program-dependent dual-stage release
+    pub fn set_auto_release(&mut self, enabled: bool) {
+        self.channels.iter_mut().for_each(|p| p.set_auto_release(enabled));
+    }
+
+    /// Set output gain as linear gain multiplier
+    pub fn set_output_gain(&mut self, output_gain: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_output_gain(output_gain));
+    }
+
+    /// Set output gain in dB
+    pub fn set_output_gain_db(&mut self, output_db: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_output_gain_db(output_db));
+    }
+
+    /// Set lookahead time in ms
+    pub fn set_lookahead_ms(&mut self, lookahead_ms: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_lookahead_ms(lookahead_ms));
+    }
+
+    /// Set processor mode (Compress, Expand, Gate, Duck)
+    pub fn set_mode(&mut self, mode: ProcessorMode) {
+        self.channels.iter_mut().for_each(|p| p.set_mode(mode));
+    }
+
+    /// Set the depth of ducking in dB
+    pub fn set_range_db(&mut self, range_db: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_range_db(range_db));
+    }
+
+    /// Set ducking hold time in seconds
+    pub fn set_hold_time(&mut self, hold_time: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_hold_time(hold_time));
+    }
+
+    /// Set gate hysteresis in dB
+    pub fn set_hysteresis_db(&mut self, hysteresis_db: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_hysteresis_db(hysteresis_db));
+    }
+
+    /// Set compression character
+    pub fn set_character(&mut self, character: CompressionCharacter) {
+        self.channels.iter_mut().for_each(|p| p.set_character(character));
+    }
+
+    /// Set a custom curve of interleaved input and output dB, or restore
+    /// the character's curve with an empty slice
+    pub fn set_custom_curve(&mut self, points: &[f32], interpolation: CurveInterpolation) {
+        self.channels.iter_mut().for_each(|p| p.set_custom_curve(points, interpolation));
+    }
+    
+    /// Set detection topology
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.channels.iter_mut().for_each(|p| p.set_topology(topology));
+    }
+
+    /// Set saturation drive
+    pub fn set_drive(&mut self, drive: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_drive(drive));
+    }
+
+    /// Set saturation colour
+    pub fn set_colour(&mut self, colour: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_colour(colour));
+    }
+
+    /// Return drive, colour and topology to those of the character
+    pub fn use_character_defaults(&mut self) {
+        self.channels.iter_mut().for_each(DynamicsProcessor::use_character_defaults);
+    }
+
+    /// Enable oversampled saturation
+    pub fn set_oversampling(&mut self, enabled: bool) {
+        self.channels.iter_mut().for_each(|p| p.set_oversampling(enabled));
+    }
+
+    /// Set dry/wet mix (0.0 = dry, 1.0 = wet)
+    pub fn set_mix(&mut self, mix: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_mix(mix));
+    }
+
+    /// Set the gain of the compressed path for parallel compression in dB
+    pub fn set_wet_gain_db(&mut self, wet_db: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_wet_gain_db(wet_db));
+    }
+
+    /// Set the gain of the dry path for parallel compression in dB
+    pub fn set_dry_gain_db(&mut self, dry_db: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_dry_gain_db(dry_db));
+    }
+
+    /// Set the wet path high-pass frequency in Hz, 0 to turn it off
+    pub fn set_wet_highpass(&mut self, freq: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_wet_highpass(freq));
+    }
+
+    /// Set the wet path low-pass frequency in Hz, 0 to turn it off
+    pub fn set_wet_lowpass(&mut self, freq: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_wet_lowpass(freq));
+    }
+
+    /// Set detection mode
+    pub fn set_detection_mode(&mut self, mode: DetectionMode) {
+        self.channels.iter_mut().for_each(|p| p.set_detection_mode(mode));
+    }
+
+    /// Set the signal each channel of a stereo pair detects from. With
+    /// `Mid` or `Side` both channels detect from the same signal.
+    pub fn set_detection_source(&mut self, source: DetectionSource) {
+        self.channels.iter_mut().for_each(|p| p.set_detection_source(source));
+    }
+
+    /// Set the frequency weighting applied before detection
+    pub fn set_weighting(&mut self, weighting: FrequencyWeighting) {
+        self.channels.iter_mut().for_each(|p| p.set_weighting(weighting));
+    }
+
+    /// Set RMS detection window length in ms
+    pub fn set_rms_window_ms(&mut self, window_ms: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_rms_window_ms(window_ms));
+    }
+
+    /// Set RMS averaging window type
+    pub fn set_rms_window(&mut self, window: RmsWindow) {
+        self.channels.iter_mut().for_each(|p| p.set_rms_window(window));
+    }
+
+    /// Enable detection from the sidechain input
+    pub fn set_sidechain_external(&mut self, enabled: bool) {
+        self.channels.iter_mut().for_each(|p| p.set_sidechain_external(enabled));
+    }
+
+    /// Enable the sidechain band-pass filter
+    pub fn set_sidechain_filter_enabled(&mut self, enabled: bool) {
+        self.channels.iter_mut().for_each(|p| p.set_sidechain_filter_enabled(enabled));
+    }
+
+    /// Set sidechain filter frequency in Hz
+    pub fn set_sidechain_filter_freq(&mut self, freq: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_sidechain_filter_freq(freq));
+    }
+
+    /// Set sidechain filter Q
+    pub fn set_sidechain_filter_q(&mut self, q: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_sidechain_filter_q(q));
+    }
+
+    /// Set the time in seconds over which parameter changes are smoothed
+    pub fn set_ramp_time(&mut self, time: f32) {
+        self.channels.iter_mut().for_each(|p| p.set_ramp_time(time));
+    }
+
+    /// Schedule a parameter change on every channel, as
+    /// `DynamicsProcessor::schedule_param`
+    pub fn schedule_param(&mut self, param: Param, value: f32, sample_offset: usize, curve: RampCurve) {
+        self.channels.iter_mut().for_each(|p| p.schedule_param(param, value, sample_offset, curve));
+    }
+
+    /// Cancel scheduled parameter changes, holding current values
+    pub fn cancel_scheduled_params(&mut self) {
+        self.channels.iter_mut().for_each(|p| p.cancel_scheduled_params());
+    }
+
+    /// Get the settings of the channels as JSON. Link settings are not
+    /// included.
+    pub fn get_settings(&self) -> String {
+        self.channels[0].get_settings()
+    }
+
+    /// Apply settings from JSON to every channel
+    pub fn apply_settings(&mut self, json: &str) -> Result<(), JsError> {
+        self.channels.iter_mut().try_for_each(|p| p.apply_settings(json))
+    }
+
+    /// Apply a built-in preset to every channel. Returns false for unknown
+    /// names.
+    pub fn apply_preset(&mut self, name: &str) -> bool {
+        self.channels.iter_mut().all(|p| p.apply_preset(name))
+    }
+
+    // ======== Processing ========
+
+    /// Process a block of interleaved frames in place
+    pub fn process_interleaved(&mut self, buffer: &mut [f32]) {
+        let channel_count = self.channels.len();
+
+        self.start_meters();
+
+        for frame in buffer.chunks_exact_mut(channel_count) {
+            self.frame.copy_from_slice(frame);
+            self.process_frame(false);
+            frame.copy_from_slice(&self.frame);
+        }
+
+        self.finish_meters();
+    }
+
+    /// Process a block of planar channels, laid out one channel after
+    /// another, from `input` into `output`
+    pub fn process_planar(&mut self, input: &[f32], output: &mut [f32]) {
+        self.process_block(input, &[], output, &mut []);
+    }
+
+    /// Process a block of planar channels from `input` into `output`,
+    /// detecting from the planar `sidechain` when external sidechain is
+    /// enabled. A sidechain with fewer channels keys the remaining
+    /// channels from its first. When not empty, `gain_reduction` receives
+    /// the per-sample gain reduction of each channel, planar, as a linear
+    /// gain multiplier. Does not allocate.
+    pub fn process_block(&mut self, input: &[f32], sidechain: &[f32], output: &mut [f32], gain_reduction: &mut [f32]) {
+        let channel_count = self.channels.len();
+        let length = input.len().min(output.len()) / channel_count;
+        let key_count = sidechain.len().checked_div(length).unwrap_or(0);
+
+        self.start_meters();
+
+        for i in 0..length {
+            for c in 0..channel_count {
+                self.frame[c] = input[c * length + i];
+            }
+
+            for c in 0..channel_count.min(key_count) {
+                self.keys[c] = sidechain[c * length + i];
+            }
+
+            for c in key_count..channel_count {
+                self.keys[c] = if key_count > 0 { sidechain[i] } else { 0.0 };
+            }
+
+            self.process_frame(key_count > 0);
+
+            for c in 0..channel_count {
+                output[c * length + i] = self.frame[c];
+
+                if let Some(reduction) = gain_reduction.get_mut(c * length + i) {
+                    *reduction = self.gains[c];
+                }
+            }
+        }
+
+        self.finish_meters();
+    }
+
+    /// Process the block in the shared input buffer into the shared
+    /// output buffer and gain reduction trace, keyed from the first
+    /// `key_count` channels of the shared sidechain buffer as in
+    /// `process_block`. Unlike `process_block` from JS, where wasm-bindgen
+    /// copies each slice through `__wbindgen_malloc`, this does not
+    /// allocate.
+    pub fn process_buffers(&mut self, key_count: usize) {
+        let keys = key_count.min(self.channels.len()) * self.block_size;
+        let input = std::mem::take(&mut self.input_buffer);
+        let sidechain = std::mem::take(&mut self.sidechain_buffer);
+        let mut output = std::mem::take(&mut self.output_buffer);
+        let mut reduction = std::mem::take(&mut self.reduction_buffer);
+
+        self.process_block(&input, &sidechain[..keys], &mut output, &mut reduction);
+
+        self.input_buffer = input;
+        self.sidechain_buffer = sidechain;
+        self.output_buffer = output;
+        self.reduction_buffer = reduction;
+    }
+
+    /// Get the current gain reduction of a channel as a linear gain
+    /// multiplier (0.0 to 1.0). In `MidSide` mode channel 0 is mid and
+    /// channel 1 is side.
+    pub fn get_gain_reduction(&self, channel: usize) -> f32 {
+        self.channels.get(channel).map_or(1.0, |p| p.get_gain_reduction())
+    }
+
+    /// Get levels of a channel measured over the last processed block
+    pub fn get_meter(&self, channel: usize) -> DynamicsMeter {
+        self.channels.get(channel).map_or_else(DynamicsMeter::new, |p| p.get_meter())
+    }
+
+    /// Reset the processor state
+    pub fn reset(&mut self) {
+        self.channels.iter_mut().for_each(|p| p.reset());
+    }
+}
+
+impl MultichannelDynamics {
+    /// Size the shared block buffers for the channel count and block size
+    fn resize_buffers(&mut self) {
+        let length = self.channels.len() * self.block_size;
+        self.input_buffer.resize(length, 0.0);
+        self.sidechain_buffer.resize(length, 0.0);
+        self.output_buffer.resize(length, 0.0);
+        self.reduction_buffer.resize(length, 1.0);
+    }
+
+    /// Start metering a block
+    fn start_meters(&mut self) {
+        self.meters.iter_mut().for_each(|meter| *meter = MeterAccumulator::new());
+    }
+
+    /// Keep each channel's meter of the finished block
+    fn finish_meters(&mut self) {
+        for (processor, meter) in self.channels.iter_mut().zip(&self.meters) {
+            processor.finish_meter(meter);
+        }
+    }
+
+    /// Process the frame held in `self.frame` in place, keyed from
+    /// `self.keys` when `keyed`
+    fn process_frame(&mut self, keyed: bool) {
+        let mid_side = self.link_mode == LinkMode::MidSide && self.channels.len() == 2;
+
+        // Encode left/right to mid/side
+        if mid_side {
+            let (l, r) = (self.frame[0], self.frame[1]);
+            self.frame[0] = (l + r) * 0.5;
+            self.frame[1] = (l - r) * 0.5;
+        }
+
+        // Detect each channel's level, from its detection source when
+        // the channels are a left/right pair
+        let stereo = self.channels.len() == 2 && !mid_side;
+        let (left, right) = (self.frame[0], self.frame.get(1).copied().unwrap_or(0.0));
+
+        for (c, processor) in self.channels.iter_mut().enumerate() {
+            let source = if stereo { processor.select_source(self.frame[c], left, right) } else { self.frame[c] };
+            self.levels[c] = processor.detect(source, keyed.then(|| self.keys[c]));
+        }
+
+        // Link detected levels across channels
+        let linked = match self.link_mode {
+            LinkMode::Independent => None,
+            LinkMode::Average => Some(self.levels.iter().sum::<f32>() / self.levels.len() as f32),
+            LinkMode::MidSide if !mid_side => None,
+            // Mid/side, like max, links toward the louder of the two, so
+            // mid and side are only reduced separately below full link
+            LinkMode::Max | LinkMode::MidSide => Some(self.levels.iter().fold(0.0f32, |a, &b| a.max(b)))
+        };
+
+        if let Some(linked) = linked {
+            for level in self.levels.iter_mut() {
+                *level += (linked - *level) * self.link_amount;
+            }
+        }
+
+        // Calculate and apply gain per channel
+        for (c, processor) in self.channels.iter_mut().enumerate() {
+            let input = self.frame[c];
+            let gain = processor.gain_for_level(self.levels[c]);
+            self.frame[c] = processor.apply_gain(input, gain);
+            self.gains[c] = gain;
+            processor.record(&mut self.meters[c], input, self.frame[c], gain);
+        }
+
+        // Decode mid/side back to left/right
+        if mid_side {
+            let (m, s) = (self.frame[0], self.frame[1]);
+            self.frame[0] = m + s;
+            self.frame[1] = m - s;
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! Harmonic saturation stage for compression character.
+
+use wasm_bindgen::prelude::*;
+use std::f32::consts::{PI, FRAC_1_SQRT_2};
+
+use crate::filters::Biquad;
+
+/// Cutoff of the oversampling filters as a fraction of the base sample rate
+const OVERSAMPLING_CUTOFF: f32 = 0.44;
+
+/// Cutoff of the DC blocker in Hz, removing the offset of even harmonics
+const DC_BLOCKER_FREQ: f32 = 10.0;
+
+/// Harmonic saturator. A polynomial shaper `x + a2·x² + a3·x³` generates
+/// only 2nd and 3rd harmonics, so harmonic levels are set exactly. For a
+/// full scale sine the 2nd harmonic sits at `drive · colour` and the 3rd at
+/// `drive · (1 - colour)` relative to the input level, and they scale with
+/// amplitude and amplitude squared below full scale. The squared term makes
+/// the curve asymmetric, so a DC blocker follows it. Optional 2x
+/// oversampling keeps the 3rd harmonic of high frequencies from aliasing.
+#[wasm_bindgen]
+pub struct Saturator {
+    drive: f32,              // Total harmonic level at full scale, 0.0 to 0.25
+    colour: f32,             // 0.0 = odd (3rd), 1.0 = even (2nd)
+    a2: f32,
+    a3: f32,
+    oversampling: bool,
+    dc_coef: f32,
+    dc_blocking: bool,       // Off when the owner blocks DC after summing
+    wet: Path,               // Shaped signal path
+    dry: Path,               // Linear copy for aligning a dry signal
+}
+
+/// Filter state of a signal path through the saturator
+struct Path {
+    upsampler: [Biquad; 2],
+    downsampler: [Biquad; 2],
+    dc_input: f32,
+    dc_output: f32,
+}
+
+impl Path {
+    fn new(lowpass: &Biquad) -> Self {
+        Self {
+            upsampler: [lowpass.clone(), lowpass.clone()],
+            downsampler: [lowpass.clone(), lowpass.clone()],
+            dc_input: 0.0,
+            dc_output: 0.0,
+        }
+    }
+
+    /// Pass a sample through the path, shaping it with `shape`, at twice
+    /// the rate when oversampling, then block DC when given a coefficient
+    #[inline]
+    fn process(&mut self, input: f32, oversampling: bool, dc_coef: Option<f32>, shape: impl Fn(f32) -> f32) -> f32 {
+        let shaped = if oversampling {
+            // Zero-stuff to twice the rate, shape, filter and drop every
+            // other sample
+            let up0 = self.upsample(input * 2.0);
+            let up1 = self.upsample(0.0);
+            self.downsample(shape(up0));
+            self.downsample(shape(up1))
+        } else {
+            shape(input)
+        };
+
+        let Some(dc_coef) = dc_coef else {
+            return shaped;
+        };
+
+        // Block DC from the even harmonics
+        self.dc_output = shaped - self.dc_input + dc_coef * self.dc_output;
+        self.dc_input = shaped;
+        self.dc_output
+    }
+
+    #[inline]
+    fn upsample(&mut self, x: f32) -> f32 {
+        let x = self.upsampler[0].process(x);
+        self.upsampler[1].process(x)
+    }
+
+    #[inline]
+    fn downsample(&mut self, x: f32) -> f32 {
+        let x = self.downsampler[0].process(x);
+        self.downsampler[1].process(x)
+    }
+
+    fn reset(&mut self) {
+        self.upsampler.iter_mut().chain(self.downsampler.iter_mut()).for_each(Biquad::reset);
+        self.dc_input = 0.0;
+        self.dc_output = 0.0;
+    }
+}
+
+#[wasm_bindgen]
+impl Saturator {
+    /// Create a new Saturator, with no drive
+    pub fn new(sample_rate: f32) -> Self {
+        let lowpass = Biquad::lowpass(sample_rate * 2.0, sample_rate * OVERSAMPLING_CUTOFF, FRAC_1_SQRT_2);
+
+        Self {
+            drive: 0.0,
+            colour: 0.5,
+            a2: 0.0,
+            a3: 0.0,
+            oversampling: false,
+            dc_coef: dc_blocker_coef(sample_rate),
+            dc_blocking: true,
+            wet: Path::new(&lowpass),
+            dry: Path::new(&lowpass),
+        }
+    }
+
+    /// Set drive, the total harmonic level relative to the fundamental for
+    /// a full scale sine (0.0 to 0.25)
+    pub fn set_drive(&mut self, drive: f32) {
+        self.drive = drive.clamp(0.0, 0.25);
+        self.update_coefficients();
+    }
+
+    /// Set colour, the balance of even to odd harmonics (0.0 = 3rd only,
+    /// 1.0 = 2nd only)
+    pub fn set_colour(&mut self, colour: f32) {
+        self.colour = colour.clamp(0.0, 1.0);
+        self.update_coefficients();
+    }
+
+    /// Enable 2x oversampling
+    pub fn set_oversampling(&mut self, enabled: bool) {
+        self.oversampling = enabled;
+        self.reset();
+    }
+
+    /// Get drive
+    pub fn get_drive(&self) -> f32 {
+        self.drive
+    }
+
+    /// Get colour
+    pub fn get_colour(&self) -> f32 {
+        self.colour
+    }
+
+    /// Get whether 2x oversampling is enabled
+    pub fn get_oversampling(&self) -> bool {
+        self.oversampling
+    }
+
+    /// Process a single sample
+    pub fn process_sample(&mut self, input: f32) -> f32 {
+        if self.drive == 0.0 {
+            return input;
+        }
+
+        let (a2, a3) = (self.a2, self.a3);
+        self.wet.process(input, self.oversampling, self.dc_blocker(), |x| shape(x, a2, a3))
+    }
+
+    /// Process a block of samples from `input` into `output`
+    pub fn process_block(&mut self, input: &[f32], output: &mut [f32]) {
+        for (out, &sample) in output.iter_mut().zip(input) {
+            *out = self.process_sample(sample);
+        }
+    }
+
+    /// Reset the saturator state
+    pub fn reset(&mut self) {
+        self.wet.reset();
+        self.dry.reset();
+    }
+}
+
+impl Saturator {
+    /// Derive polynomial coefficients from drive and colour. A sine of
+    /// amplitude A through a2·x² gives a 2nd harmonic of a2·A²/2, and
+    /// through a3·x³ a 3rd harmonic of a3·A³/4.
+    fn update_coefficients(&mut self) {
+        self.a2 = 2.0 * self.drive * self.colour;
+        // Negative so that the odd term compresses peaks rather than
+        // expanding them
+        self.a3 = -4.0 * self.drive * (1.0 - self.colour);
+    }
+
+    /// Pass a dry sample through the same filters as the shaped signal,
+    /// without shaping, so that it stays aligned in phase with the output
+    #[inline]
+    pub(crate) fn align(&mut self, input: f32) -> f32 {
+        if self.drive == 0.0 {
+            return input;
+        }
+
+        self.dry.process(input, self.oversampling, self.dc_blocker(), |x| x)
+    }
+
+    /// Leave DC blocking to the owner, which blocks DC once after summing
+    /// with signals that a blocker here would shift in phase against
+    pub(crate) fn set_dc_blocking(&mut self, enabled: bool) {
+        self.dc_blocking = enabled;
+        self.reset();
+    }
+
+    /// Coefficient of the DC blocker, when blocking
+    #[inline]
+    fn dc_blocker(&self) -> Option<f32> {
+        self.dc_blocking.then_some(self.dc_coef)
+    }
+}
+
+/// Pole of a one-pole DC blocker at `DC_BLOCKER_FREQ`
+pub(crate) fn dc_blocker_coef(sample_rate: f32) -> f32 {
+    1.0 - 2.0 * PI * DC_BLOCKER_FREQ / sample_rate
+}
+
+/// Shape a sample. The harmonic terms are evaluated on the input clamped to
+/// full scale, so levels above it pass through linearly.
+#[inline]
+fn shape(x: f32, a2: f32, a3: f32) -> f32 {
+    let clamped = x.clamp(-1.0, 1.0);
+    let squared = clamped * clamped;
+    x + a2 * squared + a3 * squared * clamped
+}
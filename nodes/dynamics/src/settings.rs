@@ -0,0 +1,220 @@
+//! Serialisable settings of a DynamicsProcessor, and a bank of presets.
+
+use serde::{Serialize, Deserialize};
+
+use crate::{CompressionCharacter, CurveInterpolation, ProcessorMode, MakeupMode, Topology};
+use crate::{DetectionMode, DetectionSource, FrequencyWeighting, RmsWindow};
+
+/// Every parameter of a `DynamicsProcessor`, in the units of its setters.
+/// Serialises to JSON with camelCase names, and fields missing from JSON
+/// take their defaults, which are those of a new processor.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DynamicsSettings {
+    pub threshold_db: f32,
+    pub ratio: f32,
+    pub knee_db: f32,
+    pub attack_time: f32,        // Seconds
+    pub release_time: f32,       // Seconds
+    pub auto_release: bool,
+    pub slow_release_time: f32,  // Seconds
+    pub makeup_db: f32,
+    pub makeup_mode: MakeupMode,
+    pub output_gain_db: f32,
+    pub lookahead_ms: f32,
+    pub mode: ProcessorMode,
+    pub range_db: f32,
+    pub hold_time: f32,          // Seconds
+    pub hysteresis_db: f32,
+    pub character: CompressionCharacter,
+    pub topology: Topology,
+    pub drive: f32,
+    pub colour: f32,
+    pub oversampling: bool,
+    pub mix: f32,
+    pub wet_gain_db: f32,
+    pub dry_gain_db: f32,
+    pub wet_highpass: f32,       // Hz, 0 when off
+    pub wet_lowpass: f32,        // Hz, 0 when off
+    pub detection_mode: DetectionMode,
+    pub detection_source: DetectionSource,
+    pub weighting: FrequencyWeighting,
+    pub rms_window_ms: f32,
+    pub rms_window: RmsWindow,
+    pub sidechain_external: bool,
+    pub sidechain_filter_enabled: bool,
+    pub sidechain_filter_freq: f32,
+    pub sidechain_filter_q: f32,
+    pub ramp_time: f32,          // Seconds
+    pub custom_curve: Vec<f32>,  // Interleaved input and output dB, empty when off
+    pub curve_interpolation: CurveInterpolation,
+}
+
+impl Default for DynamicsSettings {
+    fn default() -> Self {
+        Self {
+            threshold_db: -18.0,
+            ratio: 4.0,
+            knee_db: 6.0,
+            attack_time: 0.003,
+            release_time: 0.25,
+            auto_release: false,
+            slow_release_time: 1.5,
+            makeup_db: 0.0,
+            makeup_mode: MakeupMode::Manual,
+            output_gain_db: 0.0,
+            lookahead_ms: 0.0,
+            mode: ProcessorMode::Compress,
+            range_db: 12.0,
+            hold_time: 0.0,
+            hysteresis_db: 0.0,
+            character: CompressionCharacter::Clean,
+            topology: Topology::FeedForward,
+            drive: 0.0,
+            colour: 0.5,
+            oversampling: false,
+            mix: 1.0,
+            wet_gain_db: 0.0,
+            dry_gain_db: 0.0,
+            wet_highpass: 0.0,
+            wet_lowpass: 0.0,
+            detection_mode: DetectionMode::RMS,
+            detection_source: DetectionSource::Channel,
+            weighting: FrequencyWeighting::Flat,
+            rms_window_ms: 50.0,
+            rms_window: RmsWindow::Rectangular,
+            sidechain_external: false,
+            sidechain_filter_enabled: false,
+            sidechain_filter_freq: 1000.0,
+            sidechain_filter_q: 0.7,
+            ramp_time: 0.02,
+            custom_curve: Vec::new(),
+            curve_interpolation: CurveInterpolation::Linear,
+        }
+    }
+}
+
+/// Names of the built-in presets
+pub const PRESET_NAMES: [&str; 6] = [
+    "vocal-leveller",
+    "drum-bus-glue",
+    "mastering-limiter",
+    "podcast-gate",
+    "parallel-drums",
+    "voice-over-duck"
+];
+
+impl DynamicsSettings {
+    /// Parse settings from JSON
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Serialise settings to JSON
+    pub fn to_json(&self) -> String {
+        // Settings hold only numbers, booleans and unit enums, which
+        // always serialise
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Get a built-in preset by name
+    pub fn preset(name: &str) -> Option<Self> {
+        let defaults = Self::default();
+
+        let settings = match name {
+            // Optical-style levelling with slow, program-dependent release
+            "vocal-leveller" => Self {
+                threshold_db: -24.0,
+                ratio: 3.0,
+                knee_db: 10.0,
+                attack_time: 0.01,
+                release_time: 0.2,
+                auto_release: true,
+                makeup_mode: MakeupMode::Static,
+                character: CompressionCharacter::Smooth,
+                topology: Topology::FeedBack,
+                drive: 0.01,
+                colour: 0.8,
+                ..defaults
+            },
+            // Slow attack lets transients through, fast release pumps
+            // gently with the groove
+            "drum-bus-glue" => Self {
+                threshold_db: -20.0,
+                ratio: 2.0,
+                knee_db: 6.0,
+                attack_time: 0.03,
+                release_time: 0.1,
+                makeup_mode: MakeupMode::Static,
+                character: CompressionCharacter::Punchy,
+                detection_mode: DetectionMode::LogRMS,
+                sidechain_filter_enabled: true,
+                sidechain_filter_freq: 1000.0,
+                sidechain_filter_q: 0.3,
+                ..defaults
+            },
+            // Brickwall peak limiting with lookahead to catch the attack
+            "mastering-limiter" => Self {
+                threshold_db: -1.0,
+                ratio: 100.0,
+                knee_db: 0.0,
+                attack_time: 0.001,
+                release_time: 0.05,
+                auto_release: true,
+                lookahead_ms: 5.0,
+                detection_mode: DetectionMode::Peak,
+                oversampling: true,
+                ..defaults
+            },
+            // Closes between phrases, keyed from the voice band
+            "podcast-gate" => Self {
+                threshold_db: -45.0,
+                ratio: 10.0,
+                knee_db: 6.0,
+                attack_time: 0.002,
+                release_time: 0.15,
+                mode: ProcessorMode::Gate,
+                hysteresis_db: 6.0,
+                detection_mode: DetectionMode::Peak,
+                sidechain_filter_enabled: true,
+                sidechain_filter_freq: 1500.0,
+                sidechain_filter_q: 0.5,
+                ..defaults
+            },
+            // Heavy compression of the body of the kit, blended under the
+            // dry signal
+            "parallel-drums" => Self {
+                threshold_db: -30.0,
+                ratio: 8.0,
+                knee_db: 3.0,
+                attack_time: 0.001,
+                release_time: 0.08,
+                makeup_mode: MakeupMode::Static,
+                character: CompressionCharacter::Vintage,
+                topology: Topology::FeedBack,
+                drive: 0.035,
+                colour: 0.714,
+                mix: 0.4,
+                wet_highpass: 60.0,
+                wet_lowpass: 12000.0,
+                ..defaults
+            },
+            // Ducks music under a voice on the external sidechain
+            "voice-over-duck" => Self {
+                threshold_db: -36.0,
+                knee_db: 6.0,
+                attack_time: 0.05,
+                release_time: 0.5,
+                mode: ProcessorMode::Duck,
+                range_db: 12.0,
+                hold_time: 0.3,
+                sidechain_external: true,
+                weighting: FrequencyWeighting::A,
+                ..defaults
+            },
+            _ => return None
+        };
+
+        Some(settings)
+    }
+}
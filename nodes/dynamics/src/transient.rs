@@ -0,0 +1,165 @@
+//! Transient shaper driven by the difference of two envelope followers.
+
+use wasm_bindgen::prelude::*;
+
+use crate::utils::{gain_to_db, db_to_gain};
+use crate::{EnvelopeFollower, DetectionMode};
+
+/// RMS window of both envelopes in ms, short enough to follow attacks and
+/// long enough to smooth the waveform of low frequencies
+const RMS_WINDOW_MS: f32 = 5.0;
+
+/// Difference between fast and slow envelopes in dB that applies the full
+/// attack or sustain boost at a sensitivity of 1.0
+const FULL_SCALE_DIFFERENCE_DB: f32 = 12.0;
+
+/// Range of attack and sustain boost in dB
+const MAX_BOOST_DB: f32 = 24.0;
+
+/// Level above which clip protection starts to soften peaks
+const CLIP_KNEE: f32 = 0.9;
+
+/// Transient shaper. A fast and a slow envelope follower track the input,
+/// and their difference in dB separates the signal into attack, where the
+/// fast envelope rises above the slow one, and sustain, where it falls
+/// below. Attack and sustain are boosted or cut independently in
+/// proportion to that difference, so the shaper responds to the shape of
+/// the signal rather than its level.
+#[wasm_bindgen]
+pub struct TransientShaper {
+    fast: EnvelopeFollower,
+    slow: EnvelopeFollower,
+    attack_db: f32,          // Gain at full attack in dB, negative to cut
+    sustain_db: f32,         // Gain at full sustain in dB, negative to cut
+    sensitivity: f32,        // Scale of the envelope difference
+    output_gain: f32,
+    clip_protection: bool,
+    current_gain: f32,
+}
+
+#[wasm_bindgen]
+impl TransientShaper {
+    /// Create a new TransientShaper with no boost
+    pub fn new(sample_rate: f32) -> Self {
+        let mut fast = EnvelopeFollower::new(sample_rate);
+        fast.set_detection_mode(DetectionMode::RMS);
+        fast.set_rms_window_ms(RMS_WINDOW_MS);
+        fast.set_attack_time(0.001);
+        fast.set_release_time(0.05);
+
+        let mut slow = EnvelopeFollower::new(sample_rate);
+        slow.set_detection_mode(DetectionMode::RMS);
+        slow.set_rms_window_ms(RMS_WINDOW_MS);
+        slow.set_attack_time(0.03);
+        slow.set_release_time(0.3);
+
+        Self {
+            fast,
+            slow,
+            attack_db: 0.0,
+            sustain_db: 0.0,
+            sensitivity: 1.0,
+            output_gain: 1.0,
+            clip_protection: true,
+            current_gain: 1.0,
+        }
+    }
+
+    // ======== Parameter settings ========
+
+    /// Set attack boost in dB (-24 to 24), negative values soften attacks
+    pub fn set_attack(&mut self, attack_db: f32) {
+        self.attack_db = attack_db.clamp(-MAX_BOOST_DB, MAX_BOOST_DB);
+    }
+
+    /// Set sustain boost in dB (-24 to 24), negative values shorten tails
+    pub fn set_sustain(&mut self, sustain_db: f32) {
+        self.sustain_db = sustain_db.clamp(-MAX_BOOST_DB, MAX_BOOST_DB);
+    }
+
+    /// Set sensitivity (0.0 to 4.0), how strongly the envelope difference
+    /// drives the boosts
+    pub fn set_sensitivity(&mut self, sensitivity: f32) {
+        self.sensitivity = sensitivity.clamp(0.0, 4.0);
+    }
+
+    /// Set the attack and release times of the fast envelope in seconds
+    pub fn set_fast_times(&mut self, attack_time: f32, release_time: f32) {
+        self.fast.set_attack_time(attack_time);
+        self.fast.set_release_time(release_time);
+    }
+
+    /// Set the attack and release times of the slow envelope in seconds
+    pub fn set_slow_times(&mut self, attack_time: f32, release_time: f32) {
+        self.slow.set_attack_time(attack_time);
+        self.slow.set_release_time(release_time);
+    }
+
+    /// Set output gain as linear gain multiplier
+    pub fn set_output_gain(&mut self, output_gain: f32) {
+        self.output_gain = output_gain;
+    }
+
+    /// Enable clip protection, softly limiting the output to full scale
+    pub fn set_clip_protection(&mut self, enabled: bool) {
+        self.clip_protection = enabled;
+    }
+
+    // ======== Processing ========
+
+    /// Process a single sample and return the processed audio
+    pub fn process_sample(&mut self, input: f32) -> f32 {
+        let fast_db = gain_to_db(self.fast.process(input));
+        let slow_db = gain_to_db(self.slow.process(input));
+        let amount = ((fast_db - slow_db) * self.sensitivity / FULL_SCALE_DIFFERENCE_DB).clamp(-1.0, 1.0);
+
+        let gain_db = if amount > 0.0 {
+            self.attack_db * amount
+        } else {
+            self.sustain_db * -amount
+        };
+
+        self.current_gain = db_to_gain(gain_db);
+        let output = input * self.current_gain * self.output_gain;
+
+        if self.clip_protection {
+            soft_clip(output)
+        } else {
+            output
+        }
+    }
+
+    /// Process a block of samples from `input` into `output`
+    pub fn process_block(&mut self, input: &[f32], output: &mut [f32]) {
+        for (out, &sample) in output.iter_mut().zip(input) {
+            *out = self.process_sample(sample);
+        }
+    }
+
+    /// Get the current gain as a linear gain multiplier, above 1.0 when
+    /// boosting
+    pub fn get_gain(&self) -> f32 {
+        self.current_gain
+    }
+
+    /// Reset the shaper state
+    pub fn reset(&mut self) {
+        self.fast.reset();
+        self.slow.reset();
+        self.current_gain = 1.0;
+    }
+}
+
+/// Pass levels below `CLIP_KNEE` unchanged and bend levels above it
+/// smoothly towards full scale
+#[inline]
+fn soft_clip(x: f32) -> f32 {
+    let level = x.abs();
+
+    if level <= CLIP_KNEE {
+        return x;
+    }
+
+    let headroom = 1.0 - CLIP_KNEE;
+    (CLIP_KNEE + headroom * ((level - CLIP_KNEE) / headroom).tanh()).copysign(x)
+}
@@ -7,4 +7,18 @@ pub fn set_panic_hook() {
     // https://github.com/rustwasm/console_error_panic_hook#readme
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
-}
\ No newline at end of file
+}
+
+/// Floor for level conversions to dB, matching the -120 dB floor used by
+/// the worklet
+const MIN_GAIN: f32 = 0.000001;
+
+/// Convert a linear gain to dB
+pub fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.max(MIN_GAIN).log10()
+}
+
+/// Convert dB to a linear gain
+pub fn db_to_gain(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
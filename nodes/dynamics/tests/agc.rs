@@ -0,0 +1,84 @@
+mod common;
+
+use common::{sine, assert_near, SAMPLE_RATE};
+use dynamics::{AutoGain, LoudnessMeter};
+
+/// Run stereo 1kHz sines through the AGC in steps, each (level in dBFS,
+/// duration in seconds), and return the output of the last second. A
+/// stereo sine at -23dBFS has a loudness of -23 LUFS.
+fn run(agc: &mut AutoGain, steps: &[(f32, usize)]) -> Vec<f32> {
+    let mut output = Vec::new();
+
+    for &(level_db, seconds) in steps {
+        let amplitude = if level_db.is_finite() { 10.0f32.powf(level_db / 20.0) } else { 0.0 };
+        let second = sine(1000.0, amplitude, SAMPLE_RATE as usize);
+        let stereo = [second.as_slice(), second.as_slice()].concat();
+        output = vec![0.0; stereo.len()];
+
+        for _ in 0..seconds {
+            agc.process_planar(&stereo, &mut output);
+        }
+    }
+
+    output
+}
+
+fn agc(target_lufs: f32) -> AutoGain {
+    let mut agc = AutoGain::new(SAMPLE_RATE, 2);
+    agc.set_target_lufs(target_lufs);
+    agc
+}
+
+#[test]
+fn gain_converges_on_target_loudness() {
+    let mut agc = agc(-16.0);
+    let output = run(&mut agc, &[(-24.0, 30)]);
+
+    assert_near(agc.get_loudness(), -24.0, 0.1);
+    assert_near(agc.get_gain_db(), 8.0, 0.1);
+
+    let mut meter = LoudnessMeter::new(SAMPLE_RATE, 2);
+    meter.process_planar(&output);
+    assert_near(meter.get_momentary(), -16.0, 0.2);
+}
+
+#[test]
+fn loud_input_is_cut_to_target() {
+    let mut agc = agc(-16.0);
+    run(&mut agc, &[(-6.0, 30)]);
+
+    assert_near(agc.get_gain_db(), -10.0, 0.1);
+}
+
+#[test]
+fn boost_is_limited_to_max_gain() {
+    let mut agc = agc(-16.0);
+    agc.set_max_gain_db(6.0);
+    run(&mut agc, &[(-40.0, 30)]);
+
+    assert_near(agc.get_gain_db(), 6.0, 0.05);
+}
+
+#[test]
+fn gain_freezes_below_gate() {
+    // Once the 400ms loudness window has emptied, neither silence nor a
+    // level under the gate pulls the gain up
+    let mut agc = agc(-16.0);
+    run(&mut agc, &[(-24.0, 30), (f32::NEG_INFINITY, 1)]);
+    let gain_db = agc.get_gain_db();
+
+    run(&mut agc, &[(f32::NEG_INFINITY, 10), (-60.0, 10)]);
+    assert_near(agc.get_gain_db(), gain_db, 0.01);
+}
+
+#[test]
+fn gate_level_sets_where_gain_freezes() {
+    let mut agc = agc(-16.0);
+    agc.set_gate_lufs(-30.0);
+    run(&mut agc, &[(-24.0, 30), (-35.0, 1)]);
+    let gain_db = agc.get_gain_db();
+
+    // -35 LUFS is above the default gate but below this one
+    run(&mut agc, &[(-35.0, 10)]);
+    assert_near(agc.get_gain_db(), gain_db, 0.01);
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use common::{to_db, assert_near, SAMPLE_RATE};
+use dynamics::{DynamicsProcessor, Param, RampCurve};
+
+/// A processor held below threshold, so its output gain is all that
+/// changes the signal
+fn processor() -> DynamicsProcessor {
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_threshold_db(0.0);
+    processor
+}
+
+/// Process `length` samples of a steady input and return the gain in dB
+/// per sample
+fn gain_trace_db(processor: &mut DynamicsProcessor, length: usize) -> Vec<f32> {
+    let input = vec![0.1; length];
+    let mut output = vec![0.0; length];
+    processor.process_block(&input, None, &mut output, None);
+    output.into_iter().map(|y| to_db(y / 0.1)).collect()
+}
+
+#[test]
+fn step_lands_on_sample_offset() {
+    let mut processor = processor();
+    processor.schedule_param(Param::OutputGain, -6.0, 100, RampCurve::Step);
+    let trace = gain_trace_db(&mut processor, 200);
+
+    assert_near(trace[99], 0.0, 0.001);
+    assert_near(trace[100], -6.0, 0.001);
+}
+
+#[test]
+fn linear_ramp_reaches_value_on_event_sample() {
+    let mut processor = processor();
+    processor.schedule_param(Param::OutputGain, -12.0, 100, RampCurve::Linear);
+    let trace = gain_trace_db(&mut processor, 200);
+
+    // The ramp takes 101 samples to reach the event sample
+    assert_near(trace[50], -12.0 * 51.0 / 101.0, 0.001);
+    assert_near(trace[99], -12.0 * 100.0 / 101.0, 0.001);
+    assert_near(trace[100], -12.0, 0.001);
+    assert_near(trace[199], -12.0, 0.001);
+}
+
+#[test]
+fn exponential_ramp_across_zero_falls_back_to_linear() {
+    let trace = |curve| {
+        let mut processor = processor();
+        processor.set_output_gain(0.5);
+        processor.schedule_param(Param::OutputGain, 6.0, 100, curve);
+        gain_trace_db(&mut processor, 200)
+    };
+    let exponential = trace(RampCurve::Exponential);
+
+    assert!(exponential.iter().all(|db| db.is_finite()));
+    assert_eq!(exponential, trace(RampCurve::Linear));
+    assert_near(exponential[100], 6.0, 0.001);
+}
+
+#[test]
+fn exponential_ramp_in_db_is_linear_in_db() {
+    let trace = |curve| {
+        let mut processor = processor();
+        processor.set_output_gain_db(-24.0);
+        processor.schedule_param(Param::OutputGain, -6.0, 100, curve);
+        gain_trace_db(&mut processor, 200)
+    };
+    let exponential = trace(RampCurve::Exponential);
+
+    assert_eq!(exponential, trace(RampCurve::Linear));
+    assert_near(exponential[50], -24.0 + 18.0 * 51.0 / 101.0, 0.001);
+}
+
+#[test]
+fn exponential_ramp_of_mix_is_geometric() {
+    let mut processor = processor();
+    // Silence the wet path, so that the gain is the dry share, 1 - mix
+    processor.set_wet_gain_db(-120.0);
+    processor.set_mix(0.01);
+    processor.schedule_param(Param::Mix, 0.1, 100, RampCurve::Exponential);
+    let trace = gain_trace_db(&mut processor, 200);
+
+    for (sample, mix) in [(50, 0.01 * 10f32.powf(51.0 / 101.0)), (100, 0.1)] {
+        assert_near(trace[sample], to_db(1.0 - mix), 0.001);
+    }
+}
+
+#[test]
+fn latest_event_sets_the_target() {
+    let mut processor = processor();
+    processor.schedule_param(Param::OutputGain, -6.0, 200, RampCurve::Linear);
+    processor.schedule_param(Param::OutputGain, -12.0, 100, RampCurve::Linear);
+
+    assert_eq!(processor.settings().output_gain_db, -6.0);
+}
+
+#[test]
+fn cancel_holds_current_value() {
+    let mut processor = processor();
+    processor.schedule_param(Param::OutputGain, -12.0, 100, RampCurve::Linear);
+    let held = gain_trace_db(&mut processor, 50)[49];
+    processor.cancel_scheduled_params();
+
+    for db in gain_trace_db(&mut processor, 100) {
+        assert_near(db, held, 0.001);
+    }
+}
+
+#[test]
+fn zero_ramp_time_applies_immediately() {
+    let mut smoothed = processor();
+    let mut immediate = processor();
+    immediate.set_ramp_time(0.0);
+
+    // Values set before the first sample always apply immediately, so
+    // change the gain once processing has started
+    for processor in [&mut smoothed, &mut immediate] {
+        gain_trace_db(processor, 10);
+        processor.set_output_gain(0.5);
+    }
+
+    assert!(gain_trace_db(&mut smoothed, 1)[0] > -1.0);
+    assert_near(gain_trace_db(&mut immediate, 1)[0], -6.02, 0.01);
+}
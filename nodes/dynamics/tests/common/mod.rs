@@ -0,0 +1,44 @@
+//! Signal generation and measurement shared by the integration tests.
+
+#![allow(dead_code)]
+
+use std::f32::consts::PI;
+
+pub const SAMPLE_RATE: f32 = 48000.0;
+
+/// Samples to let detectors and filters settle before measuring
+pub const SETTLE: usize = 9600;
+
+/// Samples measured, a whole number of periods of every frequency used
+pub const LENGTH: usize = 4800;
+
+/// Generate `length` samples of a sine at `freq` Hz with amplitude `amplitude`
+pub fn sine(freq: f32, amplitude: f32, length: usize) -> Vec<f32> {
+    (0..length)
+        .map(|i| amplitude * (2.0 * PI * freq * i as f32 / SAMPLE_RATE).sin())
+        .collect()
+}
+
+/// Measure the amplitude of the component at `freq` Hz with a single bin
+/// DFT. `signal` should hold a whole number of periods of `freq`.
+pub fn amplitude_at(signal: &[f32], freq: f32) -> f32 {
+    let (re, im) = signal.iter().enumerate().fold((0.0f64, 0.0f64), |(re, im), (i, &x)| {
+        let phase = 2.0 * std::f64::consts::PI * freq as f64 * i as f64 / SAMPLE_RATE as f64;
+        (re + x as f64 * phase.cos(), im - x as f64 * phase.sin())
+    });
+
+    (2.0 * (re * re + im * im).sqrt() / signal.len() as f64) as f32
+}
+
+/// Convert a linear gain to dB
+pub fn to_db(gain: f32) -> f32 {
+    20.0 * gain.max(1e-9).log10()
+}
+
+/// Assert that `actual` is within `tolerance` of `expected`
+pub fn assert_near(actual: f32, expected: f32, tolerance: f32) {
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "expected {expected} ± {tolerance}, got {actual}"
+    );
+}
@@ -0,0 +1,216 @@
+mod common;
+
+use common::{to_db, assert_near, SAMPLE_RATE};
+use dynamics::{DynamicsProcessor, CompressionCharacter, ProcessorMode, DetectionMode, Topology, CurveInterpolation, transfer_curve};
+
+const CHARACTERS: [CompressionCharacter; 4] = [
+    CompressionCharacter::Clean,
+    CompressionCharacter::Smooth,
+    CompressionCharacter::Punchy,
+    CompressionCharacter::Vintage
+];
+
+const THRESHOLD_DB: f32 = -18.0;
+const RATIO: f32 = 4.0;
+const KNEE_DB: f32 = 6.0;
+
+/// Input levels spanning each side of the threshold and the knee
+const LEVELS_DB: [f32; 8] = [-40.0, -30.0, -22.0, -20.0, -18.0, -16.0, -9.0, 0.0];
+
+/// Reference soft knee curve of Giannoulis, Massberg & Reiss, returning
+/// gain in dB
+fn knee_gain_db(input_db: f32, threshold_db: f32, ratio: f32, knee_db: f32, mode: ProcessorMode) -> f32 {
+    let overshoot = input_db - threshold_db;
+    let in_knee = knee_db > 0.0 && overshoot.abs() <= knee_db / 2.0;
+
+    match mode {
+        ProcessorMode::Compress if in_knee => (1.0 / ratio - 1.0) * (overshoot + knee_db / 2.0).powi(2) / (2.0 * knee_db),
+        ProcessorMode::Compress if overshoot > 0.0 => overshoot * (1.0 / ratio - 1.0),
+        ProcessorMode::Compress => 0.0,
+        ProcessorMode::Expand | ProcessorMode::Gate => {
+            let ratio = if matches!(mode, ProcessorMode::Gate) { ratio * 2.0 } else { ratio };
+            if in_knee {
+                (1.0 - ratio) * (overshoot - knee_db / 2.0).powi(2) / (2.0 * knee_db)
+            } else if overshoot < 0.0 {
+                overshoot * (ratio - 1.0)
+            } else {
+                0.0
+            }
+        },
+        ProcessorMode::Duck => 0.0
+    }
+}
+
+/// Gain in dB each character's gain computer should give
+fn expected_gain_db(character: CompressionCharacter, input_db: f32, mode: ProcessorMode) -> f32 {
+    match character {
+        CompressionCharacter::Clean => knee_gain_db(input_db, THRESHOLD_DB, RATIO, KNEE_DB, mode),
+        // Optical softening grows with ratio, 15% at 4:1
+        CompressionCharacter::Smooth => knee_gain_db(input_db, THRESHOLD_DB, RATIO, KNEE_DB, mode) * 0.85,
+        CompressionCharacter::Punchy => match mode {
+            ProcessorMode::Compress => knee_gain_db(input_db, THRESHOLD_DB, RATIO, KNEE_DB, mode) * 1.1,
+            _ => knee_gain_db(input_db, THRESHOLD_DB, RATIO, KNEE_DB, mode)
+        },
+        // The FET knee widens by 12dB / ratio
+        CompressionCharacter::Vintage => knee_gain_db(input_db, THRESHOLD_DB, RATIO, KNEE_DB + 3.0, mode) * 0.9
+    }
+}
+
+fn processor(character: CompressionCharacter, mode: ProcessorMode) -> DynamicsProcessor {
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_character(character);
+    processor.set_topology(Topology::FeedForward);
+    processor.set_detection_mode(DetectionMode::Peak);
+    processor.set_mode(mode);
+    processor.set_threshold_db(THRESHOLD_DB);
+    processor.set_ratio(RATIO);
+    processor.set_knee_db(KNEE_DB);
+    processor.set_attack_time(0.001);
+    processor.set_release_time(0.01);
+    processor
+}
+
+/// Gain in dB the processor settles to for a constant level in dB
+fn settled_gain_db(processor: &mut DynamicsProcessor, level_db: f32) -> f32 {
+    let input = vec![10.0f32.powf(level_db / 20.0); 24000];
+    let mut output = vec![0.0; input.len()];
+    let mut reduction = vec![0.0; input.len()];
+    processor.process_block(&input, None, &mut output, Some(&mut reduction));
+    to_db(reduction[reduction.len() - 1])
+}
+
+#[test]
+fn transfer_curves_match_reference() {
+    for character in CHARACTERS {
+        for mode in [ProcessorMode::Compress, ProcessorMode::Expand, ProcessorMode::Gate, ProcessorMode::Duck] {
+            let curve = transfer_curve(character, mode, THRESHOLD_DB, RATIO, KNEE_DB, 0.0, -60.0, 0.0, 121);
+
+            for point in curve.chunks_exact(2) {
+                let expected = point[0] + expected_gain_db(character, point[0], mode);
+                assert_near(point[1], expected, 1e-3);
+            }
+        }
+    }
+}
+
+#[test]
+fn processors_settle_on_their_static_curves() {
+    for character in CHARACTERS {
+        for mode in [ProcessorMode::Compress, ProcessorMode::Expand, ProcessorMode::Gate] {
+            let mut processor = processor(character, mode);
+
+            for level_db in LEVELS_DB {
+                let expected = expected_gain_db(character, level_db, mode);
+                assert_near(settled_gain_db(&mut processor, level_db), expected, 0.05);
+            }
+        }
+    }
+}
+
+#[test]
+fn hard_knee_corners_at_threshold() {
+    let mut processor = processor(CompressionCharacter::Clean, ProcessorMode::Compress);
+    processor.set_knee_db(0.0);
+
+    assert_near(settled_gain_db(&mut processor, -18.5), 0.0, 0.01);
+    assert_near(settled_gain_db(&mut processor, -18.0), 0.0, 0.01);
+    assert_near(settled_gain_db(&mut processor, -14.0), -3.0, 0.01);
+}
+
+#[test]
+fn makeup_offsets_the_curve() {
+    let curve = transfer_curve(CompressionCharacter::Clean, ProcessorMode::Compress, -18.0, 4.0, 0.0, 6.0, -30.0, 0.0, 4);
+
+    // Inputs -30, -20, -10 and 0dB
+    assert_eq!(curve, vec![-30.0, -24.0, -20.0, -14.0, -10.0, -10.0, 0.0, -7.5]);
+}
+
+#[test]
+fn feedback_topologies_compress_at_the_set_ratio() {
+    for topology in [Topology::FeedBack, Topology::Hybrid] {
+        let mut processor = processor(CompressionCharacter::Clean, ProcessorMode::Compress);
+        processor.set_topology(topology);
+        processor.set_knee_db(0.0);
+
+        // Output level rises 1dB for every 4dB of input above threshold
+        let low = -6.0 + settled_gain_db(&mut processor, -6.0);
+        let high = 0.0 + settled_gain_db(&mut processor, 0.0);
+        assert_near((high - low) / 6.0, 1.0 / RATIO, 0.005);
+    }
+}
+
+/// Points of a drawn curve: unity to -60dB, 2:1 to -10dB, then a 10:1
+/// limiter
+const CUSTOM_POINTS: [f32; 8] = [-60.0, -60.0, -30.0, -45.0, -10.0, -20.0, 0.0, -19.0];
+
+#[test]
+fn custom_curves_pass_through_their_points() {
+    // Smooth and Vintage detect from their output, but a custom curve
+    // detects feed-forward whatever the topology
+    let cases = [
+        (CompressionCharacter::Clean, CurveInterpolation::Linear),
+        (CompressionCharacter::Clean, CurveInterpolation::Spline),
+        (CompressionCharacter::Smooth, CurveInterpolation::Linear),
+        (CompressionCharacter::Vintage, CurveInterpolation::Spline),
+    ];
+
+    for (character, interpolation) in cases {
+        let mut processor = processor(character, ProcessorMode::Compress);
+        // Restore the character's own topology
+        processor.use_character_defaults();
+        processor.set_custom_curve(&CUSTOM_POINTS, interpolation);
+        let curve = processor.get_transfer_curve(-80.0, 20.0, 11);
+
+        for point in curve.chunks_exact(2) {
+            match CUSTOM_POINTS.chunks_exact(2).find(|p| p[0] == point[0]) {
+                Some(p) => assert_near(point[1], p[1], 1e-3),
+                // Unity slope beyond the first and last points
+                None if point[0] < -60.0 => assert_near(point[1], point[0], 1e-3),
+                None if point[0] > 0.0 => assert_near(point[1], point[0] - 19.0, 1e-3),
+                None => ()
+            }
+        }
+
+        // The processor settles on the curve, regardless of threshold and
+        // ratio
+        processor.set_ratio(20.0);
+        assert_near(settled_gain_db(&mut processor, -30.0), -15.0, 0.05);
+    }
+}
+
+#[test]
+fn linear_custom_curves_are_straight_and_splines_monotone() {
+    let mut processor = processor(CompressionCharacter::Clean, ProcessorMode::Compress);
+
+    processor.set_custom_curve(&CUSTOM_POINTS, CurveInterpolation::Linear);
+    let curve = processor.get_transfer_curve(-30.0, -10.0, 5);
+    assert_near(curve[3], -38.75, 1e-3);
+    assert_near(curve[5], -32.5, 1e-3);
+
+    processor.set_custom_curve(&CUSTOM_POINTS, CurveInterpolation::Spline);
+    let curve = processor.get_transfer_curve(-60.0, 0.0, 601);
+    assert!(curve.chunks_exact(2).zip(curve.chunks_exact(2).skip(1)).all(|(a, b)| b[1] >= a[1]));
+
+    // An empty curve restores the character's curve
+    processor.set_custom_curve(&[], CurveInterpolation::Linear);
+    assert_near(settled_gain_db(&mut processor, -30.0), 0.0, 0.01);
+}
+
+#[test]
+fn larger_knee_widths_give_wider_knees() {
+    let knee_db = |knee_width| {
+        let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+        processor.set_knee_width(knee_width);
+        processor.settings().knee_db
+    };
+    let widths = [0.0, 0.01, 0.15, 0.5, 1.0];
+    let knees: Vec<f32> = widths.iter().map(|&width| knee_db(width)).collect();
+
+    assert_near(knees[0], 0.0, 1e-6);
+    assert_near(knees[4], 40.0, 1e-4);
+    assert!(knees.windows(2).all(|pair| pair[1] > pair[0]), "expected widening knees, got {knees:?}");
+
+    // Beyond the ends the knee stays hard or at its widest
+    assert_near(knee_db(-1.0), 0.0, 1e-6);
+    assert_near(knee_db(2.0), 40.0, 1e-4);
+}
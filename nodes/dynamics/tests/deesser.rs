@@ -0,0 +1,59 @@
+mod common;
+
+use common::{sine, amplitude_at, to_db, assert_near, SETTLE, LENGTH};
+use dynamics::{DeEsser, DeEsserMode};
+
+/// Process a 500Hz tone with a 7kHz sibilant and return the gain in dB of
+/// each after settling
+fn band_gains_db(deesser: &mut DeEsser, sibilance: f32) -> (f32, f32) {
+    let tone = sine(500.0, 0.3, SETTLE + LENGTH);
+    let input: Vec<f32> = tone.iter().zip(sine(7000.0, sibilance, tone.len())).map(|(a, b)| a + b).collect();
+    let mut output = vec![0.0; input.len()];
+    deesser.process_block(&input, &mut output);
+
+    let output = &output[SETTLE..];
+    (to_db(amplitude_at(output, 500.0) / 0.3), to_db(amplitude_at(output, 7000.0) / sibilance))
+}
+
+#[test]
+fn split_band_reduces_sibilance_alone() {
+    let (tone, sibilance) = band_gains_db(&mut DeEsser::new(common::SAMPLE_RATE), 0.3);
+
+    assert_near(tone, 0.0, 0.1);
+    assert!(sibilance < -6.0, "expected sibilance reduced, got {sibilance}dB");
+}
+
+#[test]
+fn wideband_reduces_everything_with_sibilance() {
+    let mut deesser = DeEsser::new(common::SAMPLE_RATE);
+    deesser.set_mode(DeEsserMode::Wideband);
+    let (tone, sibilance) = band_gains_db(&mut deesser, 0.3);
+
+    assert!(sibilance < -6.0, "expected sibilance reduced, got {sibilance}dB");
+    assert_near(tone, sibilance, 0.5);
+}
+
+#[test]
+fn quiet_sibilance_passes_flat() {
+    let (tone, sibilance) = band_gains_db(&mut DeEsser::new(common::SAMPLE_RATE), 0.001);
+
+    assert_near(tone, 0.0, 0.1);
+    assert_near(sibilance, 0.0, 0.1);
+}
+
+#[test]
+fn moving_the_frequency_does_not_click() {
+    let mut deesser = DeEsser::new(common::SAMPLE_RATE);
+    let input = sine(200.0, 0.5, SETTLE);
+    let mut output = vec![0.0; input.len()];
+
+    // Sweep the frequency across its range a block at a time
+    for (index, (input, output)) in input.chunks(128).zip(output.chunks_mut(128)).enumerate() {
+        deesser.set_frequency(4000.0 + 100.0 * index as f32);
+        deesser.process_block(input, output);
+    }
+
+    // The largest step of the sine is 0.5 * 2π * 200 / 48000, about 0.013
+    let max_step = output.windows(2).map(|pair| (pair[1] - pair[0]).abs()).fold(0.0, f32::max);
+    assert!(max_step < 0.02, "expected a smooth output, got a step of {max_step}");
+}
@@ -0,0 +1,160 @@
+mod common;
+
+use common::{sine, to_db, assert_near, SAMPLE_RATE};
+use dynamics::{DynamicsProcessor, CompressionCharacter, DetectionMode, Topology};
+
+fn ms(samples: usize) -> f32 {
+    samples as f32 * 1000.0 / SAMPLE_RATE
+}
+
+fn samples(ms: f32) -> usize {
+    (ms * 0.001 * SAMPLE_RATE) as usize
+}
+
+fn processor(character: CompressionCharacter, attack: f32, release: f32) -> DynamicsProcessor {
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_character(character);
+    // Detect feed-forward, so that the loop of feedback topologies does
+    // not speed up the detectors' own time behaviour
+    processor.set_topology(Topology::FeedForward);
+    processor.set_detection_mode(DetectionMode::Peak);
+    processor.set_threshold_db(-18.0);
+    processor.set_ratio(4.0);
+    processor.set_attack_time(attack);
+    processor.set_release_time(release);
+    processor
+}
+
+/// Run steps of constant level, each (level, duration in ms), and return
+/// the gain reduction trace in dB
+fn gain_reduction_db(processor: &mut DynamicsProcessor, steps: &[(f32, f32)]) -> Vec<f32> {
+    let input: Vec<f32> = steps.iter()
+        .flat_map(|&(level, duration)| std::iter::repeat_n(level, samples(duration)))
+        .collect();
+    let mut output = vec![0.0; input.len()];
+    let mut reduction = vec![0.0; input.len()];
+    processor.process_block(&input, None, &mut output, Some(&mut reduction));
+    reduction.into_iter().map(to_db).collect()
+}
+
+/// Time in ms for gain reduction to reach 90% of its final depth
+fn attack_ms(trace: &[f32]) -> f32 {
+    let target = trace[trace.len() - 1] * 0.9;
+    ms(trace.iter().position(|&db| db <= target).unwrap())
+}
+
+/// Time in ms from `start` for gain reduction to recover to within `db`
+fn release_ms(trace: &[f32], start: usize, db: f32) -> f32 {
+    ms(trace[start..].iter().position(|&gr| gr > -db).unwrap())
+}
+
+#[test]
+fn fet_attacks_faster_than_digital() {
+    let digital = gain_reduction_db(&mut processor(CompressionCharacter::Clean, 0.01, 0.1), &[(0.5, 100.0)]);
+    let fet = gain_reduction_db(&mut processor(CompressionCharacter::Vintage, 0.01, 0.1), &[(0.5, 100.0)]);
+
+    assert!(attack_ms(&fet) * 3.0 < attack_ms(&digital), "FET {} ms, digital {} ms", attack_ms(&fet), attack_ms(&digital));
+}
+
+#[test]
+fn opto_attack_is_limited_by_cell_lag() {
+    let digital = gain_reduction_db(&mut processor(CompressionCharacter::Clean, 0.0001, 0.1), &[(0.5, 200.0)]);
+    let opto = gain_reduction_db(&mut processor(CompressionCharacter::Smooth, 0.0001, 0.1), &[(0.5, 200.0)]);
+
+    assert!(attack_ms(&digital) < 1.0);
+    assert!(attack_ms(&opto) > 4.0, "opto {} ms", attack_ms(&opto));
+}
+
+#[test]
+fn opto_release_slows_after_sustained_reduction() {
+    let short = gain_reduction_db(&mut processor(CompressionCharacter::Smooth, 0.01, 0.1), &[(0.5, 50.0), (0.001, 5000.0)]);
+    let long = gain_reduction_db(&mut processor(CompressionCharacter::Smooth, 0.01, 0.1), &[(0.5, 3000.0), (0.001, 5000.0)]);
+
+    let short_release = release_ms(&short, samples(50.0), 1.0);
+    let long_release = release_ms(&long, samples(3000.0), 1.0);
+
+    assert!(long_release > short_release * 3.0, "short {short_release} ms, long {long_release} ms");
+}
+
+#[test]
+fn vca_releases_in_log_domain() {
+    // Stepping from -6dB to -30dB, a log domain detector passes below the
+    // knee, at -21dB, after 0.375 of its decay: τ·ln(1/0.375) with τ of
+    // 200ms / 2.2, which is 89ms. A linear detector takes nearly twice
+    // that.
+    let steps = [(0.5, 300.0), (0.0316, 1000.0)];
+    let vca = gain_reduction_db(&mut processor(CompressionCharacter::Punchy, 0.001, 0.2), &steps);
+    let digital = gain_reduction_db(&mut processor(CompressionCharacter::Clean, 0.001, 0.2), &steps);
+
+    let vca_release = release_ms(&vca, samples(300.0), 0.01);
+    let digital_release = release_ms(&digital, samples(300.0), 0.01);
+
+    assert_near(vca_release, 89.0, 5.0);
+    assert!(digital_release > vca_release * 1.8, "digital {digital_release} ms, VCA {vca_release} ms");
+}
+
+#[test]
+fn characters_settle_to_their_static_curves() {
+    // Far above the knee every detector settles on the input level
+    for character in [CompressionCharacter::Clean, CompressionCharacter::Smooth, CompressionCharacter::Punchy, CompressionCharacter::Vintage] {
+        let trace = gain_reduction_db(&mut processor(character, 0.001, 0.1), &[(0.5, 2000.0)]);
+        let gr = trace[trace.len() - 1];
+        // 12dB over threshold at 4:1 is 9dB of reduction, within the
+        // characters' scaling of the curve
+        assert!(gr < -7.0 && gr > -10.5, "{gr} dB");
+    }
+}
+
+/// Gain reduction in dB at the end of a second of a 1kHz sine
+fn sine_gain_db(mut processor: DynamicsProcessor, mode: DetectionMode) -> f32 {
+    processor.set_detection_mode(mode);
+    let input = sine(1000.0, 0.5, SAMPLE_RATE as usize);
+    let mut output = vec![0.0; input.len()];
+    let mut reduction = vec![0.0; input.len()];
+    processor.process_block(&input, None, &mut output, Some(&mut reduction));
+    to_db(reduction[reduction.len() - 1])
+}
+
+#[test]
+fn every_character_follows_detection_mode() {
+    // A sine's RMS level is 3dB under its peak, so RMS detection reduces
+    // less, by up to 2.25dB at 4:1
+    for character in [CompressionCharacter::Clean, CompressionCharacter::Smooth, CompressionCharacter::Punchy, CompressionCharacter::Vintage] {
+        let peak = sine_gain_db(processor(character, 0.001, 0.1), DetectionMode::Peak);
+        let rms = sine_gain_db(processor(character, 0.001, 0.1), DetectionMode::RMS);
+
+        assert!(rms - peak > 1.0, "peak {peak} dB, RMS {rms} dB");
+    }
+}
+
+#[test]
+fn opto_releases_once_at_the_release_time() {
+    // After a burst too short to charge the cell's memory, the optical
+    // cell releases with the envelope rather than releasing it again
+    let steps = [(0.5, 50.0), (0.001, 1000.0)];
+    let digital = gain_reduction_db(&mut processor(CompressionCharacter::Clean, 0.01, 0.1), &steps);
+    let opto = gain_reduction_db(&mut processor(CompressionCharacter::Smooth, 0.01, 0.1), &steps);
+
+    let digital_release = release_ms(&digital, samples(50.0), 1.0);
+    let opto_release = release_ms(&opto, samples(50.0), 1.0);
+
+    assert_near(opto_release, digital_release, digital_release * 0.1);
+}
+
+#[test]
+fn every_character_follows_auto_release() {
+    let steps = [(0.5, 3000.0), (0.001, 5000.0)];
+
+    for character in [CompressionCharacter::Punchy, CompressionCharacter::Vintage] {
+        let fixed = gain_reduction_db(&mut processor(character, 0.001, 0.1), &steps);
+
+        let mut auto = processor(character, 0.001, 0.1);
+        auto.set_auto_release(true);
+        let auto = gain_reduction_db(&mut auto, &steps);
+
+        let fixed_release = release_ms(&fixed, samples(3000.0), 1.0);
+        let auto_release = release_ms(&auto, samples(3000.0), 1.0);
+
+        assert!(auto_release > fixed_release * 3.0, "fixed {fixed_release} ms, auto {auto_release} ms");
+    }
+}
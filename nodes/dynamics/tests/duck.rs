@@ -0,0 +1,91 @@
+mod common;
+
+use common::{to_db, assert_near, SAMPLE_RATE};
+use dynamics::{DynamicsProcessor, ProcessorMode, DetectionMode};
+
+fn samples(ms: f32) -> usize {
+    (ms * 0.001 * SAMPLE_RATE) as usize
+}
+
+fn ms(samples: usize) -> f32 {
+    samples as f32 * 1000.0 / SAMPLE_RATE
+}
+
+fn ducker(range_db: f32, hold_time: f32, release_time: f32) -> DynamicsProcessor {
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_mode(ProcessorMode::Duck);
+    processor.set_detection_mode(DetectionMode::Peak);
+    processor.set_threshold_db(-20.0);
+    processor.set_knee_db(0.0);
+    processor.set_range_db(range_db);
+    processor.set_hold_time(hold_time);
+    processor.set_attack_time(0.001);
+    processor.set_release_time(release_time);
+    processor
+}
+
+/// Duck a steady input with a key of `key_level` for `key_ms`, then
+/// silence for `tail_ms`, and return the gain trace in dB
+fn gain_trace_db(processor: &mut DynamicsProcessor, key_level: f32, key_ms: f32, tail_ms: f32) -> Vec<f32> {
+    let length = samples(key_ms + tail_ms);
+    let key: Vec<f32> = (0..length).map(|i| if i < samples(key_ms) { key_level } else { 0.0 }).collect();
+    let input = vec![0.1; length];
+    let mut output = vec![0.0; length];
+    let mut reduction = vec![0.0; length];
+    processor.process_block(&input, Some(&key), &mut output, Some(&mut reduction));
+    reduction.into_iter().map(to_db).collect()
+}
+
+/// Time in ms after the key stops for the gain to recover to within 1dB
+fn recovery_ms(trace: &[f32], key_ms: f32) -> f32 {
+    ms(trace[samples(key_ms)..].iter().position(|&db| db > -1.0).unwrap())
+}
+
+#[test]
+fn key_above_threshold_ducks_by_range() {
+    for range_db in [6.0, 12.0, 30.0] {
+        let trace = gain_trace_db(&mut ducker(range_db, 0.0, 0.1), 0.5, 200.0, 0.0);
+        assert_near(trace[trace.len() - 1], -range_db, 0.01);
+    }
+}
+
+#[test]
+fn key_below_threshold_does_not_duck() {
+    let trace = gain_trace_db(&mut ducker(12.0, 0.0, 0.1), 0.05, 200.0, 0.0);
+    assert_near(trace[trace.len() - 1], 0.0, 0.01);
+}
+
+#[test]
+fn duck_mode_keys_from_the_sidechain() {
+    // A quiet input is ducked by a loud key without enabling the
+    // sidechain separately
+    let trace = gain_trace_db(&mut ducker(12.0, 0.0, 0.1), 0.5, 200.0, 0.0);
+    assert!(trace[trace.len() - 1] < -11.0);
+}
+
+#[test]
+fn hold_delays_release() {
+    let without = recovery_ms(&gain_trace_db(&mut ducker(12.0, 0.0, 0.1), 0.5, 200.0, 1000.0), 200.0);
+    let with = recovery_ms(&gain_trace_db(&mut ducker(12.0, 0.2, 0.1), 0.5, 200.0, 1000.0), 200.0);
+
+    assert_near(with - without, 200.0, 1.0);
+}
+
+#[test]
+fn held_depth_stays_at_range() {
+    let trace = gain_trace_db(&mut ducker(12.0, 0.5, 0.1), 0.5, 200.0, 1000.0);
+
+    // The key falls below threshold within 80ms, and the full depth is
+    // held for 500ms after
+    assert_near(trace[samples(200.0 + 80.0 + 400.0)], -12.0, 0.01);
+}
+
+#[test]
+fn release_time_sets_recovery() {
+    let fast = recovery_ms(&gain_trace_db(&mut ducker(12.0, 0.0, 0.1), 0.5, 200.0, 2000.0), 200.0);
+    let slow = recovery_ms(&gain_trace_db(&mut ducker(12.0, 0.0, 0.4), 0.5, 200.0, 2000.0), 200.0);
+
+    // Both the key's envelope and the ducking gain release four times
+    // slower
+    assert_near(slow / fast, 4.0, 0.1);
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::{sine, amplitude_at, assert_near, to_db, SAMPLE_RATE, SETTLE, LENGTH};
+use dynamics::{DynamicEq, DynamicEqShape, DynamicEqMode};
+
+/// One bell band at 1kHz, with an attack fast enough that the peak
+/// detector settles on the peaks of a sine
+fn eq() -> DynamicEq {
+    let mut eq = DynamicEq::new(SAMPLE_RATE, 1);
+    eq.set_band_frequency(0, 1000.0);
+    eq.set_band_attack_time(0, 0.0001);
+    eq.set_band_threshold_db(0, -20.0);
+    eq.set_band_ratio(0, 10.0);
+    eq.set_band_range_db(0, -12.0);
+    eq
+}
+
+/// Gain in dB of a sine through the EQ once settled
+fn gain_db(eq: &mut DynamicEq, freq: f32, amplitude: f32) -> f32 {
+    let input = sine(freq, amplitude, SETTLE + LENGTH);
+    let mut output = vec![0.0; input.len()];
+    eq.process_block(&input, &mut output);
+    to_db(amplitude_at(&output[SETTLE..], freq) / amplitude)
+}
+
+#[test]
+fn static_shapes_have_their_gain() {
+    let mut eq = DynamicEq::new(SAMPLE_RATE, 3);
+    eq.set_band_frequency(0, 100.0);
+    eq.set_band_shape(0, DynamicEqShape::LowShelf);
+    eq.set_band_gain_db(0, -6.0);
+    eq.set_band_frequency(1, 1000.0);
+    eq.set_band_gain_db(1, 6.0);
+    eq.set_band_frequency(2, 10000.0);
+    eq.set_band_shape(2, DynamicEqShape::HighShelf);
+    eq.set_band_gain_db(2, 3.0);
+
+    // Filters take their gains as they process
+    eq.process_block(&[0.0; 128], &mut [0.0; 128]);
+
+    let response = eq.get_response(&[20.0, 1000.0, 22000.0]);
+    assert_near(response[0], -6.0, 0.1);
+    assert_near(response[1], 6.0, 0.1);
+    assert_near(response[2], 3.0, 0.1);
+    assert_near(eq.get_band_gain_db(1), 6.0, 0.01);
+}
+
+#[test]
+fn loud_bands_are_cut_by_up_to_the_range() {
+    // 14dB over threshold at 10:1 would cut 12.6dB, limited to 12dB
+    assert_near(gain_db(&mut eq(), 1000.0, 0.5), -12.0, 0.2);
+    // Below threshold the band is left alone
+    assert_near(gain_db(&mut eq(), 1000.0, 0.01), 0.0, 0.1);
+    // As are loud tones outside the band
+    assert_near(gain_db(&mut eq(), 8000.0, 0.5), 0.0, 0.1);
+}
+
+#[test]
+fn below_mode_cuts_quiet_bands() {
+    let mut eq = eq();
+    eq.set_band_mode(0, DynamicEqMode::Below);
+    eq.set_band_threshold_db(0, -30.0);
+    eq.set_band_ratio(0, 2.0);
+
+    // 10dB under threshold at 1:2
+    assert_near(gain_db(&mut eq, 1000.0, 0.01), -10.0, 0.1);
+    assert_near(gain_db(&mut eq, 1000.0, 0.5), 0.0, 0.1);
+}
+
+#[test]
+fn positive_ranges_boost() {
+    let mut eq = eq();
+    eq.set_band_range_db(0, 6.0);
+
+    assert_near(gain_db(&mut eq, 1000.0, 0.5), 6.0, 0.2);
+
+    eq.set_band_bypass(0, true);
+    assert_near(gain_db(&mut eq, 1000.0, 0.5), 0.0, 0.01);
+}
+
+#[test]
+fn shared_buffers_match_process_block() {
+    let input = sine(1000.0, 0.5, LENGTH);
+    let mut output = vec![0.0; input.len()];
+    eq().process_block(&input, &mut output);
+
+    // The buffers are written and read from JS through views of wasm memory
+    let mut eq = eq();
+    eq.set_block_size(LENGTH);
+    unsafe {
+        std::slice::from_raw_parts_mut(eq.input_ptr(), input.len()).copy_from_slice(&input);
+    }
+    eq.process_buffers();
+
+    unsafe {
+        assert_eq!(std::slice::from_raw_parts(eq.output_ptr(), output.len()), output);
+    }
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use common::{to_db, assert_near, SAMPLE_RATE};
+use dynamics::{DynamicsProcessor, ProcessorMode, DetectionMode};
+
+fn gate(hysteresis_db: f32) -> DynamicsProcessor {
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_mode(ProcessorMode::Gate);
+    processor.set_detection_mode(DetectionMode::Peak);
+    processor.set_threshold_db(-40.0);
+    processor.set_ratio(4.0);
+    processor.set_knee_db(0.0);
+    processor.set_hysteresis_db(hysteresis_db);
+    processor.set_attack_time(0.0001);
+    processor.set_release_time(0.001);
+    processor
+}
+
+/// Run steps of constant level in dB, 50ms each, and return the gain in
+/// dB at the end of each step
+fn step_gains_db(processor: &mut DynamicsProcessor, levels_db: &[f32]) -> Vec<f32> {
+    levels_db.iter().map(|&level_db| {
+        let input = vec![10.0f32.powf(level_db / 20.0); 2400];
+        let mut output = vec![0.0; input.len()];
+        let mut reduction = vec![0.0; input.len()];
+        processor.process_block(&input, None, &mut output, Some(&mut reduction));
+        to_db(reduction[reduction.len() - 1])
+    }).collect()
+}
+
+#[test]
+fn gate_opens_at_threshold_and_closes_below_hysteresis() {
+    let gains = step_gains_db(&mut gate(6.0), &[-50.0, -38.0, -43.0, -47.0, -43.0]);
+
+    // Closed at -50dB: 10dB under at 8:1 is 70dB of reduction
+    assert_near(gains[0], -70.0, 0.1);
+    // Opens above threshold and stays open within the hysteresis
+    assert_near(gains[1], 0.0, 0.01);
+    assert_near(gains[2], 0.0, 0.01);
+    // Closes below threshold less the hysteresis, back to the full curve
+    // at 7dB under
+    assert_near(gains[3], -49.0, 0.1);
+    // And stays closed until the level passes the threshold again
+    assert_near(gains[4], -21.0, 0.1);
+}
+
+#[test]
+fn gate_without_hysteresis_follows_threshold() {
+    let gains = step_gains_db(&mut gate(0.0), &[-50.0, -38.0, -43.0]);
+
+    assert_near(gains[1], 0.0, 0.01);
+    assert_near(gains[2], -21.0, 0.1);
+}
+
+#[test]
+fn hysteresis_stops_chatter_around_threshold() {
+    // A level wavering ±1dB around the threshold
+    let levels: Vec<f32> = (0..20).map(|i| if i % 2 == 0 { -39.0 } else { -41.0 }).collect();
+
+    let chattering = step_gains_db(&mut gate(0.0), &levels);
+    let steady = step_gains_db(&mut gate(3.0), &levels);
+
+    assert!(chattering.iter().any(|&gain| gain < -6.0));
+    assert!(steady.iter().all(|&gain| gain > -0.01), "{steady:?}");
+}
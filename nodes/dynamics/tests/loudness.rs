@@ -0,0 +1,98 @@
+mod common;
+
+use std::f32::consts::PI;
+
+use common::{sine, assert_near, SAMPLE_RATE};
+use dynamics::LoudnessMeter;
+
+/// Measure stereo 1kHz sines in steps, each (level in dBFS, duration in
+/// seconds), as in the test signals of EBU Tech 3341 and 3342
+fn measure(steps: &[(f32, usize)]) -> LoudnessMeter {
+    let mut meter = LoudnessMeter::new(SAMPLE_RATE, 2);
+
+    for &(level_db, seconds) in steps {
+        // A second of sine holds a whole number of periods, so seconds
+        // join without a discontinuity
+        let second = sine(1000.0, 10.0f32.powf(level_db / 20.0), SAMPLE_RATE as usize);
+        let stereo = [second.as_slice(), second.as_slice()].concat();
+
+        for _ in 0..seconds {
+            meter.process_planar(&stereo);
+        }
+    }
+
+    meter
+}
+
+#[test]
+fn reference_sine_reads_minus_23_lufs() {
+    // Tech 3341 test 1
+    let meter = measure(&[(-23.0, 20)]);
+
+    assert_near(meter.get_momentary(), -23.0, 0.1);
+    assert_near(meter.get_short_term(), -23.0, 0.1);
+    assert_near(meter.get_integrated(), -23.0, 0.1);
+}
+
+#[test]
+fn integrated_loudness_follows_level() {
+    // Tech 3341 test 2
+    let meter = measure(&[(-33.0, 20)]);
+
+    assert_near(meter.get_integrated(), -33.0, 0.1);
+}
+
+#[test]
+fn relative_gate_excludes_quiet_passages() {
+    // Tech 3341 test 3
+    let meter = measure(&[(-36.0, 10), (-23.0, 60), (-36.0, 10)]);
+
+    assert_near(meter.get_integrated(), -23.0, 0.1);
+}
+
+#[test]
+fn absolute_gate_excludes_silence() {
+    // Tech 3341 test 4
+    let meter = measure(&[(-72.0, 10), (-36.0, 10), (-23.0, 60), (-36.0, 10), (-72.0, 10)]);
+
+    assert_near(meter.get_integrated(), -23.0, 0.1);
+}
+
+#[test]
+fn loudness_range_matches_tech_3342() {
+    // Tech 3342 tests 1 to 4
+    let cases: [(&[(f32, usize)], f32); 4] = [
+        (&[(-20.0, 20), (-30.0, 20)], 10.0),
+        (&[(-20.0, 20), (-15.0, 20)], 5.0),
+        (&[(-40.0, 20), (-20.0, 20)], 20.0),
+        (&[(-50.0, 20), (-35.0, 20), (-20.0, 20), (-35.0, 20), (-50.0, 20)], 15.0),
+    ];
+
+    for (steps, range) in cases {
+        assert_near(measure(steps).get_loudness_range(), range, 1.0);
+    }
+}
+
+#[test]
+fn true_peak_reads_sample_peaks() {
+    // An impulse reads its sample peak at any oversampling
+    for sample_rate in [44100.0, 48000.0, 96000.0, 192000.0] {
+        let mut meter = LoudnessMeter::new(sample_rate, 1);
+        let mut impulse = vec![0.0; 64];
+        impulse[10] = 0.5;
+        meter.process_planar(&impulse);
+
+        assert_near(meter.get_true_peak(), -6.02, 0.01);
+    }
+}
+
+#[test]
+fn true_peak_finds_peaks_between_samples() {
+    // A quarter sample rate sine sampled 45° from its peaks has samples at
+    // -3dB of its 0dB true peak
+    let mut meter = LoudnessMeter::new(SAMPLE_RATE, 1);
+    let input: Vec<f32> = (0..4800).map(|i| (PI * 0.5 * i as f32 + PI * 0.25).sin()).collect();
+    meter.process_planar(&input);
+
+    assert_near(meter.get_true_peak(), 0.0, 0.5);
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use common::{sine, amplitude_at, to_db, assert_near, SAMPLE_RATE, LENGTH};
+use dynamics::{DynamicsProcessor, ProcessorMode, MakeupMode, DetectionMode};
+
+fn measured(mode: ProcessorMode, threshold_db: f32, ratio: f32) -> DynamicsProcessor {
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_mode(mode);
+    processor.set_detection_mode(DetectionMode::RMS);
+    processor.set_threshold_db(threshold_db);
+    processor.set_ratio(ratio);
+    processor.set_knee_db(0.0);
+    processor.set_makeup_mode(MakeupMode::Measured);
+    processor
+}
+
+/// Process `seconds` of a 1kHz sine and return the gain in dB from input
+/// to output over the last 100ms
+fn run_gain_db(processor: &mut DynamicsProcessor, amplitude: f32, seconds: f32) -> f32 {
+    let input = sine(1000.0, amplitude, (seconds * SAMPLE_RATE) as usize);
+    let mut output = vec![0.0; input.len()];
+    processor.process_block(&input, None, &mut output, None);
+
+    let tail = &output[output.len() - LENGTH..];
+    if amplitude == 0.0 {
+        return to_db(tail.iter().fold(0.0, |peak, x| x.abs().max(peak)));
+    }
+
+    to_db(amplitude_at(tail, 1000.0) / amplitude)
+}
+
+#[test]
+fn measured_makeup_restores_compressed_level() {
+    // 12dB over threshold at 4:1 is 9dB of reduction
+    let mut processor = measured(ProcessorMode::Compress, -30.0, 4.0);
+    assert_near(run_gain_db(&mut processor, 0.0707, 20.0), 0.0, 0.5);
+}
+
+#[test]
+fn measured_makeup_holds_through_silence() {
+    let mut processor = measured(ProcessorMode::Compress, -30.0, 4.0);
+    run_gain_db(&mut processor, 0.0707, 20.0);
+    run_gain_db(&mut processor, 0.0, 4.0);
+
+    // Once the detector has caught up after the pause the makeup is
+    // still in place
+    let input = sine(1000.0, 0.0707, 2 * LENGTH);
+    let mut output = vec![0.0; input.len()];
+    processor.process_block(&input, None, &mut output, None);
+    assert_near(to_db(amplitude_at(&output[LENGTH..], 1000.0) / 0.0707), 0.0, 0.5);
+}
+
+#[test]
+fn measured_makeup_is_limited() {
+    // An RMS level of -6.1dB is 53.9dB over threshold, reduced by 51.2dB
+    // at 20:1 and made up by no more than 24dB
+    let mut processor = measured(ProcessorMode::Compress, -60.0, 20.0);
+    assert_near(run_gain_db(&mut processor, 0.7, 20.0), -27.2, 0.5);
+}
+
+#[test]
+fn gate_with_measured_makeup_stays_bounded_after_silence() {
+    let mut processor = measured(ProcessorMode::Gate, -40.0, 4.0);
+    run_gain_db(&mut processor, 0.0, 4.0);
+
+    let input = sine(1000.0, 0.3, LENGTH);
+    let mut output = vec![0.0; input.len()];
+    processor.process_block(&input, None, &mut output, None);
+
+    let peak = output.iter().fold(0.0f32, |peak, x| x.abs().max(peak));
+    assert!(peak.is_finite() && peak <= 0.31, "expected no makeup, got a peak of {peak}");
+}
+
+#[test]
+fn expander_and_ducker_do_not_apply_measured_makeup() {
+    let mut expander = measured(ProcessorMode::Expand, -20.0, 2.0);
+    assert!(run_gain_db(&mut expander, 0.01, 4.0) < -20.0);
+
+    let mut ducker = measured(ProcessorMode::Duck, -20.0, 2.0);
+    ducker.set_range_db(12.0);
+    assert_near(run_gain_db(&mut ducker, 0.5, 4.0), -12.0, 0.5);
+}
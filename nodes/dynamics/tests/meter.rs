@@ -0,0 +1,75 @@
+mod common;
+
+use common::{assert_near, SAMPLE_RATE};
+use dynamics::DynamicsProcessor;
+
+/// A compressor fed a rising level, so its gain reduction deepens every
+/// sample
+fn compressor() -> DynamicsProcessor {
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_threshold_db(-40.0);
+    processor.set_knee_db(0.0);
+    processor.set_attack_time(0.0);
+    processor
+}
+
+/// Process a rising level in blocks of `block` samples, returning the
+/// gain reduction trace
+fn process(processor: &mut DynamicsProcessor, length: usize, block: usize) -> Vec<f32> {
+    let input: Vec<f32> = (0..length).map(|i| 0.01 + 0.99 * i as f32 / length as f32).collect();
+    let mut output = vec![0.0; length];
+    let mut reduction = vec![0.0; length];
+
+    for ((input, output), reduction) in input.chunks(block).zip(output.chunks_mut(block)).zip(reduction.chunks_mut(block)) {
+        processor.process_block(input, None, output, Some(reduction));
+    }
+
+    reduction
+}
+
+/// Deepest gain reduction of a run of samples
+fn deepest(gains: &[f32]) -> f32 {
+    gains.iter().copied().fold(1.0, f32::min)
+}
+
+/// Read the gain history oldest first
+fn history(processor: &DynamicsProcessor) -> Vec<f32> {
+    let buffer = unsafe { std::slice::from_raw_parts(processor.gain_history_ptr(), processor.gain_history_length()) };
+    let (newer, older) = buffer.split_at(processor.gain_history_index());
+    [older, newer].concat()
+}
+
+#[test]
+fn gain_history_decimates_to_the_deepest_reduction() {
+    let mut processor = compressor();
+    processor.set_gain_history(8, 10);
+    let trace = process(&mut processor, 80, 80);
+
+    let expected: Vec<f32> = trace.chunks(10).map(deepest).collect();
+    assert_eq!(history(&processor), expected);
+    assert!(expected.windows(2).all(|pair| pair[1] < pair[0]));
+}
+
+#[test]
+fn gain_history_wraps_in_order() {
+    let mut processor = compressor();
+    processor.set_gain_history(4, 10);
+
+    // Seven entries and a part entry, across blocks that split entries
+    let trace = process(&mut processor, 75, 16);
+    let entries: Vec<f32> = trace.chunks_exact(10).map(deepest).collect();
+
+    assert_eq!(processor.gain_history_index(), 7 % 4);
+    assert_eq!(history(&processor), entries[3..]);
+}
+
+#[test]
+fn empty_gain_history_is_disabled() {
+    let mut processor = compressor();
+    processor.set_gain_history(0, 10);
+    let trace = process(&mut processor, 100, 100);
+
+    // Block meters still record without a history
+    assert_eq!(processor.gain_history_length(), 0);
+    assert_near(processor.get_meter().min_gain_reduction, deepest(&trace), 1e-6);
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use common::{assert_near, SAMPLE_RATE};
+use dynamics::{EnvelopeFollower, EnvelopeScale, DetectionMode};
+
+/// A peak follower with instant attack and release, so a constant input
+/// settles on its level in one sample
+fn follower() -> EnvelopeFollower {
+    let mut follower = EnvelopeFollower::new(SAMPLE_RATE);
+    follower.set_detection_mode(DetectionMode::Peak);
+    follower.set_attack_time(0.0);
+    follower.set_release_time(0.0);
+    follower
+}
+
+/// Output once settled on a constant level
+fn output_at(follower: &mut EnvelopeFollower, level: f32) -> f32 {
+    let input = vec![level; 64];
+    let mut output = vec![0.0; input.len()];
+    follower.process_block(&input, &mut output);
+    output[output.len() - 1]
+}
+
+#[test]
+fn default_output_is_the_envelope() {
+    let follower = || {
+        let mut follower = follower();
+        follower.set_release_time(0.001);
+        follower
+    };
+
+    let input: Vec<f32> = (0..480).map(|i| if i < 240 { 0.5 } else { 0.0 }).collect();
+    let mut output = vec![0.0; input.len()];
+    follower().process_block(&input, &mut output);
+
+    let mut reference = follower();
+    for (&x, &y) in input.iter().zip(&output) {
+        assert_eq!(reference.process(x), y);
+    }
+}
+
+#[test]
+fn decibels_scale_normalises_from_the_floor() {
+    let mut follower = follower();
+    follower.set_output_scale(EnvelopeScale::Decibels);
+    follower.set_output_floor_db(-60.0);
+
+    assert_near(output_at(&mut follower, 1.0), 1.0, 1e-4);
+    assert_near(output_at(&mut follower, 0.1), 2.0 / 3.0, 1e-4);
+    assert_near(output_at(&mut follower, 0.001), 0.0, 1e-4);
+    // Below the floor holds at the foot of the range
+    assert_near(output_at(&mut follower, 0.0001), 0.0, 1e-6);
+
+    // A range from the floor to 0 outputs dB
+    follower.set_output_range(-60.0, 0.0);
+    assert_near(output_at(&mut follower, 0.1), -20.0, 1e-3);
+}
+
+#[test]
+fn range_and_curve_map_the_output() {
+    let mut follower = follower();
+    follower.set_output_range(200.0, 2000.0);
+    assert_near(output_at(&mut follower, 0.5), 1100.0, 0.01);
+
+    follower.set_output_curve(2.0);
+    assert_near(output_at(&mut follower, 0.5), 650.0, 0.01);
+    assert_near(follower.get_output(), 650.0, 0.01);
+
+    // An inverted range falls as the envelope rises
+    follower.set_output_curve(1.0);
+    follower.set_output_range(1.0, 0.0);
+    assert_near(output_at(&mut follower, 0.25), 0.75, 1e-4);
+}
+
+#[test]
+fn shared_buffers_match_process_block() {
+    let input: Vec<f32> = (0..480).map(|i| if i < 240 { 0.5 } else { 0.0 }).collect();
+    let mut output = vec![0.0; input.len()];
+    let mut expected = follower();
+    expected.set_release_time(0.001);
+    expected.process_block(&input, &mut output);
+
+    // The buffers are written and read from JS through views of wasm memory
+    let mut follower = follower();
+    follower.set_release_time(0.001);
+    follower.set_block_size(input.len());
+    unsafe {
+        std::slice::from_raw_parts_mut(follower.input_ptr(), input.len()).copy_from_slice(&input);
+    }
+    follower.process_buffers();
+
+    unsafe {
+        assert_eq!(std::slice::from_raw_parts(follower.output_ptr(), output.len()), output);
+    }
+}
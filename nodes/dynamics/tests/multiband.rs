@@ -0,0 +1,71 @@
+mod common;
+
+use common::{sine, amplitude_at, to_db, assert_near, SETTLE, LENGTH};
+use dynamics::{MultibandDynamics, CompressionCharacter};
+
+fn bypassed(band_count: usize) -> MultibandDynamics {
+    let mut multiband = MultibandDynamics::new(common::SAMPLE_RATE, band_count);
+
+    for band in 0..band_count {
+        multiband.set_band_bypass(band, true);
+    }
+
+    multiband
+}
+
+#[test]
+fn bypassed_bands_sum_flat() {
+    for band_count in 2..=5 {
+        for freq in [50.0, 100.0, 500.0, 1000.0, 2000.0, 8000.0, 15000.0] {
+            let mut multiband = bypassed(band_count);
+            let input = sine(freq, 0.5, SETTLE + LENGTH);
+            let mut output = vec![0.0; input.len()];
+            multiband.process_block(&input, &mut output);
+
+            let gain = amplitude_at(&output[SETTLE..], freq) / 0.5;
+            assert_near(to_db(gain), 0.0, 0.01);
+        }
+    }
+}
+
+#[test]
+fn moving_a_crossover_does_not_click() {
+    let mut multiband = bypassed(2);
+    let input = sine(200.0, 0.5, SETTLE);
+    let mut output = vec![0.0; input.len()];
+
+    // Sweep the crossover across the signal a block at a time
+    for (index, (input, output)) in input.chunks(128).zip(output.chunks_mut(128)).enumerate() {
+        multiband.set_crossover_freq(0, 100.0 + 10.0 * index as f32);
+        multiband.process_block(input, output);
+    }
+
+    // The largest step of the sine is 0.5 * 2π * 200 / 48000, about 0.013
+    let max_step = output.windows(2).map(|pair| (pair[1] - pair[0]).abs()).fold(0.0, f32::max);
+    assert!(max_step < 0.02, "expected a smooth output, got a step of {max_step}");
+}
+
+#[test]
+fn saturating_bands_stay_phase_coherent() {
+    // At a low crossover a DC blocker on one band alone would shift it in
+    // phase against the other, so mixed characters must sum as all
+    // saturating bands do
+    let crossover_db = |characters: [CompressionCharacter; 2]| {
+        let mut multiband = MultibandDynamics::new(common::SAMPLE_RATE, 2);
+        multiband.set_crossover_freq(0, 30.0);
+
+        for (band, character) in characters.into_iter().enumerate() {
+            multiband.set_band_character(band, character);
+            multiband.set_band_threshold(band, 1.0);
+        }
+
+        let input = sine(30.0, 0.05, SETTLE + LENGTH);
+        let mut output = vec![0.0; input.len()];
+        multiband.process_block(&input, &mut output);
+        to_db(amplitude_at(&output[SETTLE..], 30.0) / 0.05)
+    };
+
+    let mixed = crossover_db([CompressionCharacter::Vintage, CompressionCharacter::Clean]);
+    let saturating = crossover_db([CompressionCharacter::Vintage, CompressionCharacter::Vintage]);
+    assert_near(mixed, saturating, 0.02);
+}
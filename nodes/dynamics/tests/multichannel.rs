@@ -0,0 +1,117 @@
+mod common;
+
+use common::{sine, to_db, assert_near, SAMPLE_RATE};
+use dynamics::{MultichannelDynamics, LinkMode, DetectionMode, ProcessorMode};
+
+const LENGTH: usize = 9600;
+
+fn linked(mode: LinkMode) -> MultichannelDynamics {
+    let mut processor = MultichannelDynamics::new(SAMPLE_RATE, 2);
+    processor.set_link_mode(mode);
+    processor.set_detection_mode(DetectionMode::Peak);
+    processor.set_threshold(0.1);
+    processor.set_ratio(4.0);
+    processor.set_knee_width(0.0);
+    processor.set_attack_time(0.001);
+    processor.set_release_time(0.05);
+    processor
+}
+
+/// Process a loud left and a quiet right channel and return the gain
+/// reduction of each channel in dB at the end of the block
+fn end_gains_db(processor: &mut MultichannelDynamics) -> (f32, f32) {
+    let input = [sine(1000.0, 0.8, LENGTH), sine(1000.0, 0.05, LENGTH)].concat();
+    let mut output = vec![0.0; input.len()];
+    let mut reduction = vec![0.0; input.len()];
+    processor.process_block(&input, &[], &mut output, &mut reduction);
+    (to_db(reduction[LENGTH - 1]), to_db(reduction[2 * LENGTH - 1]))
+}
+
+#[test]
+fn linked_modes_give_both_channels_the_same_gain() {
+    for mode in [LinkMode::Max, LinkMode::Average, LinkMode::MidSide] {
+        let (left, right) = end_gains_db(&mut linked(mode));
+
+        assert!(left < -3.0, "expected reduction, got {left}dB");
+        assert_near(left, right, 0.001);
+    }
+}
+
+#[test]
+fn independent_channels_reduce_on_their_own_level() {
+    let (left, right) = end_gains_db(&mut linked(LinkMode::Independent));
+
+    assert!(left < -3.0, "expected reduction, got {left}dB");
+    assert_near(right, 0.0, 0.001);
+}
+
+#[test]
+fn partial_link_amount_moves_gains_towards_each_other() {
+    let (full_left, _) = end_gains_db(&mut linked(LinkMode::Independent));
+
+    let mut processor = linked(LinkMode::Max);
+    processor.set_link_amount(0.5);
+    let (left, right) = end_gains_db(&mut processor);
+
+    assert_near(left, full_left, 0.001);
+    assert!(right < -0.5 && right > left, "expected right between 0dB and {left}dB, got {right}dB");
+}
+
+#[test]
+fn mono_sidechain_keys_every_channel() {
+    let mut processor = linked(LinkMode::Independent);
+    processor.set_mode(ProcessorMode::Duck);
+    processor.set_range_db(12.0);
+    processor.set_sidechain_external(true);
+
+    let input = [sine(1000.0, 0.1, LENGTH), sine(1000.0, 0.1, LENGTH)].concat();
+    let key = vec![0.5; LENGTH];
+    let mut output = vec![0.0; input.len()];
+    let mut reduction = vec![0.0; input.len()];
+    processor.process_block(&input, &key, &mut output, &mut reduction);
+
+    assert_near(to_db(reduction[LENGTH - 1]), -12.0, 0.1);
+    assert_near(to_db(reduction[2 * LENGTH - 1]), -12.0, 0.1);
+}
+
+#[test]
+fn added_channels_copy_the_first() {
+    let mut processor = linked(LinkMode::Independent);
+    processor.set_channel_count(3);
+    assert_eq!(processor.channel_count(), 3);
+
+    let input = vec![0.8; 3 * LENGTH];
+    let mut output = vec![0.0; input.len()];
+    let mut reduction = vec![0.0; input.len()];
+    processor.process_block(&input, &[], &mut output, &mut reduction);
+
+    assert_near(to_db(reduction[3 * LENGTH - 1]), to_db(reduction[LENGTH - 1]), 0.001);
+    assert!(processor.get_meter(2).min_gain_reduction < 0.5);
+}
+
+#[test]
+fn shared_buffers_match_process_block() {
+    let input = [sine(1000.0, 0.8, LENGTH), sine(1000.0, 0.05, LENGTH)].concat();
+    let key = sine(300.0, 0.5, LENGTH);
+    let mut output = vec![0.0; input.len()];
+    let mut reduction = vec![0.0; input.len()];
+
+    let mut expected = linked(LinkMode::Average);
+    expected.set_sidechain_external(true);
+    expected.process_block(&input, &key, &mut output, &mut reduction);
+
+    // The buffers are written and read from JS through views of wasm memory
+    let mut processor = linked(LinkMode::Average);
+    processor.set_sidechain_external(true);
+    processor.set_block_size(LENGTH);
+    unsafe {
+        std::slice::from_raw_parts_mut(processor.input_ptr(), input.len()).copy_from_slice(&input);
+        std::slice::from_raw_parts_mut(processor.sidechain_ptr(), key.len()).copy_from_slice(&key);
+    }
+    processor.process_buffers(1);
+
+    unsafe {
+        assert_eq!(std::slice::from_raw_parts(processor.output_ptr(), output.len()), output);
+        assert_eq!(std::slice::from_raw_parts(processor.gain_reduction_ptr(), reduction.len()), reduction);
+    }
+}
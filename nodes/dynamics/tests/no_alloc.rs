@@ -0,0 +1,187 @@
+//! Processing must not allocate, as it runs on the audio thread. A counting
+//! allocator records allocations made while a test's thread is processing.
+//! These are native calls; from JS, wasm-bindgen copies slice arguments
+//! through `__wbindgen_malloc`, so the worklets pass blocks through shared
+//! buffers instead, as with `MultichannelDynamics::process_buffers`.
+
+mod common;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use common::{sine, SAMPLE_RATE};
+use dynamics::{
+    DynamicsProcessor, MultichannelDynamics, MultibandDynamics, DeEsser, TransientShaper, DynamicEq, EnvelopeFollower, EnvelopeScale,
+    LoudnessMeter, AutoGain, CompressionCharacter, ProcessorMode, DetectionMode,
+    FrequencyWeighting, Topology, LinkMode, Param, RampCurve, CurveInterpolation
+};
+
+struct CountingAlloc;
+
+thread_local! {
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count() {
+    if COUNTING.try_with(Cell::get).unwrap_or(false) {
+        ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count();
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAlloc = CountingAlloc;
+
+/// Run `process` and fail if it allocates. The flag and count are per
+/// thread, so only allocations on this thread count, and tests running
+/// on other threads at the same time do not.
+fn assert_no_alloc(name: &str, process: impl FnOnce()) {
+    let before = ALLOCATIONS.with(Cell::get);
+    COUNTING.with(|counting| counting.set(true));
+    process();
+    COUNTING.with(|counting| counting.set(false));
+    let allocations = ALLOCATIONS.with(Cell::get) - before;
+
+    assert_eq!(allocations, 0, "{name} allocated {allocations} times while processing");
+}
+
+fn input() -> Vec<f32> {
+    sine(1000.0, 0.8, 4800)
+}
+
+#[test]
+fn dynamics_processor_does_not_allocate() {
+    let input = input();
+    let mut output = vec![0.0; input.len()];
+    let mut reduction = vec![0.0; input.len()];
+
+    for character in [CompressionCharacter::Clean, CompressionCharacter::Smooth, CompressionCharacter::Punchy, CompressionCharacter::Vintage] {
+        for mode in [ProcessorMode::Compress, ProcessorMode::Expand, ProcessorMode::Gate, ProcessorMode::Duck] {
+            for detection_mode in [DetectionMode::Peak, DetectionMode::RMS, DetectionMode::LogRMS, DetectionMode::Adaptive] {
+                let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+                processor.set_character(character);
+                processor.set_mode(mode);
+                processor.set_detection_mode(detection_mode);
+
+                assert_no_alloc("DynamicsProcessor", || {
+                    processor.process_block(&input, Some(&input), &mut output, Some(&mut reduction));
+                    for &sample in &input {
+                        processor.process_sample(sample, None);
+                    }
+                });
+            }
+        }
+    }
+}
+
+#[test]
+fn dynamics_processor_features_do_not_allocate() {
+    let input = input();
+    let mut output = vec![0.0; input.len()];
+
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_topology(Topology::Hybrid);
+    processor.set_weighting(FrequencyWeighting::K);
+    processor.set_lookahead_ms(5.0);
+    processor.set_drive(0.1);
+    processor.set_oversampling(true);
+    processor.set_mix(0.5);
+    processor.set_wet_highpass(100.0);
+    processor.set_wet_lowpass(8000.0);
+    processor.set_sidechain_filter_enabled(true);
+    processor.set_gain_history(256, 32);
+    processor.set_custom_curve(&[-60.0, -60.0, -20.0, -30.0, 0.0, -24.0], CurveInterpolation::Spline);
+
+    // Parameters changing while processing, smoothed and scheduled
+    processor.process_block(&input, None, &mut output, None);
+    processor.set_threshold_db(-30.0);
+    processor.schedule_param(Param::Ratio, 8.0, 100, RampCurve::Step);
+    processor.schedule_param(Param::Mix, 1.0, 2000, RampCurve::Linear);
+    processor.schedule_param(Param::OutputGain, -6.0, 4000, RampCurve::Exponential);
+
+    assert_no_alloc("DynamicsProcessor", || {
+        processor.process_block(&input, None, &mut output, None);
+    });
+}
+
+#[test]
+fn multichannel_processors_do_not_allocate() {
+    let interleaved: Vec<f32> = input().into_iter().flat_map(|x| [x, 0.5 * x]).collect();
+    let mut buffer = interleaved.clone();
+    let mut output = vec![0.0; interleaved.len()];
+
+    for link_mode in [LinkMode::Independent, LinkMode::Max, LinkMode::Average, LinkMode::MidSide] {
+        let mut processor = MultichannelDynamics::new(SAMPLE_RATE, 2);
+        processor.set_link_mode(link_mode);
+        processor.set_block_size(128);
+
+        assert_no_alloc("MultichannelDynamics", || {
+            processor.process_interleaved(&mut buffer);
+            processor.process_planar(&interleaved, &mut output);
+            processor.process_buffers(1);
+        });
+    }
+
+    let mut loudness = LoudnessMeter::new(SAMPLE_RATE, 2);
+    assert_no_alloc("LoudnessMeter", || {
+        loudness.process_interleaved(&interleaved);
+        loudness.process_planar(&interleaved);
+    });
+
+    let mut agc = AutoGain::new(SAMPLE_RATE, 2);
+    assert_no_alloc("AutoGain", || {
+        agc.process_interleaved(&mut buffer);
+        agc.process_planar(&interleaved, &mut output);
+    });
+}
+
+#[test]
+fn band_processors_do_not_allocate() {
+    let input = input();
+    let mut output = vec![0.0; input.len()];
+
+    let mut multiband = MultibandDynamics::new(SAMPLE_RATE, 3);
+    assert_no_alloc("MultibandDynamics", || multiband.process_block(&input, &mut output));
+
+    let mut deesser = DeEsser::new(SAMPLE_RATE);
+    assert_no_alloc("DeEsser", || deesser.process_block(&input, &mut output));
+
+    let mut shaper = TransientShaper::new(SAMPLE_RATE);
+    assert_no_alloc("TransientShaper", || shaper.process_block(&input, &mut output));
+
+    let mut follower = EnvelopeFollower::new(SAMPLE_RATE);
+    follower.set_output_scale(EnvelopeScale::Decibels);
+    follower.set_output_curve(2.0);
+    assert_no_alloc("EnvelopeFollower", || follower.process_block(&input, &mut output));
+
+    follower.set_block_size(128);
+    assert_no_alloc("EnvelopeFollower buffers", || follower.process_buffers());
+
+    let mut eq = DynamicEq::new(SAMPLE_RATE, 4);
+    eq.set_band_frequency(0, 1000.0);
+    assert_no_alloc("DynamicEq", || eq.process_block(&input, &mut output));
+
+    eq.set_block_size(128);
+    assert_no_alloc("DynamicEq buffers", || eq.process_buffers());
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use common::{sine, amplitude_at, to_db, assert_near, SAMPLE_RATE, SETTLE, LENGTH};
+use dynamics::DynamicsProcessor;
+
+/// Gain in dB at `freq` of a processor left below threshold
+fn response_db(processor: &mut DynamicsProcessor, freq: f32) -> f32 {
+    processor.reset();
+    let input = sine(freq, 0.25, SETTLE + LENGTH);
+    let mut output = vec![0.0; input.len()];
+    processor.process_block(&input, None, &mut output, None);
+
+    to_db(amplitude_at(&output[SETTLE..], freq) / 0.25)
+}
+
+#[test]
+fn filtered_parallel_mix_sums_flat() {
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_threshold_db(0.0);
+    processor.set_wet_highpass(200.0);
+    processor.set_wet_lowpass(5000.0);
+
+    for mix in [0.0, 0.5, 1.0] {
+        processor.set_mix(mix);
+
+        for freq in [50.0, 200.0, 1000.0, 5000.0, 15000.0] {
+            assert_near(response_db(&mut processor, freq), 0.0, 0.05);
+        }
+    }
+}
+
+#[test]
+fn wet_filters_limit_compression_to_their_band() {
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_threshold_db(-40.0);
+    processor.set_ratio(10.0);
+    processor.set_wet_highpass(2000.0);
+
+    // Below the high-pass the wet path carries the signal uncompressed
+    assert_near(response_db(&mut processor, 100.0), 0.0, 0.1);
+    assert!(response_db(&mut processor, 10000.0) < -20.0);
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use common::{sine, assert_near, SAMPLE_RATE};
+use dynamics::{EnvelopeFollower, DetectionMode, RmsWindow};
+
+/// Window of 10ms, 480 samples
+const WINDOW: usize = 480;
+
+/// An RMS follower with instant ballistics, so it reads the windowed RMS
+fn follower(window: RmsWindow) -> EnvelopeFollower {
+    let mut follower = EnvelopeFollower::new(SAMPLE_RATE);
+    follower.set_detection_mode(DetectionMode::RMS);
+    follower.set_rms_window(window);
+    follower.set_rms_window_ms(10.0);
+    follower.set_attack_time(0.0);
+    follower.set_release_time(0.0);
+    follower
+}
+
+fn follow(follower: &mut EnvelopeFollower, input: &[f32]) -> Vec<f32> {
+    input.iter().map(|&x| follower.process(x)).collect()
+}
+
+#[test]
+fn rectangular_window_reaches_a_step_after_one_window() {
+    let trace = follow(&mut follower(RmsWindow::Rectangular), &[0.5; 2 * WINDOW]);
+
+    assert_near(trace[WINDOW - 2], 0.5 * ((WINDOW - 1) as f32 / WINDOW as f32).sqrt(), 1e-6);
+    assert!(trace[WINDOW - 2] < 0.4999);
+    assert_near(trace[WINDOW - 1], 0.5, 1e-6);
+    assert_near(trace[2 * WINDOW - 1], 0.5, 1e-6);
+}
+
+#[test]
+fn exponential_window_is_a_one_pole_average() {
+    // Power averaged by a one-pole with the window as its time constant
+    let input: Vec<f32> = [sine(1000.0, 0.5, 4 * WINDOW), sine(1000.0, 0.1, 4 * WINDOW)].concat();
+    let coef = (-1.0 / WINDOW as f64).exp();
+    let mut mean = 0.0f64;
+
+    let trace = follow(&mut follower(RmsWindow::Exponential), &input);
+
+    for (&x, &rms) in input.iter().zip(&trace) {
+        mean = coef * mean + (1.0 - coef) * (x as f64).powi(2);
+        assert_near(rms, mean.sqrt() as f32, 1e-5);
+    }
+
+    // A step reaches 1 - 1/e of its power after one window
+    let trace = follow(&mut follower(RmsWindow::Exponential), &[0.5; WINDOW]);
+    assert_near(trace[WINDOW - 1], 0.5 * (1.0 - (-1.0f32).exp()).sqrt(), 1e-4);
+}
+
+#[test]
+fn running_sum_does_not_drift() {
+    // A minute of slowly rising DC under a tone, for the running sum to
+    // accumulate rounding error in
+    let length = 60 * SAMPLE_RATE as usize;
+    let tone = sine(1000.0, 0.01, length);
+    let input: Vec<f32> = tone.iter().enumerate()
+        .map(|(i, &x)| 0.01 + 0.99 * i as f32 / length as f32 + x)
+        .collect();
+
+    let trace = follow(&mut follower(RmsWindow::Rectangular), &input);
+
+    // Check mid-window too, between the re-sums
+    for end in [length / 3 + WINDOW / 2, 2 * length / 3 + 7, length - 1] {
+        let power = input[end + 1 - WINDOW..=end].iter().map(|&x| (x as f64).powi(2)).sum::<f64>() / WINDOW as f64;
+        let exact = power.sqrt() as f32;
+        assert_near(trace[end] / exact, 1.0, 1e-5);
+    }
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::{sine, SAMPLE_RATE};
+use dynamics::{DynamicsProcessor, CompressionCharacter, ProcessorMode, DetectionMode, Topology, transfer_curve};
+
+const CHARACTERS: [CompressionCharacter; 4] = [
+    CompressionCharacter::Clean,
+    CompressionCharacter::Smooth,
+    CompressionCharacter::Punchy,
+    CompressionCharacter::Vintage
+];
+
+const MODES: [ProcessorMode; 4] = [ProcessorMode::Compress, ProcessorMode::Expand, ProcessorMode::Gate, ProcessorMode::Duck];
+
+const DETECTION_MODES: [DetectionMode; 4] = [DetectionMode::Peak, DetectionMode::RMS, DetectionMode::LogRMS, DetectionMode::Adaptive];
+
+/// Silence, then a full scale sine, then silence again
+fn signal() -> Vec<f32> {
+    let mut signal = vec![0.0; 4800];
+    signal.extend(sine(1000.0, 1.0, 4800));
+    signal.extend(vec![0.0; 4800]);
+    signal
+}
+
+fn assert_finite(processor: &mut DynamicsProcessor, input: &[f32]) {
+    let mut output = vec![0.0; input.len()];
+    let mut reduction = vec![0.0; input.len()];
+    processor.process_block(input, Some(input), &mut output, Some(&mut reduction));
+
+    assert!(output.iter().all(|x| x.is_finite()));
+    assert!(reduction.iter().all(|x| x.is_finite()));
+}
+
+#[test]
+fn zero_thresholds_stay_finite() {
+    let input = signal();
+
+    for character in CHARACTERS {
+        for mode in MODES {
+            for detection_mode in DETECTION_MODES {
+                for topology in [Topology::FeedForward, Topology::FeedBack, Topology::Hybrid] {
+                    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+                    processor.set_character(character);
+                    processor.set_mode(mode);
+                    processor.set_detection_mode(detection_mode);
+                    processor.set_topology(topology);
+                    // A threshold of zero gain, at the foot of the dB range
+                    processor.set_threshold(0.0);
+                    processor.set_ratio(20.0);
+                    processor.set_knee_width(0.0);
+                    assert_finite(&mut processor, &input);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn extreme_settings_stay_finite() {
+    let input = signal();
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_threshold_db(f32::NEG_INFINITY);
+    processor.set_ratio(f32::INFINITY);
+    processor.set_knee_db(f32::INFINITY);
+    processor.set_attack_time(0.0);
+    processor.set_release_time(0.0);
+    processor.set_makeup_gain(0.0);
+    assert_finite(&mut processor, &input);
+
+    // Settings beyond their ranges clamp
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_threshold_db(10.0);
+    processor.set_ratio(0.0);
+    processor.set_knee_db(-6.0);
+    assert_finite(&mut processor, &input);
+}
+
+#[test]
+fn transfer_curves_at_zero_threshold_stay_finite() {
+    for character in CHARACTERS {
+        for mode in MODES {
+            let curve = transfer_curve(character, mode, f32::NEG_INFINITY, 20.0, 0.0, 0.0, -200.0, 0.0, 101);
+            assert!(curve.iter().all(|x| x.is_finite()));
+        }
+    }
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use common::{sine, amplitude_at, assert_near, to_db, SAMPLE_RATE, SETTLE, LENGTH};
+use dynamics::{Saturator, DynamicsProcessor, CompressionCharacter, Topology};
+
+fn saturate(saturator: &mut Saturator, freq: f32, amplitude: f32) -> Vec<f32> {
+    let input = sine(freq, amplitude, SETTLE + LENGTH);
+    let mut output = vec![0.0; input.len()];
+    saturator.process_block(&input, &mut output);
+    output.split_off(SETTLE)
+}
+
+#[test]
+fn harmonic_levels_at_full_scale() {
+    let mut saturator = Saturator::new(SAMPLE_RATE);
+    saturator.set_drive(0.1);
+    saturator.set_colour(0.75);
+
+    let output = saturate(&mut saturator, 1000.0, 1.0);
+
+    assert_near(amplitude_at(&output, 2000.0), 0.075, 0.002);
+    assert_near(amplitude_at(&output, 3000.0), 0.025, 0.001);
+    assert!(amplitude_at(&output, 4000.0) < 1e-4);
+}
+
+#[test]
+fn harmonic_levels_scale_with_amplitude() {
+    let mut saturator = Saturator::new(SAMPLE_RATE);
+    saturator.set_drive(0.1);
+    saturator.set_colour(0.5);
+
+    let output = saturate(&mut saturator, 1000.0, 0.5);
+
+    // 2nd harmonic scales with amplitude squared, 3rd with amplitude cubed
+    assert_near(amplitude_at(&output, 2000.0), 0.05 * 0.25, 0.0005);
+    assert_near(amplitude_at(&output, 3000.0), 0.05 * 0.125, 0.0003);
+}
+
+#[test]
+fn colour_extremes_select_even_or_odd() {
+    let mut even = Saturator::new(SAMPLE_RATE);
+    even.set_drive(0.1);
+    even.set_colour(1.0);
+    let output = saturate(&mut even, 1000.0, 1.0);
+    assert!(amplitude_at(&output, 3000.0) < 1e-4);
+
+    let mut odd = Saturator::new(SAMPLE_RATE);
+    odd.set_drive(0.1);
+    odd.set_colour(0.0);
+    let output = saturate(&mut odd, 1000.0, 1.0);
+    assert!(amplitude_at(&output, 2000.0) < 1e-4);
+}
+
+#[test]
+fn oversampling_reduces_aliasing() {
+    // The 3rd harmonic of 15kHz, 45kHz, aliases to 3kHz at 48kHz
+    let alias = |oversampling| {
+        let mut saturator = Saturator::new(SAMPLE_RATE);
+        saturator.set_drive(0.2);
+        saturator.set_colour(0.0);
+        saturator.set_oversampling(oversampling);
+        amplitude_at(&saturate(&mut saturator, 15000.0, 1.0), 3000.0)
+    };
+
+    assert!(to_db(alias(false)) - to_db(alias(true)) > 20.0);
+}
+
+#[test]
+fn vintage_has_predictable_harmonic_ratio() {
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_character(CompressionCharacter::Vintage);
+    // Unity ratio, so that only the character stage shapes the signal
+    processor.set_ratio(1.0);
+
+    let input = sine(1000.0, 1.0, SETTLE + LENGTH);
+    let mut output = vec![0.0; input.len()];
+    processor.process_block(&input, None, &mut output, None);
+    let output = output.split_off(SETTLE);
+
+    let second = amplitude_at(&output, 2000.0);
+    let third = amplitude_at(&output, 3000.0);
+
+    assert_near(second, 0.025, 0.001);
+    assert_near(third, 0.01, 0.0005);
+    assert_near(second / third, 2.5, 0.1);
+}
+
+#[test]
+fn clean_adds_no_harmonics() {
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_character(CompressionCharacter::Clean);
+    processor.set_ratio(1.0);
+
+    let input = sine(1000.0, 1.0, SETTLE + LENGTH);
+    let mut output = vec![0.0; input.len()];
+    processor.process_block(&input, None, &mut output, None);
+    let output = output.split_off(SETTLE);
+
+    assert!(amplitude_at(&output, 2000.0) < 1e-5);
+    assert!(amplitude_at(&output, 3000.0) < 1e-5);
+}
+
+#[test]
+fn explicit_saturation_and_topology_survive_character_changes() {
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_drive(0.05);
+    processor.set_topology(Topology::Hybrid);
+    processor.set_character(CompressionCharacter::Vintage);
+
+    let settings = processor.settings();
+    assert_eq!(settings.drive, 0.05);
+    assert!(settings.topology == Topology::Hybrid);
+    // Colour was never set, so it follows the character
+    let vintage_colour = settings.colour;
+    processor.set_character(CompressionCharacter::Smooth);
+    assert_eq!(processor.settings().drive, 0.05);
+    assert!(processor.settings().colour != vintage_colour);
+
+    // Restoring the character's defaults forgets the overrides
+    processor.use_character_defaults();
+    let settings = processor.settings();
+    assert!(settings.topology == Topology::FeedBack);
+    assert!(settings.drive != 0.05);
+
+    // Settings restore overrides but leave character values following it
+    let mut restored = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_drive(0.05);
+    restored.set_settings(&processor.settings());
+    restored.set_character(CompressionCharacter::Vintage);
+    assert_eq!(restored.settings().drive, 0.05);
+    assert!(restored.settings().topology == Topology::FeedBack);
+}
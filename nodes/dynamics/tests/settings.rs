@@ -0,0 +1,57 @@
+mod common;
+
+use common::{sine, SAMPLE_RATE};
+use dynamics::{DynamicsProcessor, DynamicsSettings, PRESET_NAMES};
+
+/// Process a tone rising from quiet to loud, keyed by a tone at another
+/// frequency, and return the output
+fn process(processor: &mut DynamicsProcessor) -> Vec<f32> {
+    let input: Vec<f32> = sine(1000.0, 1.0, 9600).into_iter().enumerate()
+        .map(|(i, x)| x * i as f32 / 9600.0)
+        .collect();
+    let key = sine(300.0, 0.5, input.len());
+    let mut output = vec![0.0; input.len()];
+    processor.process_block(&input, Some(&key), &mut output, None);
+    output
+}
+
+#[test]
+fn presets_round_trip_through_json() {
+    for name in PRESET_NAMES {
+        let mut original = DynamicsProcessor::new(SAMPLE_RATE);
+        assert!(original.apply_preset(name), "{name} is not a preset");
+        let json = original.get_settings();
+
+        let mut restored = DynamicsProcessor::new(SAMPLE_RATE);
+        restored.apply_settings(&json).unwrap();
+
+        assert_eq!(restored.get_settings(), json, "{name} settings changed");
+        assert_eq!(process(&mut restored), process(&mut original), "{name} output changed");
+    }
+}
+
+#[test]
+fn unknown_preset_is_rejected() {
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    let json = processor.get_settings();
+
+    assert!(DynamicsSettings::preset("no-such-preset").is_none());
+    assert!(!processor.apply_preset("no-such-preset"));
+    assert_eq!(processor.get_settings(), json);
+}
+
+#[test]
+fn missing_settings_take_defaults() {
+    // Settings left from a preset are replaced by defaults, not kept
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.apply_preset("mastering-limiter");
+    processor.apply_settings(r#"{"thresholdDb": -30.0, "ratio": 8.0}"#).unwrap();
+
+    let expected = DynamicsSettings { threshold_db: -30.0, ratio: 8.0, ..DynamicsSettings::default() };
+    assert_eq!(processor.get_settings(), expected.to_json());
+}
+
+#[test]
+fn defaults_are_those_of_a_new_processor() {
+    assert_eq!(DynamicsProcessor::new(SAMPLE_RATE).get_settings(), DynamicsSettings::default().to_json());
+}
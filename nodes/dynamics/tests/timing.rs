@@ -0,0 +1,98 @@
+mod common;
+
+use common::{to_db, assert_near, SAMPLE_RATE};
+use dynamics::{EnvelopeFollower, DynamicsProcessor, DetectionMode};
+
+/// Samples for an envelope to pass from 10% to 90% of a step, or from 90%
+/// to 10% when falling
+fn rise_samples(trace: &[f32], from: f32, to: f32) -> f32 {
+    let level = |fraction: f32| from + (to - from) * fraction;
+    let passed = |fraction: f32| trace.iter()
+        .position(|&x| if to > from { x >= level(fraction) } else { x <= level(fraction) })
+        .unwrap() as f32;
+    passed(0.9) - passed(0.1)
+}
+
+fn follower(attack: f32, release: f32) -> EnvelopeFollower {
+    let mut follower = EnvelopeFollower::new(SAMPLE_RATE);
+    follower.set_detection_mode(DetectionMode::Peak);
+    follower.set_attack_time(attack);
+    follower.set_release_time(release);
+    follower
+}
+
+#[test]
+fn attack_times_are_10_to_90_percent_rise_times() {
+    for attack in [0.001, 0.01, 0.1] {
+        let mut follower = follower(attack, 1.0);
+        let trace: Vec<f32> = (0..(attack * SAMPLE_RATE * 4.0) as usize).map(|_| follower.process(1.0)).collect();
+
+        // ln(9) / 2.2 of the set time, within a sample and the precision
+        // of the coefficient
+        let expected = attack * SAMPLE_RATE * 9.0f32.ln() / 2.2;
+        assert_near(rise_samples(&trace, 0.0, 1.0), expected, 1.0 + expected * 1e-3);
+    }
+}
+
+#[test]
+fn release_times_are_90_to_10_percent_fall_times() {
+    for release in [0.01, 0.1, 1.0] {
+        let mut follower = follower(0.0, release);
+        follower.process(1.0);
+        let trace: Vec<f32> = (0..(release * SAMPLE_RATE * 4.0) as usize).map(|_| follower.process(0.0)).collect();
+
+        let expected = release * SAMPLE_RATE * 9.0f32.ln() / 2.2;
+        assert_near(rise_samples(&trace, 1.0, 0.0), expected, 1.0 + expected * 1e-3);
+    }
+}
+
+fn limiter(lookahead_ms: f32) -> DynamicsProcessor {
+    let mut processor = DynamicsProcessor::new(SAMPLE_RATE);
+    processor.set_detection_mode(DetectionMode::Peak);
+    processor.set_threshold_db(-20.0);
+    processor.set_ratio(20.0);
+    processor.set_knee_db(0.0);
+    processor.set_attack_time(0.001);
+    processor.set_release_time(0.1);
+    processor.set_lookahead_ms(lookahead_ms);
+    processor
+}
+
+#[test]
+fn lookahead_delays_by_its_length() {
+    for (lookahead_ms, delay) in [(0.0, 0), (1.5, 72), (5.0, 240)] {
+        let mut processor = limiter(lookahead_ms);
+        processor.set_ratio(1.0);
+
+        let mut input = vec![0.0; 1000];
+        input[100] = 0.5;
+        let mut output = vec![0.0; input.len()];
+        processor.process_block(&input, None, &mut output, None);
+
+        let peak = output.iter().position(|&x| x != 0.0).unwrap();
+        assert_eq!(peak, 100 + delay);
+        assert_near(output[peak], 0.5, 1e-6);
+    }
+}
+
+#[test]
+fn lookahead_reduces_gain_before_the_transient() {
+    let onset = 1000;
+    let mut input = vec![0.0; 4000];
+    input[onset..].fill(1.0);
+
+    let first_output_db = |lookahead_ms: f32| {
+        let mut processor = limiter(lookahead_ms);
+        let mut output = vec![0.0; input.len()];
+        processor.process_block(&input, None, &mut output, None);
+        let arrival = output.iter().position(|&x| x != 0.0).unwrap();
+        assert_eq!(arrival, onset + (lookahead_ms * 0.001 * SAMPLE_RATE) as usize);
+        to_db(output[arrival])
+    };
+
+    // 20dB over threshold at 20:1 settles at -19dB. Without lookahead the
+    // transient passes at full level, with lookahead of a few attack times
+    // it arrives at the settled gain.
+    assert_near(first_output_db(0.0), 0.0, 0.1);
+    assert_near(first_output_db(5.0), -19.0, 0.2);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use common::{sine, to_db, assert_near, SAMPLE_RATE};
+use dynamics::TransientShaper;
+
+fn samples(ms: f32) -> usize {
+    (ms * 0.001 * SAMPLE_RATE) as usize
+}
+
+fn shaper(attack_db: f32, sustain_db: f32) -> TransientShaper {
+    let mut shaper = TransientShaper::new(SAMPLE_RATE);
+    shaper.set_attack(attack_db);
+    shaper.set_sustain(sustain_db);
+    shaper
+}
+
+/// Run steps of a 1kHz sine, each (amplitude, duration in ms), and return
+/// the shaper's gain in dB per sample
+fn gain_trace_db(shaper: &mut TransientShaper, steps: &[(f32, f32)]) -> Vec<f32> {
+    steps.iter()
+        .flat_map(|&(amplitude, duration)| sine(1000.0, amplitude, samples(duration)))
+        .map(|x| {
+            shaper.process_sample(x);
+            to_db(shaper.get_gain())
+        })
+        .collect()
+}
+
+fn max(trace: &[f32]) -> f32 {
+    trace.iter().fold(f32::MIN, |a, &b| a.max(b))
+}
+
+fn min(trace: &[f32]) -> f32 {
+    trace.iter().fold(f32::MAX, |a, &b| a.min(b))
+}
+
+#[test]
+fn attack_boost_lifts_onsets() {
+    let trace = gain_trace_db(&mut shaper(12.0, 0.0), &[(0.0, 100.0), (0.2, 500.0)]);
+    let onset = &trace[samples(100.0)..samples(150.0)];
+
+    assert!(max(onset) > 6.0, "expected an attack boost, got {}dB", max(onset));
+    // A steady tone is neither attack nor sustain
+    assert_near(trace[trace.len() - 1], 0.0, 0.5);
+}
+
+#[test]
+fn attack_cut_softens_onsets() {
+    let trace = gain_trace_db(&mut shaper(-12.0, 0.0), &[(0.0, 100.0), (0.2, 500.0)]);
+    let onset = &trace[samples(100.0)..samples(150.0)];
+
+    assert!(min(onset) < -6.0, "expected an attack cut, got {}dB", min(onset));
+}
+
+#[test]
+fn sustain_cut_shortens_tails() {
+    let trace = gain_trace_db(&mut shaper(0.0, -12.0), &[(0.5, 500.0), (0.05, 300.0)]);
+    let tail = &trace[samples(500.0)..samples(600.0)];
+
+    assert!(min(tail) < -6.0, "expected a sustain cut, got {}dB", min(tail));
+    // Attack is not boosted, so the onset of the tone is left alone
+    assert!(max(&trace[..samples(500.0)]) <= 0.0);
+}
+
+#[test]
+fn no_boost_passes_unchanged() {
+    let mut shaper = shaper(0.0, 0.0);
+    let input = sine(1000.0, 0.5, samples(500.0));
+    let mut output = vec![0.0; input.len()];
+    shaper.process_block(&input, &mut output);
+
+    assert_eq!(input, output);
+}
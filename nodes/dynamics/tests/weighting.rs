@@ -0,0 +1,134 @@
+mod common;
+
+use common::{sine, amplitude_at, to_db, assert_near, SAMPLE_RATE, SETTLE, LENGTH};
+use dynamics::{EnvelopeFollower, FrequencyWeighting, DetectionMode, DetectionSource, MultichannelDynamics, LinkMode};
+
+/// Gain in dB of a weighting at a frequency
+fn weighting_db(weighting: FrequencyWeighting, freq: f32) -> f32 {
+    let mut follower = EnvelopeFollower::new(SAMPLE_RATE);
+    follower.set_weighting(weighting);
+
+    let weighted: Vec<f32> = sine(freq, 0.5, SETTLE + LENGTH).into_iter().map(|x| follower.weight(x)).collect();
+    to_db(amplitude_at(&weighted[SETTLE..], freq) / 0.5)
+}
+
+/// Magnitude in dB of a biquad with normalised coefficients
+fn biquad_db(b: [f64; 3], a: [f64; 3], freq: f64) -> f64 {
+    let w = 2.0 * std::f64::consts::PI * freq / SAMPLE_RATE as f64;
+    let response = |c: [f64; 3]| {
+        let re = c[0] + c[1] * w.cos() + c[2] * (2.0 * w).cos();
+        let im = -c[1] * w.sin() - c[2] * (2.0 * w).sin();
+        re.hypot(im)
+    };
+    20.0 * (response(b) / response(a)).log10()
+}
+
+// The bilinear transform compresses frequencies towards Nyquist, so that
+// at 48kHz the 12.2kHz pole of the A and C weightings attenuates 10kHz
+// about 0.6dB less than the analogue curve, well within the class 1
+// tolerance of IEC 61672
+
+#[test]
+fn a_weighting_matches_iec_61672() {
+    assert_near(weighting_db(FrequencyWeighting::A, 100.0), -19.1, 0.1);
+    assert_near(weighting_db(FrequencyWeighting::A, 1000.0), 0.0, 0.05);
+    assert_near(weighting_db(FrequencyWeighting::A, 10000.0), -2.5, 0.7);
+}
+
+#[test]
+fn c_weighting_matches_iec_61672() {
+    assert_near(weighting_db(FrequencyWeighting::C, 100.0), -0.3, 0.1);
+    assert_near(weighting_db(FrequencyWeighting::C, 1000.0), 0.0, 0.05);
+    assert_near(weighting_db(FrequencyWeighting::C, 10000.0), -4.4, 0.7);
+}
+
+#[test]
+fn k_weighting_matches_bs_1770() {
+    // The 48kHz coefficients tabulated in BS.1770-4
+    let shelf = ([1.53512485958697, -2.69169618940638, 1.19839281085285], [1.0, -1.69065929318241, 0.73248077421585]);
+    let highpass = ([1.0, -2.0, 1.0], [1.0, -1.99004745483398, 0.99007225036621]);
+
+    for freq in [100.0, 1000.0, 10000.0] {
+        let reference = biquad_db(shelf.0, shelf.1, freq as f64) + biquad_db(highpass.0, highpass.1, freq as f64);
+        assert_near(weighting_db(FrequencyWeighting::K, freq), reference as f32, 0.01);
+    }
+}
+
+#[test]
+fn flat_weighting_passes_unchanged() {
+    for freq in [100.0, 1000.0, 10000.0] {
+        assert_near(weighting_db(FrequencyWeighting::Flat, freq), 0.0, 0.001);
+    }
+}
+
+/// Follow a signal with instant ballistics and return the envelope
+fn follow(mode: DetectionMode, input: &[f32]) -> Vec<f32> {
+    let mut follower = EnvelopeFollower::new(SAMPLE_RATE);
+    follower.set_detection_mode(mode);
+    follower.set_attack_time(0.0);
+    follower.set_release_time(0.0);
+    input.iter().map(|&x| follower.process(x)).collect()
+}
+
+#[test]
+fn adaptive_detects_rms_of_steady_signals() {
+    // A sine's crest factor is 3dB, so adaptive detection reads its RMS
+    let input = sine(1000.0, 0.5, SETTLE);
+    let adaptive = follow(DetectionMode::Adaptive, &input);
+    let rms = follow(DetectionMode::RMS, &input);
+
+    assert_near(adaptive[SETTLE - 1], rms[SETTLE - 1], 0.001);
+    assert_near(adaptive[SETTLE - 1], 0.354, 0.01);
+}
+
+#[test]
+fn adaptive_detects_peaks_of_transients() {
+    // Clicks every 100ms have a crest factor far above 15dB, so adaptive
+    // detection reads their peaks, which RMS averages away
+    let input: Vec<f32> = (0..SETTLE).map(|i| if i % 4800 == 0 { 0.5 } else { 0.0 }).collect();
+    let max = |trace: Vec<f32>| trace.into_iter().fold(0.0, f32::max);
+
+    assert_near(max(follow(DetectionMode::Adaptive, &input)), 0.5, 0.01);
+    assert!(max(follow(DetectionMode::RMS, &input)) < 0.1);
+}
+
+#[test]
+fn log_rms_detects_the_geometric_mean() {
+    // Alternating levels 20dB apart, whose RMS is nearly the louder level
+    // less 3dB, and whose mean level in dB is halfway between
+    let input: Vec<f32> = (0..SETTLE).map(|i| if i % 2 == 0 { 0.5 } else { 0.05 }).collect();
+
+    assert_near(follow(DetectionMode::LogRMS, &input)[SETTLE - 1], (0.5f32 * 0.05).sqrt(), 0.001);
+    assert_near(follow(DetectionMode::RMS, &input)[SETTLE - 1], ((0.25 + 0.0025) / 2.0f32).sqrt(), 0.001);
+}
+
+/// Gain reduction in dB of both channels of a stereo processor detecting
+/// from `source`, for a signal wholly in the side channel
+fn side_signal_gains_db(source: DetectionSource) -> (f32, f32) {
+    let mut processor = MultichannelDynamics::new(SAMPLE_RATE, 2);
+    processor.set_link_mode(LinkMode::Independent);
+    processor.set_detection_source(source);
+    processor.set_threshold(0.1);
+
+    let left = sine(1000.0, 0.5, SETTLE);
+    let right: Vec<f32> = left.iter().map(|x| -x).collect();
+    let input = [left, right].concat();
+    let mut output = vec![0.0; input.len()];
+    let mut reduction = vec![0.0; input.len()];
+    processor.process_block(&input, &[], &mut output, &mut reduction);
+
+    (to_db(reduction[SETTLE - 1]), to_db(reduction[2 * SETTLE - 1]))
+}
+
+#[test]
+fn detection_source_selects_mid_or_side() {
+    // Mid of an out of phase pair is silent
+    let (left, right) = side_signal_gains_db(DetectionSource::Mid);
+    assert_near(left, 0.0, 0.001);
+    assert_near(right, 0.0, 0.001);
+
+    // Side holds all of it, and both channels detect from it
+    let (left, right) = side_signal_gains_db(DetectionSource::Side);
+    assert!(left < -3.0, "expected reduction, got {left}dB");
+    assert_near(left, right, 0.001);
+}
@@ -39,7 +39,13 @@ export default class Enveloper extends AudioWorkletNode {
             detectionMode: 'detectionMode',
             filterEnabled: 'filterEnabled',
             filterFreq: 'filterFreq',
-            filterQ: 'filterQ'
+            filterQ: 'filterQ',
+            // Output mapping
+            scale: 'scale',
+            floor: 'floor',
+            min: 'min',
+            max: 'max',
+            curve: 'curve'
         };
 
         // Set initial parameters from options
@@ -59,10 +65,16 @@ export default class Enveloper extends AudioWorkletNode {
         });
     }
 
+    // Average output over the last block, the envelope mapped to the
+    // output range
     get envelope() {
         return this.#envelope;
     }
 
+    // The output is a modulation signal. Connect it to an AudioParam with
+    // a value of 0 and set min and max in the param's units, for example
+    // { scale: 'db', min: 200, max: 4000, curve: 2 } to sweep a filter
+    // cutoff in Hz as the level rises from the floor to 0dBFS.
     setParameter(name, value) {
         this.port.postMessage({ type: 'param', name, value });
     }
@@ -82,13 +94,23 @@ export default class Enveloper extends AudioWorkletNode {
         attack: { min: 0.001, max: 1, default: 0.003, law: 'log-36db', unit: 's' },
         release: { min: 0.01, max: 2, default: 0.1, law: 'log-36db', unit: 's' },
         detectionMode: { 
-            options: ['peak', 'rms', 'logrms'],
+            options: ['peak', 'rms', 'logrms', 'adaptive'],
             default: 'rms'
         },
         filterEnabled: { type: 'boolean', default: false },
         filterFreq: { min: 20, max: 20000, default: 1000, law: 'log', unit: 'Hz' },
         filterQ: { min: 0.1, max: 10, default: 0.7 },
         
+        // Output mapping parameters
+        scale: {
+            options: ['linear', 'db'],
+            default: 'linear'
+        },
+        floor: { min: -120, max: -1, default: -60, unit: 'dB' },
+        min: { default: 0 },
+        max: { default: 1 },
+        curve: { min: 0.1, max: 10, default: 1, law: 'log' },
+        
         // Read-only property
         envelope: { readonly: true }
     };
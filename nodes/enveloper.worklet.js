@@ -1,5 +1,6 @@
 // Enveloper AudioWorklet
-// This processor extracts the amplitude envelope from audio signals
+// This processor extracts the amplitude envelope from audio signals, and
+// outputs it scaled and mapped to a range as a modulation signal
 
 class EnveloperProcessor extends AudioWorkletProcessor {
     constructor(options) {
@@ -8,20 +9,33 @@ class EnveloperProcessor extends AudioWorkletProcessor {
         // Process options
         this.options = options.processorOptions || {};
         this.wasmModule = null;
-        this.follower = null;
+        this.wasm = null;
+        this.followers = [];           // One per channel
         
         // Default values
         this.attack = 0.003;           // seconds
         this.release = 0.1;            // seconds
-        this.detectionMode = 1;        // 0=peak, 1=RMS, 2=logRMS
+        this.detectionMode = 1;        // 0=peak, 1=RMS, 2=logRMS, 3=adaptive
         this.filterEnabled = false;
         this.filterFreq = 1000;        // Hz
         this.filterQ = 0.7;
+        this.scale = 0;                // 0=linear, 1=dB from floor to 0dBFS
+        this.floor = -60;              // dB
+        this.min = 0;                  // Output at the foot of the scale
+        this.max = 1;                  // Output at the top of the scale
+        this.curve = 1;                // Exponent shaping the scaled envelope
         
         // Envelope value for metering
         this.currentEnvelope = 0;
         this.meterUpdateCounter = 0;
         
+        // Views of each follower's shared buffers in wasm memory. Passing
+        // arrays to process_block instead would copy them through
+        // __wbindgen_malloc every block.
+        this.blockLength = 0;
+        this.inputBuffers = [];
+        this.outputBuffers = [];
+        
         // Setup message handling
         this.port.onmessage = (e) => {
             if (e.data instanceof ArrayBuffer) {
@@ -49,13 +63,13 @@ class EnveloperProcessor extends AudioWorkletProcessor {
             const wasm = wasmModule.instance.exports;
             
             this.wasmModule = wasmModule;
+            this.wasm = wasm;
             
-            // Create the envelope follower
-            // Reuse the envelope follower from the dynamics processor
-            this.follower = wasm.EnvelopeFollower.new(sampleRate);
-            
-            // Set default parameters
-            this.updateAllParameters();
+            // Create envelope followers for a stereo input, reusing the
+            // envelope follower from the dynamics processor. More are
+            // added when more channels arrive.
+            this.addFollower();
+            this.addFollower();
             
             // Notify that module is loaded
             this.port.postMessage({ type: 'wasm-module-loaded' });
@@ -68,58 +82,109 @@ class EnveloperProcessor extends AudioWorkletProcessor {
         }
     }
     
-    updateAllParameters() {
-        if (!this.follower) return;
-        
-        this.follower.set_attack_time(this.attack);
-        this.follower.set_release_time(this.release);
-        this.follower.set_detection_mode(this.detectionMode);
-        this.follower.set_filter_enabled(this.filterEnabled);
-        this.follower.set_filter_freq(this.filterFreq);
-        this.follower.set_filter_q(this.filterQ);
+    addFollower() {
+        const follower = this.wasm.EnvelopeFollower.new(sampleRate);
+        this.updateAllParameters(follower);
+        if (this.blockLength) follower.set_block_size(this.blockLength);
+        this.followers.push(follower);
+    }
+    
+    mapBuffers() {
+        const memory = this.wasm.memory.buffer;
+        const length = this.blockLength;
+        this.inputBuffers = this.followers.map((follower) => new Float32Array(memory, follower.input_ptr(), length));
+        this.outputBuffers = this.followers.map((follower) => new Float32Array(memory, follower.output_ptr(), length));
+    }
+    
+    updateAllParameters(follower) {
+        follower.set_attack_time(this.attack);
+        follower.set_release_time(this.release);
+        follower.set_detection_mode(this.detectionMode);
+        follower.set_filter_enabled(this.filterEnabled);
+        follower.set_filter_freq(this.filterFreq);
+        follower.set_filter_q(this.filterQ);
+        follower.set_output_scale(this.scale);
+        follower.set_output_floor_db(this.floor);
+        follower.set_output_range(this.min, this.max);
+        follower.set_output_curve(this.curve);
     }
     
     updateParameter(name, value) {
-        if (!this.follower) return;
+        // Parameters are kept for followers added later, including those
+        // set before the WASM module has loaded
+        const followers = this.followers.length ? this.followers : [null];
         
+        for (const follower of followers) {
+            this.setFollowerParameter(follower, name, value);
+        }
+    }
+    
+    setFollowerParameter(follower, name, value) {
         switch (name) {
             case 'attack':
                 this.attack = value;
-                this.follower.set_attack_time(value);
+                follower?.set_attack_time(value);
                 break;
                 
             case 'release':
                 this.release = value;
-                this.follower.set_release_time(value);
+                follower?.set_release_time(value);
                 break;
                 
             case 'detectionMode':
                 // Convert detection mode string to enum index
-                const detectionMap = { 'peak': 0, 'rms': 1, 'logrms': 2 };
-                this.detectionMode = detectionMap[value] || 1;
-                this.follower.set_detection_mode(this.detectionMode);
+                const detectionMap = { 'peak': 0, 'rms': 1, 'logrms': 2, 'adaptive': 3 };
+                this.detectionMode = detectionMap[value] ?? 1;
+                follower?.set_detection_mode(this.detectionMode);
                 break;
                 
             case 'filterEnabled':
                 this.filterEnabled = !!value;
-                this.follower.set_filter_enabled(this.filterEnabled);
+                follower?.set_filter_enabled(this.filterEnabled);
                 break;
                 
             case 'filterFreq':
                 this.filterFreq = value;
-                this.follower.set_filter_freq(value);
+                follower?.set_filter_freq(value);
                 break;
                 
             case 'filterQ':
                 this.filterQ = value;
-                this.follower.set_filter_q(value);
+                follower?.set_filter_q(value);
+                break;
+                
+            case 'scale':
+                // Convert scale string to enum index
+                const scaleMap = { 'linear': 0, 'db': 1 };
+                this.scale = scaleMap[value] || 0;
+                follower?.set_output_scale(this.scale);
+                break;
+                
+            case 'floor':
+                this.floor = value; // dB
+                follower?.set_output_floor_db(value);
+                break;
+                
+            case 'min':
+                this.min = value;
+                follower?.set_output_range(this.min, this.max);
+                break;
+                
+            case 'max':
+                this.max = value;
+                follower?.set_output_range(this.min, this.max);
+                break;
+                
+            case 'curve':
+                this.curve = value;
+                follower?.set_output_curve(value);
                 break;
         }
     }
     
     process(inputs, outputs, parameters) {
         // If WASM module isn't loaded yet, pass audio through
-        if (!this.follower) {
+        if (!this.wasm) {
             // Pass through
             if (inputs[0] && outputs[0]) {
                 for (let channel = 0; channel < inputs[0].length; channel++) {
@@ -138,6 +203,23 @@ class EnveloperProcessor extends AudioWorkletProcessor {
         
         // Number of channels to process (mono or stereo)
         const channels = Math.min(input.length, output.length);
+        if (!channels) return true;
+        
+        const length = input[0].length;
+        const added = this.followers.length < channels;
+        
+        while (this.followers.length < channels) {
+            this.addFollower();
+        }
+        
+        if (this.blockLength !== length) {
+            this.blockLength = length;
+            this.followers.forEach((follower) => follower.set_block_size(length));
+            this.mapBuffers();
+        } else if (added || this.inputBuffers[0].length === 0) {
+            // Views are detached when wasm memory grows
+            this.mapBuffers();
+        }
         
         // Process each channel
         let totalEnvelope = 0;
@@ -148,16 +230,15 @@ class EnveloperProcessor extends AudioWorkletProcessor {
             
             if (!inputChannel || !outputChannel) continue;
             
-            // Process each sample
-            for (let i = 0; i < inputChannel.length; i++) {
-                // Get envelope through WASM envelope follower
-                const envelopeValue = this.follower.process(inputChannel[i]);
-                
-                // Output envelope as DC offset signal for visualization/use
-                outputChannel[i] = envelopeValue;
-                
-                // Track envelope for metering
-                totalEnvelope += envelopeValue;
+            // Output the mapped envelope as a DC offset signal, to drive
+            // params or for visualisation
+            this.inputBuffers[channel].set(inputChannel);
+            this.followers[channel].process_buffers();
+            outputChannel.set(this.outputBuffers[channel]);
+            
+            // Track envelope for metering
+            for (let i = 0; i < outputChannel.length; i++) {
+                totalEnvelope += outputChannel[i];
             }
         }
         
@@ -0,0 +1,90 @@
+/**
+DynamicEQ(transport, settings)
+Creates a dynamic equalizer with four bands, low, lomid, himid and high,
+whose gains move with the level in their band, for taming resonances that
+only ring on some notes.
+**/
+
+import NodeObject from '../modules/node-object.js';
+import DynamicEqNode from '../nodes/dynamic-eq.js';
+
+const define = Object.defineProperties;
+
+// Band parameter values of each object
+const values = new WeakMap();
+
+const bands = {
+    low:   { index: 0, shape: 'lowshelf',  freq: 120 },
+    lomid: { index: 1, shape: 'bell',      freq: 400 },
+    himid: { index: 2, shape: 'bell',      freq: 2500 },
+    high:  { index: 3, shape: 'highshelf', freq: 8000 }
+};
+
+// Object property suffixes and the band parameters they set
+const params = {
+    type:      'shape',
+    mode:      'mode',
+    freq:      'frequency',
+    Q:         'q',
+    gain:      'gain',
+    threshold: 'threshold',
+    ratio:     'ratio',
+    range:     'range',
+    attack:    'attack',
+    release:   'release'
+};
+
+export default class DynamicEQ extends NodeObject {
+    constructor(transport, settings = {}) {
+        const node = new DynamicEqNode(transport.context, { bandCount: 4 });
+        super(transport, node);
+        values.set(this, {});
+
+        for (const name in bands) {
+            const band = bands[name];
+            for (const suffix in params) {
+                const property = name + '-' + suffix;
+                const value = property in settings ? settings[property] :
+                    suffix === 'type' ? band.shape :
+                    suffix === 'freq' ? band.freq :
+                    DynamicEQ.config[property].default ;
+
+                this[property] = value;
+            }
+        }
+    }
+
+    // Current gain of each band in dB
+    get gains() {
+        return this.node.gains;
+    }
+
+    static preload = DynamicEqNode.preload;
+
+    static config = {};
+}
+
+// Define band properties, and their config from the node's band config
+for (const name in bands) {
+    const band = bands[name];
+
+    for (const suffix in params) {
+        const property = name + '-' + suffix;
+        const param = params[suffix];
+
+        DynamicEQ.config[property] = DynamicEqNode.config[param];
+
+        define(DynamicEQ.prototype, {
+            [property]: {
+                get: function() { return values.get(this)[property]; },
+                set: function(value) {
+                    values.get(this)[property] = value;
+                    this.node.setBandParameter(band.index, param, value);
+                },
+                enumerable: true
+            }
+        });
+    }
+}
+
+DynamicEQ.config.gains = { readonly: true };
@@ -1,6 +1,7 @@
 /**
 Enveloper(transport, settings)
-Creates an envelope follower that tracks the amplitude envelope of an audio signal.
+Creates an envelope follower that tracks the amplitude envelope of an audio signal,
+and outputs it mapped to a range as a modulation signal for driving params.
 **/
 
 import NodeObject from '../modules/node-object.js';
@@ -22,6 +23,13 @@ export default class Enveloper extends NodeObject {
         filterFreq:    { min: 20,  max: 20000, defaultValue: 1000, law: 'log', unit: 'Hz' },
         filterQ:       { min: 0.1, max: 10, defaultValue: 0.7 },
 
+        // Output mapping
+        scale:         { options: ['linear', 'db'], defaultValue: 'linear' },
+        floor:         { min: -120, max: -1, defaultValue: -60, unit: 'dB' },
+        min:           { defaultValue: 0 },
+        max:           { defaultValue: 1 },
+        curve:         { min: 0.1, max: 10, defaultValue: 1, law: 'log' },
+
         // Read-only property
         envelope: { readonly: true }
     };